@@ -0,0 +1,211 @@
+// Runtime helpers for mdbook-admonish.
+//
+// Installed by `mdbook-admonish install --js`, and loaded by mdbook through
+// `output.html.additional-js`.
+(function () {
+  "use strict";
+
+  // Keyboard and focus management for collapsible admonitions.
+  //
+  // Native `<details>` elements are keyboard operable, but once styled they no
+  // longer expose their state reliably to assistive technology. We:
+  // - mirror the open state onto the summary as `aria-expanded`
+  // - close the admonition on `Escape` from anywhere inside it
+  // - return focus to the summary when closing would hide the focused element
+  //
+  // The open state is remembered per page and block id, so readers working
+  // through a long chapter keep their expanded blocks when navigating away.
+  function initCollapsible(details) {
+    const summary = details.querySelector(":scope > summary.admonition-title");
+    if (summary === null) {
+      return;
+    }
+
+    const key = details.id
+      ? `mdbook-admonish-open:${location.pathname}#${details.id}`
+      : null;
+    const saved = key === null ? null : load(key);
+    if (typeof saved === "boolean") {
+      details.open = saved;
+    }
+
+    const syncExpanded = () => {
+      summary.setAttribute("aria-expanded", details.open ? "true" : "false");
+    };
+    syncExpanded();
+
+    details.addEventListener("toggle", () => {
+      syncExpanded();
+      if (key !== null) {
+        save(key, details.open);
+      }
+      if (details.open) {
+        refreshEditors(details);
+      }
+      if (!details.open && details.contains(document.activeElement)) {
+        summary.focus();
+      }
+    });
+
+    details.addEventListener("keydown", (event) => {
+      if (event.key !== "Escape" || !details.open) {
+        return;
+      }
+      // Only close the innermost open admonition
+      event.stopPropagation();
+      details.open = false;
+      summary.focus();
+    });
+  }
+
+  // mdbook sets up editable Rust playgrounds on page load, when editors inside
+  // a closed admonition have no size. Redraw them once they are shown.
+  function refreshEditors(details) {
+    (window.editors || []).forEach((editor) => {
+      if (details.contains(editor.container)) {
+        editor.resize();
+        editor.renderer.updateFull();
+      }
+    });
+  }
+
+  // Deep links into collapsed admonitions.
+  //
+  // Navigating to an anchor inside a closed `<details>` would otherwise land
+  // on an invisible element. Open every admonition enclosing the target, then
+  // scroll to it once it is shown.
+  function revealTarget() {
+    const id = decodeURIComponent(location.hash.slice(1));
+    const target = id ? document.getElementById(id) : null;
+    if (target === null) {
+      return;
+    }
+    let revealed = false;
+    for (
+      let details = target.closest("details.admonition");
+      details !== null;
+      details = details.parentElement.closest("details.admonition")
+    ) {
+      if (!details.open) {
+        details.open = true;
+        revealed = true;
+      }
+    }
+    if (revealed) {
+      target.scrollIntoView();
+    }
+  }
+
+  // Printing.
+  //
+  // Collapsed admonitions would print as empty boxes, so open them all while
+  // printing, and restore their state afterwards. mdbook's print page prints
+  // on load, and is only for printing, so blocks there are opened for good.
+  function initPrinting() {
+    const collapsed = () =>
+      Array.from(document.querySelectorAll("details.admonition:not([open])"));
+    if (/\/print\.html$/.test(location.pathname)) {
+      collapsed().forEach((details) => {
+        details.open = true;
+      });
+      return;
+    }
+    let opened = [];
+    window.addEventListener("beforeprint", () => {
+      opened = collapsed();
+      opened.forEach((details) => {
+        details.open = true;
+      });
+    });
+    window.addEventListener("afterprint", () => {
+      opened.forEach((details) => {
+        details.open = false;
+      });
+      opened = [];
+    });
+  }
+
+  // Copy anchor buttons, rendered with `copy_anchor_button`.
+  //
+  // Buttons are hidden until enabled here, as they do nothing otherwise.
+  function initCopyAnchor(button) {
+    if (!navigator.clipboard) {
+      return;
+    }
+    button.hidden = false;
+    button.addEventListener("click", (event) => {
+      // Don't toggle a collapsible admonition
+      event.preventDefault();
+      const url = new URL(button.dataset.anchor, location.href).href;
+      navigator.clipboard.writeText(url).then(
+        () => {
+          button.classList.add("admonition-copied");
+          setTimeout(() => button.classList.remove("admonition-copied"), 2000);
+        },
+        () => {
+          // Nothing to do, the anchor link can still be copied
+        }
+      );
+    });
+  }
+
+  // Clickable task lists.
+  //
+  // mdbook renders task list items as disabled checkboxes. Inside admonitions
+  // we enable them, and remember their state per page and block id, so readers
+  // can tick off steps as they go.
+  function initTaskList(admonition) {
+    if (!admonition.id) {
+      return;
+    }
+    // Checkboxes in nested admonitions belong to those admonitions
+    const checkboxes = Array.from(
+      admonition.querySelectorAll('input[type="checkbox"][disabled]')
+    ).filter((checkbox) => checkbox.closest(".admonition") === admonition);
+    if (checkboxes.length === 0) {
+      return;
+    }
+
+    const key = `mdbook-admonish-tasks:${location.pathname}#${admonition.id}`;
+    const saved = load(key);
+    if (Array.isArray(saved) && saved.length === checkboxes.length) {
+      checkboxes.forEach((checkbox, index) => {
+        checkbox.checked = saved[index];
+      });
+    }
+
+    checkboxes.forEach((checkbox) => {
+      checkbox.disabled = false;
+      checkbox.addEventListener("change", () => {
+        save(key, checkboxes.map((checkbox) => checkbox.checked));
+      });
+    });
+  }
+
+  // Storage may be unavailable, such as in private browsing or sandboxed
+  // iframes. Blocks and task lists then still work, but are not remembered.
+  function load(key) {
+    try {
+      return JSON.parse(localStorage.getItem(key));
+    } catch (error) {
+      return null;
+    }
+  }
+
+  function save(key, value) {
+    try {
+      localStorage.setItem(key, JSON.stringify(value));
+    } catch (error) {
+      // Nothing to do, state is kept for this page view only
+    }
+  }
+
+  document.querySelectorAll("details.admonition").forEach(initCollapsible);
+  document.querySelectorAll(".admonition").forEach(initTaskList);
+  document
+    .querySelectorAll("button.admonition-copy-anchor")
+    .forEach(initCopyAnchor);
+  initPrinting();
+  revealTarget();
+  window.addEventListener("hashchange", revealTarget);
+})();
@@ -0,0 +1,226 @@
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use mdbook_admonish::{check_book, Diagnostic};
+use serde_json::{json, Value};
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// How to print problems found by `check`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Format {
+    /// One line per problem, such as `src/intro.md:12:1: Message [code]`
+    #[default]
+    Text,
+    /// A JSON array of problems
+    Json,
+    /// A SARIF 2.1.0 log, for code scanning tools
+    Sarif,
+}
+
+pub fn handle_check(proj_dir: PathBuf, format: Format) -> Result<()> {
+    let mut diagnostics = check_book(&proj_dir)?;
+    // Report paths relative to where we were run, so tools can find them
+    for diagnostic in &mut diagnostics {
+        if let Some(path) = &diagnostic.path {
+            diagnostic.path = Some(relative_to(&proj_dir, path));
+        }
+    }
+
+    let mut stdout = io::stdout().lock();
+    match format {
+        Format::Text => {
+            for diagnostic in &diagnostics {
+                writeln!(stdout, "{}", format_diagnostic(diagnostic))?;
+            }
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut stdout, &diagnostics)?;
+            writeln!(stdout)?;
+        }
+        Format::Sarif => {
+            serde_json::to_writer_pretty(&mut stdout, &sarif(&diagnostics))?;
+            writeln!(stdout)?;
+        }
+    }
+    if !diagnostics.is_empty() {
+        return Err(anyhow!("Found {} problem(s)", diagnostics.len()));
+    }
+    log::info!("No problems found");
+    Ok(())
+}
+
+/// `path` under `proj_dir`, without a redundant `./` prefix.
+fn relative_to(proj_dir: &Path, path: &Path) -> PathBuf {
+    if proj_dir == Path::new(".") {
+        path.to_owned()
+    } else {
+        proj_dir.join(path)
+    }
+}
+
+/// The diagnostic as a line, such as `src/intro.md:12:1: Message [code]`.
+fn format_diagnostic(diagnostic: &Diagnostic) -> String {
+    let mut location = String::new();
+    if let Some(path) = &diagnostic.path {
+        location.push_str(&path.display().to_string());
+        for number in [diagnostic.line, diagnostic.column].into_iter().flatten() {
+            location.push_str(&format!(":{number}"));
+        }
+        location.push_str(": ");
+    }
+    format!("{location}{} [{}]", diagnostic.message, diagnostic.code)
+}
+
+/// The diagnostics as a SARIF log.
+///
+/// See <https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html>.
+fn sarif(diagnostics: &[Diagnostic]) -> Value {
+    let mut rules: Vec<&str> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.code)
+        .collect();
+    rules.sort_unstable();
+    rules.dedup();
+
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let mut result = json!({
+                "ruleId": diagnostic.code,
+                "level": "warning",
+                "message": { "text": diagnostic.message },
+            });
+            if let Some(path) = &diagnostic.path {
+                let mut physical_location = json!({
+                    "artifactLocation": { "uri": path.display().to_string().replace('\\', "/") },
+                });
+                if let Some(line) = diagnostic.line {
+                    let mut region = json!({ "startLine": line });
+                    if let Some(column) = diagnostic.column {
+                        region["startColumn"] = column.into();
+                    }
+                    physical_location["region"] = region;
+                }
+                result["locations"] = json!([{ "physicalLocation": physical_location }]);
+            }
+            result
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "mdbook-admonish",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules
+                        .iter()
+                        .map(|rule| json!({ "id": rule }))
+                        .collect::<Vec<_>>(),
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn diagnostic() -> Diagnostic {
+        Diagnostic {
+            path: Some(PathBuf::from("src/intro.md")),
+            line: Some(12),
+            column: Some(3),
+            code: "empty-block",
+            message: "Admonition has no content".to_owned(),
+        }
+    }
+
+    #[test]
+    fn formats_diagnostics() {
+        assert_eq!(
+            format_diagnostic(&diagnostic()),
+            "src/intro.md:12:3: Admonition has no content [empty-block]"
+        );
+        assert_eq!(
+            format_diagnostic(&Diagnostic {
+                line: None,
+                column: None,
+                ..diagnostic()
+            }),
+            "src/intro.md: Admonition has no content [empty-block]"
+        );
+        assert_eq!(
+            format_diagnostic(&Diagnostic {
+                path: None,
+                ..diagnostic()
+            }),
+            "Admonition has no content [empty-block]"
+        );
+    }
+
+    #[test]
+    fn relative_paths() {
+        assert_eq!(
+            relative_to(Path::new("."), Path::new("src/intro.md")),
+            PathBuf::from("src/intro.md")
+        );
+        assert_eq!(
+            relative_to(Path::new("book"), Path::new("src/intro.md")),
+            PathBuf::from("book/src/intro.md")
+        );
+    }
+
+    #[test]
+    fn sarif_log() {
+        let log = sarif(&[
+            diagnostic(),
+            Diagnostic {
+                path: Some(PathBuf::from("src/setup.md")),
+                line: None,
+                column: None,
+                code: "budget-exceeded",
+                message: "Over budget".to_owned(),
+            },
+        ]);
+        let run = &log["runs"][0];
+        assert_eq!(
+            run["tool"]["driver"]["rules"],
+            json!([{ "id": "budget-exceeded" }, { "id": "empty-block" }])
+        );
+        assert_eq!(
+            run["results"],
+            json!([
+                {
+                    "ruleId": "empty-block",
+                    "level": "warning",
+                    "message": { "text": "Admonition has no content" },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": "src/intro.md" },
+                            "region": { "startLine": 12, "startColumn": 3 },
+                        },
+                    }],
+                },
+                {
+                    "ruleId": "budget-exceeded",
+                    "level": "warning",
+                    "message": { "text": "Over budget" },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": "src/setup.md" },
+                        },
+                    }],
+                },
+            ])
+        );
+    }
+}
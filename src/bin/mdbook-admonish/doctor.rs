@@ -0,0 +1,311 @@
+use anyhow::{anyhow, Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use toml_edit::{Document, Item};
+
+use crate::install::{asset_is_hashed, ADMONISH_CSS_FILES, ASSETS_VERSION};
+
+/// A problem with the book setup, and how to fix it.
+#[derive(Debug, PartialEq)]
+struct Finding {
+    problem: String,
+    fix: String,
+}
+
+impl Finding {
+    fn new(problem: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            problem: problem.into(),
+            fix: fix.into(),
+        }
+    }
+}
+
+pub fn handle_doctor(proj_dir: PathBuf) -> Result<()> {
+    let config = proj_dir.join("book.toml");
+    log::info!("Reading configuration file '{}'", config.display());
+    let toml = fs::read_to_string(&config)
+        .with_context(|| format!("can't read configuration file '{}'", config.display()))?;
+    let doc = toml
+        .parse::<Document>()
+        .context("configuration is not valid TOML")?;
+
+    let mut findings = Vec::new();
+    check_layout(&proj_dir, &doc, &mut findings);
+    check_preprocessor(&doc, &mut findings);
+    check_ordering(&doc, &mut findings);
+    check_css(&proj_dir, &doc, &mut findings);
+
+    if findings.is_empty() {
+        log::info!("No problems found");
+        return Ok(());
+    }
+    for Finding { problem, fix } in &findings {
+        log::warn!("{problem}\n  fix: {fix}");
+    }
+    Err(anyhow!("Found {} problem(s)", findings.len()))
+}
+
+/// The book source directory should exist, and contain a summary.
+fn check_layout(proj_dir: &Path, doc: &Document, findings: &mut Vec<Finding>) {
+    let src = doc
+        .get("book")
+        .and_then(|book| book.get("src"))
+        .and_then(Item::as_str)
+        .unwrap_or("src");
+    let summary = proj_dir.join(src).join("SUMMARY.md");
+    if !summary.is_file() {
+        findings.push(Finding::new(
+            format!(
+                "Book sources not found: '{}' does not exist",
+                summary.display()
+            ),
+            "Run this command from the book root directory (containing `book.toml`), or pass the book directory as an argument",
+        ));
+    }
+}
+
+/// The preprocessor should be configured, with up to date assets.
+fn check_preprocessor(doc: &Document, findings: &mut Vec<Finding>) {
+    const REINSTALL: &str = "Run `mdbook-admonish install` to update installed assets";
+
+    let preprocessor = match admonish_preprocessor(doc) {
+        Some(preprocessor) => preprocessor,
+        None => {
+            findings.push(Finding::new(
+                "No `[preprocessor.admonish]` section in `book.toml`",
+                "Run `mdbook-admonish install` to add the required configuration",
+            ));
+            return;
+        }
+    };
+
+    if preprocessor.get("command").and_then(Item::as_str).is_none() {
+        findings.push(Finding::new(
+            "No `command` set for `[preprocessor.admonish]`",
+            r#"Set `command = "mdbook-admonish"`"#,
+        ));
+    }
+
+    if inline_css(doc) {
+        return;
+    }
+    match preprocessor.get("assets_version").and_then(Item::as_str) {
+        None => findings.push(Finding::new("No `assets_version` found", REINSTALL)),
+        Some(version) if version != ASSETS_VERSION.trim() => findings.push(Finding::new(
+            format!(
+                "Installed assets version '{version}' does not match this version of mdbook-admonish ('{}')",
+                ASSETS_VERSION.trim()
+            ),
+            REINSTALL,
+        )),
+        Some(_) => {}
+    }
+}
+
+/// The preprocessor should run after `links`, and ordering should not contain
+/// any direct conflicts.
+fn check_ordering(doc: &Document, findings: &mut Vec<Finding>) {
+    let preprocessor = match admonish_preprocessor(doc) {
+        Some(preprocessor) => preprocessor,
+        None => return,
+    };
+    let after = string_array(preprocessor.get("after"));
+    let before = string_array(preprocessor.get("before"));
+
+    if !after.iter().any(|name| name == "links") {
+        findings.push(Finding::new(
+            "Content included with `{{#include}}` will not be processed",
+            r#"Set `after = ["links"]` in `[preprocessor.admonish]`"#,
+        ));
+    }
+
+    for name in after.iter().filter(|name| before.contains(name)) {
+        findings.push(Finding::new(
+            format!("Preprocessor '{name}' is listed in both `before` and `after`"),
+            format!("Remove '{name}' from one of `before` or `after` in `[preprocessor.admonish]`"),
+        ));
+    }
+
+    let others = doc
+        .get("preprocessor")
+        .and_then(Item::as_table_like)
+        .into_iter()
+        .flat_map(|table| table.iter())
+        .filter(|(name, _)| *name != "admonish");
+    for (name, other) in others {
+        let other_after = string_array(other.get("after"));
+        let other_before = string_array(other.get("before"));
+        let admonish = "admonish".to_owned();
+        let name = name.to_owned();
+        if (after.contains(&name) && other_after.contains(&admonish))
+            || (before.contains(&name) && other_before.contains(&admonish))
+        {
+            findings.push(Finding::new(
+                format!("Preprocessor ordering between 'admonish' and '{name}' is circular"),
+                format!("Remove the ordering constraint from one of `[preprocessor.admonish]` or `[preprocessor.{name}]`"),
+            ));
+        }
+    }
+}
+
+/// The stylesheet should be referenced by the HTML output, and up to date.
+fn check_css(proj_dir: &Path, doc: &Document, findings: &mut Vec<Finding>) {
+    if inline_css(doc) {
+        return;
+    }
+    let additional_css = string_array(
+        doc.get("output")
+            .and_then(|output| output.get("html"))
+            .and_then(|html| html.get("additional-css")),
+    );
+
+    for (name, content) in ADMONISH_CSS_FILES {
+        let path = match additional_css
+            .iter()
+            .find(|path| asset_is_hashed(path, name).is_some())
+        {
+            // Normalize path to remove no-op components
+            Some(path) => proj_dir.join(path).components().collect::<PathBuf>(),
+            None => {
+                findings.push(Finding::new(
+                    format!("'{name}' is not listed in `output.html.additional-css`"),
+                    "Run `mdbook-admonish install` to add the required configuration",
+                ));
+                continue;
+            }
+        };
+        match fs::read(&path) {
+            Err(_) => findings.push(Finding::new(
+                format!("Stylesheet '{}' does not exist", path.display()),
+                "Run `mdbook-admonish install` to copy the stylesheet into your book",
+            )),
+            Ok(installed) if installed != *content => findings.push(Finding::new(
+                format!(
+                    "Stylesheet '{}' is out of date, or has been edited",
+                    path.display()
+                ),
+                "Run `mdbook-admonish install` to update the stylesheet",
+            )),
+            Ok(_) => {}
+        }
+    }
+}
+
+fn admonish_preprocessor(doc: &Document) -> Option<&Item> {
+    doc.get("preprocessor")?.get("admonish")
+}
+
+/// Whether the stylesheet is embedded in chapters, rather than installed.
+fn inline_css(doc: &Document) -> bool {
+    admonish_preprocessor(doc)
+        .and_then(|preprocessor| preprocessor.get("inline_css"))
+        .and_then(Item::as_bool)
+        .unwrap_or_default()
+}
+
+fn string_array(item: Option<&Item>) -> Vec<String> {
+    item.and_then(Item::as_array)
+        .into_iter()
+        .flat_map(|array| array.iter())
+        .filter_map(|value| value.as_str())
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn problems(toml: &str, check: fn(&Document, &mut Vec<Finding>)) -> Vec<String> {
+        let doc = toml.parse::<Document>().unwrap();
+        let mut findings = Vec::new();
+        check(&doc, &mut findings);
+        findings
+            .into_iter()
+            .map(|finding| finding.problem)
+            .collect()
+    }
+
+    #[test]
+    fn preprocessor_missing() {
+        assert_eq!(
+            problems("[book]\n", check_preprocessor),
+            vec!["No `[preprocessor.admonish]` section in `book.toml`"]
+        );
+    }
+
+    #[test]
+    fn preprocessor_inline_css() {
+        assert_eq!(
+            problems(
+                r#"
+[preprocessor.admonish]
+command = "mdbook-admonish"
+inline_css = true
+"#,
+                check_preprocessor
+            ),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn preprocessor_assets_version_mismatch() {
+        assert_eq!(
+            problems(
+                r#"
+[preprocessor.admonish]
+command = "mdbook-admonish"
+assets_version = "1.0.0"
+"#,
+                check_preprocessor
+            ),
+            vec![format!(
+                "Installed assets version '1.0.0' does not match this version of mdbook-admonish ('{}')",
+                ASSETS_VERSION.trim()
+            )]
+        );
+    }
+
+    #[test]
+    fn ordering_ok() {
+        assert_eq!(
+            problems(
+                r#"
+[preprocessor.admonish]
+after = ["links"]
+
+[preprocessor.toc]
+after = ["admonish"]
+"#,
+                check_ordering
+            ),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn ordering_conflicts() {
+        assert_eq!(
+            problems(
+                r#"
+[preprocessor.admonish]
+after = ["links", "toc"]
+before = ["links"]
+
+[preprocessor.toc]
+after = ["admonish"]
+"#,
+                check_ordering
+            ),
+            vec![
+                "Preprocessor 'links' is listed in both `before` and `after`",
+                "Preprocessor ordering between 'admonish' and 'toc' is circular",
+            ]
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use mdbook_admonish::{process, Config};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+pub fn handle_filter(config: Option<&Path>, renderer: &str) -> Result<()> {
+    let config = load_config(config, renderer)?;
+    let mut content = String::new();
+    io::stdin()
+        .read_to_string(&mut content)
+        .context("can't read markdown from standard input")?;
+    io::stdout()
+        .lock()
+        .write_all(process(&content, &config)?.as_bytes())?;
+    Ok(())
+}
+
+/// Load options written as the `[preprocessor.admonish]` table of `book.toml`
+/// from the file at `path`, if any.
+///
+/// Paths in the options are relative to the directory of the file.
+fn load_config(path: Option<&Path>, renderer: &str) -> Result<Config> {
+    let mut builder = Config::builder().renderer(renderer);
+    if let Some(path) = path {
+        let toml = fs::read_to_string(path)
+            .with_context(|| format!("can't read configuration '{}'", path.display()))?;
+        builder = builder.toml(toml);
+        if let Some(dir) = path.parent() {
+            builder = builder.book_root(dir);
+        }
+    }
+    builder.build()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn filters_for_renderer() {
+        let content = "```admonish\nContent.\n```\n";
+        let config = load_config(None, "html").unwrap();
+        assert!(process(content, &config)
+            .unwrap()
+            .contains(r#"<div id="admonition-note" class="admonition admonish-note">"#));
+
+        let config = load_config(None, "test").unwrap();
+        assert_eq!(content, process(content, &config).unwrap());
+    }
+
+    #[test]
+    fn missing_config() {
+        let error = load_config(Some(Path::new("missing/admonish.toml")), "html").unwrap_err();
+        assert_eq!(
+            "can't read configuration 'missing/admonish.toml'",
+            error.to_string()
+        );
+    }
+}
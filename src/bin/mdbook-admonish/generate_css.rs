@@ -0,0 +1,24 @@
+use anyhow::{Context, Result};
+use mdbook_admonish::{generate_css, CssOptions};
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+pub fn handle_generate_css(
+    proj_dir: PathBuf,
+    output: Option<PathBuf>,
+    options: CssOptions,
+) -> Result<()> {
+    let css = generate_css(&proj_dir, &options)?;
+    match output {
+        Some(output) => {
+            fs::write(&output, css)
+                .with_context(|| format!("can't write stylesheet '{}'", output.display()))?;
+            log::info!("Wrote stylesheet to '{}'", output.display());
+        }
+        None => io::stdout().lock().write_all(css.as_bytes())?,
+    }
+    Ok(())
+}
@@ -0,0 +1,485 @@
+use anyhow::{anyhow, Context, Result};
+use mdbook_admonish::hash_hex;
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+use toml_edit::{self, Array, Document, Item, Table, Value};
+
+pub(crate) const ASSETS_VERSION: &str = std::include_str!("../assets/VERSION");
+
+pub(crate) const ADMONISH_CSS_FILES: &[(&str, &[u8])] = &[(
+    "mdbook-admonish.css",
+    include_bytes!("../assets/mdbook-admonish.css"),
+)];
+
+pub(crate) const ADMONISH_JS_FILES: &[(&str, &[u8])] = &[(
+    "mdbook-admonish.js",
+    include_bytes!("../assets/mdbook-admonish.js"),
+)];
+
+/// Sass sources the stylesheet is compiled from, for books with their own Sass pipeline.
+pub(crate) const ADMONISH_SCSS_FILES: &[(&str, &[u8])] = &[
+    (
+        "mdbook-admonish.scss",
+        include_bytes!("../../../compile_assets/scss/mdbook-admonish.scss"),
+    ),
+    (
+        "admonition.scss",
+        include_bytes!("../../../compile_assets/scss/admonition.scss"),
+    ),
+    (
+        "material-color.scss",
+        include_bytes!("../../../compile_assets/scss/material-color.scss"),
+    ),
+];
+
+/// Subdirectory of the css directory the Sass sources are installed into.
+const SCSS_DIR: &str = "mdbook-admonish-scss";
+
+trait ArrayExt {
+    fn contains_str(&self, value: &str) -> bool;
+}
+
+impl ArrayExt for Array {
+    fn contains_str(&self, value: &str) -> bool {
+        self.iter().any(|element| match element.as_str() {
+            None => false,
+            Some(element_str) => element_str == value,
+        })
+    }
+}
+
+pub fn handle_install(
+    proj_dir: PathBuf,
+    css_dir: Option<PathBuf>,
+    css_name: Option<&str>,
+    js: bool,
+    scss: bool,
+    locked: bool,
+    cache_bust: bool,
+) -> Result<()> {
+    let config = proj_dir.join("book.toml");
+    log::info!("Reading configuration file '{}'", config.display());
+    let toml = fs::read_to_string(&config)
+        .with_context(|| format!("can't read configuration file '{}'", config.display()))?;
+    let mut doc = toml
+        .parse::<Document>()
+        .context("configuration is not valid TOML")?;
+
+    if let Ok(preprocessor) = preprocessor(&mut doc) {
+        let value = toml_edit::value(
+            toml_edit::Value::from(ASSETS_VERSION.trim())
+                .decorated(" ", " # do not edit: managed by `mdbook-admonish install`"),
+        );
+        preprocessor["assets_version"] = value;
+    } else {
+        log::info!("Unexpected configuration, not updating prereprocessor configuration");
+    };
+
+    let css_files = ADMONISH_CSS_FILES
+        .iter()
+        .map(|(name, content)| (css_name.unwrap_or(name), *content))
+        .collect::<Vec<_>>();
+    let css_dir = css_dir.unwrap_or_else(|| default_assets_dir(&doc, &css_files));
+    let mut assets = install_assets(
+        &mut doc,
+        &proj_dir,
+        &css_dir,
+        "additional-css",
+        &css_files,
+        cache_bust,
+    )?;
+    if js {
+        assets.extend(install_assets(
+            &mut doc,
+            &proj_dir,
+            &css_dir,
+            "additional-js",
+            ADMONISH_JS_FILES,
+            cache_bust,
+        )?);
+    }
+    if scss {
+        // Sources are not referenced from the configuration, only imported by the book's own Sass
+        let scss_dir = proj_dir.join(&css_dir).join(SCSS_DIR);
+        assets.extend(
+            ADMONISH_SCSS_FILES
+                .iter()
+                .map(|(name, content)| (scss_dir.join(name).components().collect(), *content)),
+        );
+    }
+
+    let new_toml = doc.to_string();
+    if locked {
+        return verify_locked(&config, new_toml != toml, &assets);
+    }
+
+    for (filepath, content) in assets {
+        log::info!("Copying asset to '{}'", filepath.display());
+        if let Some(parent) = filepath.parent() {
+            fs::create_dir_all(parent).context("can't create asset directory")?;
+        }
+        let mut file = File::create(&filepath).context("can't open file for writing")?;
+        file.write_all(content)
+            .context("can't write content to file")?;
+    }
+
+    if new_toml != toml {
+        log::info!("Saving changed configuration to '{}'", config.display());
+        let mut file =
+            File::create(config).context("can't open configuration file for writing.")?;
+        file.write_all(new_toml.as_bytes())
+            .context("can't write configuration")?;
+    } else {
+        log::info!("Configuration '{}' already up to date", config.display());
+    }
+
+    log::info!("mdbook-admonish is now installed. You can start using it in your book.");
+    let codeblock = r#"```admonish warning
+A beautifully styled message.
+```"#;
+    log::info!("Add a code block like:\n{}", codeblock);
+    Ok(())
+}
+
+/// Check that installation would make no changes, without making them.
+fn verify_locked(config: &Path, config_changed: bool, assets: &[(PathBuf, &[u8])]) -> Result<()> {
+    let mut outdated = Vec::new();
+    if config_changed {
+        outdated.push(config);
+    }
+    for (filepath, content) in assets {
+        if fs::read(filepath).ok().as_deref() != Some(*content) {
+            outdated.push(filepath);
+        }
+    }
+
+    if outdated.is_empty() {
+        log::info!("mdbook-admonish installation is up to date");
+        return Ok(());
+    }
+    for filepath in &outdated {
+        log::error!("'{}' would be updated", filepath.display());
+    }
+    Err(anyhow!(
+        "mdbook-admonish installation is out of date, run `mdbook-admonish install` to update"
+    ))
+}
+
+/// Reference asset files in the given `output.html` configuration key.
+///
+/// If `cache_bust` is set, or the asset is already installed with a hashed
+/// filename, the filename includes a hash of the content. Browsers then fetch
+/// the new asset after an update, rather than using a stale cached copy.
+///
+/// Returns the files that should be written into the book.
+fn install_assets(
+    doc: &mut Document,
+    proj_dir: &Path,
+    assets_dir: &Path,
+    key: &str,
+    files: &[(&str, &'static [u8])],
+    cache_bust: bool,
+) -> Result<Vec<(PathBuf, &'static [u8])>> {
+    let mut assets = Vec::with_capacity(files.len());
+    let mut additional_assets = additional_assets(doc, key);
+    for (name, content) in files {
+        let hashed = cache_bust
+            || additional_assets
+                .as_ref()
+                .map_or(false, |additional_assets| {
+                    additional_assets
+                        .iter()
+                        .filter_map(|path| asset_is_hashed(path.as_str()?, name))
+                        .any(|hashed| hashed)
+                });
+        let file_name = if hashed {
+            hashed_file_name(name, content)
+        } else {
+            name.to_string()
+        };
+        // Configured paths are relative to the book root
+        let path = assets_dir.join(file_name);
+        // Normalize path to remove no-op components
+        // https://github.com/tommilligan/mdbook-admonish/issues/47
+        let path: PathBuf = path.components().collect();
+        let filepath_str = path.to_str().context("non-utf8 filepath")?;
+        let filepath: PathBuf = proj_dir.join(&path).components().collect();
+
+        if let Ok(ref mut additional_assets) = additional_assets {
+            if hashed {
+                // Replace references to any other version of the asset
+                additional_assets.retain(|path| match path.as_str() {
+                    Some(path) if path != filepath_str && asset_is_hashed(path, name).is_some() => {
+                        log::info!("Removing stale '{path}' from '{key}'");
+                        false
+                    }
+                    _ => true,
+                });
+            }
+            if !additional_assets.contains_str(filepath_str) {
+                log::info!("Adding '{filepath_str}' to '{key}'");
+                additional_assets.push(filepath_str);
+            }
+        } else {
+            log::warn!("Unexpected configuration, not updating '{key}'");
+        }
+
+        assets.push((filepath, *content));
+    }
+    Ok(assets)
+}
+
+/// The directory to install assets into, if not given.
+///
+/// Reinstalling keeps the stylesheet where it is already installed. Otherwise,
+/// assets go in the theme directory if configured, or the book root.
+fn default_assets_dir(doc: &Document, css_files: &[(&str, &[u8])]) -> PathBuf {
+    let html = doc.get("output").and_then(|output| output.get("html"));
+    let installed = html
+        .and_then(|html| html.get("additional-css"))
+        .and_then(Item::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .find(|path| {
+            css_files
+                .iter()
+                .any(|(name, _)| asset_is_hashed(path, name).is_some())
+        });
+    if let Some(path) = installed {
+        return Path::new(path)
+            .parent()
+            .map(Path::to_owned)
+            .unwrap_or_default();
+    }
+    html.and_then(|html| html.get("theme"))
+        .and_then(Item::as_str)
+        .map_or_else(|| PathBuf::from("."), PathBuf::from)
+}
+
+/// Whether `path` refers to the asset `name`, and if so, whether the filename
+/// includes a content hash (such as `mdbook-admonish.0123abcd.css`).
+pub(crate) fn asset_is_hashed(path: &str, name: &str) -> Option<bool> {
+    let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    if file_name == name {
+        return Some(false);
+    }
+    let (stem, extension) = name.rsplit_once('.')?;
+    let hash = file_name
+        .strip_prefix(stem)?
+        .strip_suffix(extension)?
+        .strip_prefix('.')?
+        .strip_suffix('.')?;
+    (!hash.is_empty() && hash.chars().all(|c| c.is_ascii_hexdigit())).then_some(true)
+}
+
+/// The filename of an asset, including a hash of its content.
+pub(crate) fn hashed_file_name(name: &str, content: &[u8]) -> String {
+    let hash = hash_hex(content);
+    match name.rsplit_once('.') {
+        Some((stem, extension)) => format!("{stem}.{hash}.{extension}"),
+        None => format!("{name}.{hash}"),
+    }
+}
+
+/// Return the given `output.html` array field (such as `additional-css`),
+/// initializing if required.
+///
+/// Return `Err` if the existing configuration is unknown.
+fn additional_assets<'a>(doc: &'a mut Document, key: &str) -> Result<&'a mut Array, ()> {
+    let doc = doc.as_table_mut();
+
+    let empty_table = Item::Table(Table::default());
+    let empty_array = Item::Value(Value::Array(Array::default()));
+
+    doc.entry("output")
+        .or_insert(empty_table.clone())
+        .as_table_mut()
+        .and_then(|item| {
+            item.entry("html")
+                .or_insert(empty_table)
+                .as_table_mut()?
+                .entry(key)
+                .or_insert(empty_array)
+                .as_value_mut()?
+                .as_array_mut()
+        })
+        .ok_or(())
+}
+
+/// Return the preprocessor table for admonish, initializing if required.
+///
+/// Return `Err` if the existing configuration is unknown.
+fn preprocessor(doc: &mut Document) -> Result<&mut Item, ()> {
+    let doc = doc.as_table_mut();
+
+    let empty_table = Item::Table(Table::default());
+    let item = doc.entry("preprocessor").or_insert(empty_table.clone());
+    let item = item
+        .as_table_mut()
+        .ok_or(())?
+        .entry("admonish")
+        .or_insert(empty_table);
+    item["command"] = toml_edit::value("mdbook-admonish");
+    Ok(item)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn asset_names() {
+        let hashed = hashed_file_name("mdbook-admonish.css", b"content");
+        assert!(hashed.starts_with("mdbook-admonish."));
+        assert!(hashed.ends_with(".css"));
+        assert_ne!(hashed, hashed_file_name("mdbook-admonish.css", b"changed"));
+
+        assert_eq!(
+            asset_is_hashed("./theme/mdbook-admonish.css", "mdbook-admonish.css"),
+            Some(false)
+        );
+        assert_eq!(
+            asset_is_hashed(&format!("./theme/{hashed}"), "mdbook-admonish.css"),
+            Some(true)
+        );
+        assert_eq!(
+            asset_is_hashed("./theme/custom.css", "mdbook-admonish.css"),
+            None
+        );
+        assert_eq!(
+            asset_is_hashed("./theme/mdbook-admonish.dark.css", "mdbook-admonish.css"),
+            None
+        );
+    }
+
+    #[test]
+    fn install_cache_busted_assets() {
+        let mut doc = r#"[output.html]
+additional-css = ["./custom.css", "./mdbook-admonish.css"]
+"#
+        .parse::<Document>()
+        .unwrap();
+        let files: &[(&str, &[u8])] = &[("mdbook-admonish.css", b"content")];
+        let hashed = format!("./{}", hashed_file_name("mdbook-admonish.css", b"content"));
+
+        let assets = install_assets(
+            &mut doc,
+            Path::new("."),
+            Path::new("."),
+            "additional-css",
+            files,
+            true,
+        )
+        .unwrap();
+        assert_eq!(assets, vec![(PathBuf::from(&hashed), &b"content"[..])]);
+        assert_eq!(
+            string_array(&doc),
+            vec!["./custom.css".to_owned(), hashed.clone()]
+        );
+
+        // Once hashed, assets stay hashed
+        let files: &[(&str, &[u8])] = &[("mdbook-admonish.css", b"changed")];
+        let rehashed = format!("./{}", hashed_file_name("mdbook-admonish.css", b"changed"));
+        install_assets(
+            &mut doc,
+            Path::new("."),
+            Path::new("."),
+            "additional-css",
+            files,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            string_array(&doc),
+            vec!["./custom.css".to_owned(), rehashed]
+        );
+    }
+
+    #[test]
+    fn install_from_book_directory() {
+        let mut doc = "".parse::<Document>().unwrap();
+        let files: &[(&str, &[u8])] = &[("admonish.css", b"content")];
+        let assets = install_assets(
+            &mut doc,
+            Path::new("books/guide"),
+            Path::new("./assets/css"),
+            "additional-css",
+            files,
+            false,
+        )
+        .unwrap();
+        // Files are written into the book, but referenced from its root
+        assert_eq!(
+            assets,
+            vec![(
+                PathBuf::from("books/guide/assets/css/admonish.css"),
+                &b"content"[..]
+            )]
+        );
+        assert_eq!(string_array(&doc), vec!["./assets/css/admonish.css"]);
+    }
+
+    #[test]
+    fn default_assets_dirs() {
+        let doc = "".parse::<Document>().unwrap();
+        assert_eq!(
+            default_assets_dir(&doc, ADMONISH_CSS_FILES),
+            PathBuf::from(".")
+        );
+
+        let doc = r#"[output.html]
+theme = "shared/theme"
+"#
+        .parse::<Document>()
+        .unwrap();
+        assert_eq!(
+            default_assets_dir(&doc, ADMONISH_CSS_FILES),
+            PathBuf::from("shared/theme")
+        );
+
+        // Already installed assets stay where they are
+        let doc = r#"[output.html]
+theme = "shared/theme"
+additional-css = ["./custom.css", "./assets/mdbook-admonish.css"]
+"#
+        .parse::<Document>()
+        .unwrap();
+        assert_eq!(
+            default_assets_dir(&doc, ADMONISH_CSS_FILES),
+            PathBuf::from("./assets")
+        );
+        let files: &[(&str, &[u8])] = &[("custom.css", b"content")];
+        assert_eq!(default_assets_dir(&doc, files), PathBuf::from("."));
+    }
+
+    #[test]
+    fn scss_imports_are_installed() {
+        for (_, content) in ADMONISH_SCSS_FILES {
+            let content = std::str::from_utf8(content).unwrap();
+            for line in content.lines() {
+                let Some(import) = line.trim().strip_prefix("@import \"./") else {
+                    continue;
+                };
+                let name = format!("{}.scss", import.trim_end_matches("\";"));
+                assert!(
+                    ADMONISH_SCSS_FILES.iter().any(|(file, _)| *file == name),
+                    "'{name}' is imported but not installed"
+                );
+            }
+        }
+    }
+
+    fn string_array(doc: &Document) -> Vec<String> {
+        doc["output"]["html"]["additional-css"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_str().unwrap().to_owned())
+            .collect()
+    }
+}
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use mdbook_admonish::{list_admonitions, ListedAdmonition};
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+pub fn handle_list(proj_dir: PathBuf, json: bool) -> Result<()> {
+    let admonitions = list_admonitions(&proj_dir)?;
+    let mut stdout = io::stdout().lock();
+    if json {
+        serde_json::to_writer_pretty(&mut stdout, &admonitions)?;
+        writeln!(stdout)?;
+    } else {
+        for admonition in &admonitions {
+            writeln!(stdout, "{}", format_line(admonition))?;
+        }
+    }
+    Ok(())
+}
+
+/// The admonition as a tab separated line of location, directive and title.
+fn format_line(admonition: &ListedAdmonition) -> String {
+    format!(
+        "{}:{}\t{}\t{}",
+        admonition.path.display(),
+        admonition.line,
+        admonition.directive,
+        admonition.title
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn formats_line() {
+        let admonition = ListedAdmonition {
+            path: PathBuf::from("src/intro.md"),
+            line: 12,
+            directive: "warning".to_owned(),
+            title: "Back up first".to_owned(),
+            id: "admonition-back-up-first".to_owned(),
+            tags: Vec::new(),
+            collapsible: false,
+            length: 0,
+        };
+        assert_eq!(
+            format_line(&admonition),
+            "src/intro.md:12\twarning\tBack up first"
+        );
+    }
+}
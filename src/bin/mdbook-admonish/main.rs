@@ -0,0 +1,343 @@
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use mdbook::{
+    errors::Error,
+    preprocess::{CmdPreprocessor, Preprocessor},
+};
+use mdbook_admonish::{Admonish, CssOptions};
+use std::{io, path::PathBuf, process};
+
+mod check;
+#[cfg(feature = "cli-install")]
+mod doctor;
+mod filter;
+mod generate_css;
+#[cfg(feature = "cli-install")]
+mod install;
+mod list;
+mod playground;
+mod render;
+mod stats;
+#[cfg(feature = "cli-install")]
+mod upgrade;
+
+/// mdbook preprocessor to add support for admonitions
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Fail the build if any warnings are reported
+    ///
+    /// Overrides `warnings.deny` in `book.toml`.
+    #[arg(long, global = true)]
+    deny_warnings: bool,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Check whether a renderer is supported by this preprocessor
+    Supports { renderer: String },
+
+    /// Check every admonition in the book for problems, without building it
+    ///
+    /// Exits with an error if any problems are found.
+    Check {
+        /// Root directory for the book, should contain the configuration file (`book.toml`)
+        ///
+        /// If not set, defaults to the current directory.
+        dir: Option<PathBuf>,
+
+        /// Output format for problems found
+        #[arg(long, value_enum, default_value_t)]
+        format: check::Format,
+    },
+
+    /// List every admonition in the book, with its location, directive and title
+    List {
+        /// Root directory for the book, should contain the configuration file (`book.toml`)
+        ///
+        /// If not set, defaults to the current directory.
+        dir: Option<PathBuf>,
+
+        /// Print the admonitions as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Generate the stylesheet, with the colors and icons configured for each directive
+    ///
+    /// Use this in place of the installed `mdbook-admonish.css`, to style directives
+    /// without editing the stylesheet. Rerun after changing the configuration, or
+    /// upgrading.
+    GenerateCss {
+        /// Root directory for the book, should contain the configuration file (`book.toml`)
+        ///
+        /// If not set, defaults to the current directory.
+        dir: Option<PathBuf>,
+
+        /// Write the stylesheet to this file, rather than standard output
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+
+        /// Only include styles for the directives used in the book
+        ///
+        /// Rerun after using another directive, as it is otherwise unstyled.
+        #[arg(long)]
+        used_only: bool,
+
+        /// Append rules for a high contrast variant
+        ///
+        /// Titles are untinted and set apart by a border, and blocks have heavier borders.
+        #[arg(long)]
+        high_contrast: bool,
+    },
+
+    /// Render the admonitions in markdown from standard input, to standard output
+    ///
+    /// This applies the preprocessor to a single document, outside of an mdbook build.
+    Filter {
+        /// Configuration file, with options as written in the `[preprocessor.admonish]`
+        /// table of `book.toml`
+        ///
+        /// Paths in the options are relative to the directory of this file.
+        #[arg(long)]
+        config: Option<PathBuf>,
+
+        /// Renderer to render for, which selects the render mode
+        #[arg(long, default_value = "html")]
+        renderer: String,
+    },
+
+    /// Render a single chapter of the book, and print the result
+    ///
+    /// The chapter is rendered with the book's configuration, as it would be
+    /// by `mdbook build`, without building the rest of the book.
+    Render {
+        /// Path of the chapter source file
+        chapter: PathBuf,
+
+        /// Root directory for the book, should contain the configuration file (`book.toml`)
+        ///
+        /// If not set, defaults to the current directory.
+        #[arg(long)]
+        dir: Option<PathBuf>,
+
+        /// Renderer to render for, which selects the render mode
+        #[arg(long, default_value = "html")]
+        renderer: String,
+    },
+
+    /// Serve a page to try out admonitions, rendered with the book's configuration
+    ///
+    /// Type markdown on the left, and see it rendered and styled on the right.
+    Playground {
+        /// Root directory for the book, should contain the configuration file (`book.toml`)
+        ///
+        /// If not set, defaults to the current directory.
+        dir: Option<PathBuf>,
+
+        /// Port to serve the page on, at `localhost`
+        #[arg(long, short, default_value_t = 3001)]
+        port: u16,
+    },
+
+    /// Summarize the admonitions in the book, per directive and per chapter
+    Stats {
+        /// Root directory for the book, should contain the configuration file (`book.toml`)
+        ///
+        /// If not set, defaults to the current directory.
+        dir: Option<PathBuf>,
+
+        /// Print the summary as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    #[cfg(feature = "cli-install")]
+    /// Install the required assset files and include it in the config
+    Install {
+        /// Root directory for the book, should contain the configuration file (`book.toml`)
+        ///
+        /// If not set, defaults to the current directory.
+        dir: Option<PathBuf>,
+
+        /// Relative directory for the css assets, from the book directory root
+        ///
+        /// If not set, defaults to the directory of the installed stylesheet, if any.
+        /// Otherwise, to the `output.html.theme` directory if set, or the book root.
+        #[arg(long)]
+        css_dir: Option<PathBuf>,
+
+        /// Filename for the stylesheet, in place of `mdbook-admonish.css`
+        #[arg(long)]
+        css_name: Option<String>,
+
+        /// Also install the optional javascript assets
+        ///
+        /// These provide keyboard and focus management for collapsible blocks,
+        /// and clickable task lists.
+        #[arg(long)]
+        js: bool,
+
+        /// Also install the Sass sources of the stylesheet
+        ///
+        /// These are written to a `mdbook-admonish-scss` directory alongside the
+        /// stylesheet, for books that compile admonition styles in their own Sass pipeline.
+        #[arg(long)]
+        scss: bool,
+
+        /// Make no changes, and fail if the installation is not up to date
+        ///
+        /// Useful to verify installed assets in CI.
+        #[arg(long)]
+        locked: bool,
+
+        /// Include a hash of the content in asset filenames
+        ///
+        /// This ensures readers see updated styles after an upgrade, rather than
+        /// a stale copy cached by their browser. Once enabled, later installs keep
+        /// using hashed filenames.
+        #[arg(long)]
+        cache_bust: bool,
+    },
+
+    #[cfg(feature = "cli-install")]
+    /// Diagnose common problems with the book setup, and suggest fixes
+    Doctor {
+        /// Root directory for the book, should contain the configuration file (`book.toml`)
+        ///
+        /// If not set, defaults to the current directory.
+        dir: Option<PathBuf>,
+    },
+
+    #[cfg(feature = "cli-install")]
+    /// Migrate the configuration and installed assets from an older release
+    ///
+    /// The previous configuration is backed up to `book.toml.bak`.
+    Upgrade {
+        /// Root directory for the book, should contain the configuration file (`book.toml`)
+        ///
+        /// If not set, defaults to the current directory.
+        dir: Option<PathBuf>,
+    },
+}
+
+fn main() {
+    env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
+
+    let cli = Cli::parse();
+    if let Err(error) = run(cli) {
+        log::error!("Fatal error: {}", error);
+        for error in error.chain() {
+            log::error!("  - {}", error);
+        }
+        process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        None => handle_preprocessing(cli.deny_warnings),
+        Some(Commands::Supports { renderer }) => {
+            handle_supports(renderer);
+        }
+        Some(Commands::Check { dir, format }) => {
+            check::handle_check(dir.unwrap_or_else(|| PathBuf::from(".")), format)
+        }
+        Some(Commands::List { dir, json }) => {
+            list::handle_list(dir.unwrap_or_else(|| PathBuf::from(".")), json)
+        }
+        Some(Commands::GenerateCss {
+            dir,
+            output,
+            used_only,
+            high_contrast,
+        }) => generate_css::handle_generate_css(
+            dir.unwrap_or_else(|| PathBuf::from(".")),
+            output,
+            CssOptions {
+                used_only,
+                high_contrast,
+            },
+        ),
+        Some(Commands::Filter { config, renderer }) => {
+            filter::handle_filter(config.as_deref(), &renderer)
+        }
+        Some(Commands::Render {
+            chapter,
+            dir,
+            renderer,
+        }) => render::handle_render(
+            dir.unwrap_or_else(|| PathBuf::from(".")),
+            &chapter,
+            &renderer,
+        ),
+        Some(Commands::Playground { dir, port }) => {
+            playground::handle_playground(dir.unwrap_or_else(|| PathBuf::from(".")), port)
+        }
+        Some(Commands::Stats { dir, json }) => {
+            stats::handle_stats(dir.unwrap_or_else(|| PathBuf::from(".")), json)
+        }
+        #[cfg(feature = "cli-install")]
+        Some(Commands::Install {
+            dir,
+            css_dir,
+            css_name,
+            js,
+            scss,
+            locked,
+            cache_bust,
+        }) => install::handle_install(
+            dir.unwrap_or_else(|| PathBuf::from(".")),
+            css_dir,
+            css_name.as_deref(),
+            js,
+            scss,
+            locked,
+            cache_bust,
+        ),
+        #[cfg(feature = "cli-install")]
+        Some(Commands::Doctor { dir }) => {
+            doctor::handle_doctor(dir.unwrap_or_else(|| PathBuf::from(".")))
+        }
+        #[cfg(feature = "cli-install")]
+        Some(Commands::Upgrade { dir }) => {
+            upgrade::handle_upgrade(dir.unwrap_or_else(|| PathBuf::from(".")))
+        }
+    }
+}
+
+fn handle_preprocessing(deny_warnings: bool) -> Result<(), Error> {
+    let (mut ctx, book) = CmdPreprocessor::parse_input(io::stdin())?;
+    if deny_warnings {
+        ctx.config
+            .set("preprocessor.admonish.warnings.deny", true)?;
+    }
+
+    if ctx.mdbook_version != mdbook::MDBOOK_VERSION {
+        eprintln!(
+            "Warning: The mdbook-admonish preprocessor was built against version \
+             {} of mdbook, but we're being called from version {}",
+            mdbook::MDBOOK_VERSION,
+            ctx.mdbook_version
+        );
+    }
+
+    let processed_book = Admonish.run(&ctx, book)?;
+    serde_json::to_writer(io::stdout(), &processed_book)?;
+
+    Ok(())
+}
+
+fn handle_supports(renderer: String) -> ! {
+    let supported = Admonish.supports_renderer(&renderer);
+
+    // Signal whether the renderer is supported by exiting with 1 or 0.
+    if supported {
+        process::exit(0);
+    } else {
+        process::exit(1);
+    }
+}
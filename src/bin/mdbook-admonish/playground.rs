@@ -0,0 +1,169 @@
+use anyhow::{anyhow, Context, Result};
+use mdbook_admonish::{generate_css, process, ConfigBuilder, CssOptions};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+};
+
+const PAGE: &str = include_str!("../assets/playground.html");
+
+pub fn handle_playground(proj_dir: PathBuf, port: u16) -> Result<()> {
+    // Only served locally, as it reads the book from disk
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .with_context(|| format!("can't listen on port {port}"))?;
+    log::info!("Serving the playground at http://localhost:{port}");
+    for stream in listener.incoming() {
+        let result = stream
+            .context("can't accept connection")
+            .and_then(|mut stream| serve(&proj_dir, &mut stream));
+        if let Err(error) = result {
+            log::warn!("{error:#}");
+        }
+    }
+    Ok(())
+}
+
+/// A request to the playground, which only needs the method, path and body.
+#[derive(Debug, PartialEq, Eq)]
+struct Request {
+    method: String,
+    path: String,
+    body: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+fn serve(proj_dir: &Path, stream: &mut TcpStream) -> Result<()> {
+    let request = read_request(&mut BufReader::new(&*stream))?;
+    let response = respond(proj_dir, &request);
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        response.body
+    )
+    .context("can't write response")
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_owned(), path.to_owned()),
+        _ => return Err(anyhow!("invalid request '{}'", line.trim_end())),
+    };
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().context("invalid content length")?;
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Request {
+        method,
+        path,
+        body: String::from_utf8(body).context("request body is not UTF-8")?,
+    })
+}
+
+fn respond(proj_dir: &Path, request: &Request) -> Response {
+    let result = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => page(proj_dir).map(|page| ("text/html", page)),
+        ("POST", "/render") => render(proj_dir, &request.body).map(|html| ("text/html", html)),
+        _ => {
+            return Response {
+                status: "404 Not Found",
+                content_type: "text/plain",
+                body: "Not found".to_owned(),
+            }
+        }
+    };
+    match result {
+        Ok((content_type, body)) => Response {
+            status: "200 OK",
+            content_type,
+            body,
+        },
+        Err(error) => Response {
+            status: "500 Internal Server Error",
+            content_type: "text/plain",
+            body: format!("{error:#}"),
+        },
+    }
+}
+
+/// The playground page, styled with the book's stylesheet.
+fn page(proj_dir: &Path) -> Result<String> {
+    let stylesheet = generate_css(proj_dir, &CssOptions::default())?;
+    Ok(PAGE.replace("/* stylesheet */", &stylesheet))
+}
+
+/// Render `markdown` to HTML, as a chapter of the book would be.
+fn render(proj_dir: &Path, markdown: &str) -> Result<String> {
+    // Loaded for each render, so edits to `book.toml` apply immediately
+    let config = ConfigBuilder::from_book(proj_dir)?.build()?;
+    let markdown = process(markdown, &config)?;
+    Ok(mdbook::utils::render_markdown(&markdown, false))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn reads_request() {
+        let mut request: &[u8] =
+            b"POST /render HTTP/1.1\r\nHost: localhost\r\ncontent-length: 5\r\n\r\nHello";
+        assert_eq!(
+            read_request(&mut request).unwrap(),
+            Request {
+                method: "POST".to_owned(),
+                path: "/render".to_owned(),
+                body: "Hello".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn renders_with_book_config() {
+        let request = Request {
+            method: "POST".to_owned(),
+            path: "/render".to_owned(),
+            body: "```admonish tip\nContent.\n```\n".to_owned(),
+        };
+        let response = respond(Path::new("book"), &request);
+        assert_eq!(response.status, "200 OK");
+        assert!(response
+            .body
+            .contains(r#"<div id="admonition-tip" class="admonition admonish-tip">"#));
+        assert!(response.body.contains("<p>Content.</p>"));
+    }
+
+    #[test]
+    fn unknown_path() {
+        let request = Request {
+            method: "GET".to_owned(),
+            path: "/missing".to_owned(),
+            body: String::new(),
+        };
+        assert_eq!(respond(Path::new("book"), &request).status, "404 Not Found");
+    }
+}
@@ -0,0 +1,38 @@
+use anyhow::Result;
+use mdbook_admonish::render_chapter;
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+pub fn handle_render(proj_dir: PathBuf, chapter: &Path, renderer: &str) -> Result<()> {
+    let content = render_chapter(&proj_dir, chapter, renderer)?;
+    io::stdout().lock().write_all(content.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn renders_chapter_by_either_path() {
+        let book = Path::new("book");
+        let content = render_chapter(book, Path::new("book/src/overview.md"), "html").unwrap();
+        assert!(content.contains(r#"class="admonition admonish-note""#));
+        assert_eq!(
+            content,
+            render_chapter(book, Path::new("overview.md"), "html").unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_chapter() {
+        let error = render_chapter(Path::new("book"), Path::new("missing.md"), "html").unwrap_err();
+        assert_eq!(
+            "No chapter in the book has source 'missing.md'",
+            error.to_string()
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::Result;
+use mdbook_admonish::{list_admonitions, ListedAdmonition};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+/// A summary of the admonitions in a book.
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct Stats {
+    total: usize,
+    collapsible: usize,
+    /// Average length of block content, in characters.
+    average_length: usize,
+    directives: BTreeMap<String, usize>,
+    chapters: BTreeMap<PathBuf, usize>,
+}
+
+impl Stats {
+    fn new(admonitions: &[ListedAdmonition]) -> Self {
+        let mut stats = Self {
+            total: admonitions.len(),
+            ..Self::default()
+        };
+        let mut total_length = 0;
+        for admonition in admonitions {
+            if admonition.collapsible {
+                stats.collapsible += 1;
+            }
+            total_length += admonition.length;
+            *stats
+                .directives
+                .entry(admonition.directive.clone())
+                .or_default() += 1;
+            *stats.chapters.entry(admonition.path.clone()).or_default() += 1;
+        }
+        stats.average_length = total_length.checked_div(stats.total).unwrap_or_default();
+        stats
+    }
+
+    fn write_text(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(
+            writer,
+            "Admonitions: {} ({} collapsible)",
+            self.total, self.collapsible
+        )?;
+        writeln!(writer, "Average length: {} characters", self.average_length)?;
+        writeln!(writer, "\nBy directive:")?;
+        for (directive, count) in &self.directives {
+            writeln!(writer, "  {directive}: {count}")?;
+        }
+        writeln!(writer, "\nBy chapter:")?;
+        for (chapter, count) in &self.chapters {
+            writeln!(writer, "  {}: {count}", chapter.display())?;
+        }
+        Ok(())
+    }
+}
+
+pub fn handle_stats(proj_dir: PathBuf, json: bool) -> Result<()> {
+    let stats = Stats::new(&list_admonitions(&proj_dir)?);
+    let mut stdout = io::stdout().lock();
+    if json {
+        serde_json::to_writer_pretty(&mut stdout, &stats)?;
+        writeln!(stdout)?;
+    } else {
+        stats.write_text(stdout)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn admonition(
+        path: &str,
+        directive: &str,
+        collapsible: bool,
+        length: usize,
+    ) -> ListedAdmonition {
+        ListedAdmonition {
+            path: PathBuf::from(path),
+            line: 1,
+            directive: directive.to_owned(),
+            title: String::new(),
+            id: String::new(),
+            tags: Vec::new(),
+            collapsible,
+            length,
+        }
+    }
+
+    #[test]
+    fn summarizes_admonitions() {
+        let stats = Stats::new(&[
+            admonition("src/intro.md", "warning", false, 10),
+            admonition("src/intro.md", "note", true, 20),
+            admonition("src/setup.md", "warning", false, 31),
+        ]);
+        let mut text = Vec::new();
+        stats.write_text(&mut text).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            r#"Admonitions: 3 (1 collapsible)
+Average length: 20 characters
+
+By directive:
+  note: 1
+  warning: 2
+
+By chapter:
+  src/intro.md: 2
+  src/setup.md: 1
+"#
+        );
+    }
+
+    #[test]
+    fn empty_book() {
+        assert_eq!(Stats::new(&[]), Stats::default());
+    }
+}
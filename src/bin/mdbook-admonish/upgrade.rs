@@ -0,0 +1,266 @@
+use anyhow::{anyhow, Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use toml_edit::{Document, Item, Table};
+
+use crate::install::{
+    asset_is_hashed, hashed_file_name, ADMONISH_CSS_FILES, ADMONISH_JS_FILES, ASSETS_VERSION,
+};
+
+/// A change to the configuration, required by a newer release.
+///
+/// Returns a description of each change made.
+type Migration = fn(&mut Table) -> Vec<String>;
+
+/// All migrations, in the order they should be applied.
+const MIGRATIONS: &[Migration] = &[add_command, rename_action_mode];
+
+pub fn handle_upgrade(proj_dir: PathBuf) -> Result<()> {
+    let config = proj_dir.join("book.toml");
+    log::info!("Reading configuration file '{}'", config.display());
+    let toml = fs::read_to_string(&config)
+        .with_context(|| format!("can't read configuration file '{}'", config.display()))?;
+    let mut doc = toml
+        .parse::<Document>()
+        .context("configuration is not valid TOML")?;
+
+    let mut changes = migrate(&mut doc)?;
+    let assets = upgrade_assets(&proj_dir, &mut doc, &mut changes);
+
+    if changes.is_empty() {
+        log::info!("Configuration '{}' already up to date", config.display());
+        return Ok(());
+    }
+    for change in &changes {
+        log::info!("{change}");
+    }
+
+    for (filepath, content) in assets {
+        log::info!("Copying asset to '{}'", filepath.display());
+        fs::write(&filepath, content)
+            .with_context(|| format!("can't write asset '{}'", filepath.display()))?;
+    }
+
+    let new_toml = doc.to_string();
+    if new_toml != toml {
+        let backup = config.with_extension("toml.bak");
+        log::info!("Backing up configuration to '{}'", backup.display());
+        fs::write(&backup, &toml).context("can't write configuration backup")?;
+        log::info!("Saving changed configuration to '{}'", config.display());
+        fs::write(&config, new_toml).context("can't write configuration")?;
+    }
+    Ok(())
+}
+
+/// Apply all migrations to the preprocessor configuration.
+fn migrate(doc: &mut Document) -> Result<Vec<String>> {
+    let preprocessor = doc
+        .get_mut("preprocessor")
+        .and_then(|preprocessor| preprocessor.get_mut("admonish"))
+        .and_then(Item::as_table_mut)
+        .ok_or_else(|| {
+            anyhow!(
+                "No `[preprocessor.admonish]` section found, run `mdbook-admonish install` first"
+            )
+        })?;
+    Ok(MIGRATIONS
+        .iter()
+        .flat_map(|migration| migration(preprocessor))
+        .collect())
+}
+
+/// `command` is required for `mdbook` to run the preprocessor.
+fn add_command(preprocessor: &mut Table) -> Vec<String> {
+    if preprocessor.contains_key("command") {
+        return Vec::new();
+    }
+    preprocessor["command"] = toml_edit::value("mdbook-admonish");
+    vec!["Added required `command`".to_owned()]
+}
+
+/// `renderer.<name>.action_mode` was documented in place of `render_mode`.
+fn rename_action_mode(preprocessor: &mut Table) -> Vec<String> {
+    let renderers = match preprocessor
+        .get_mut("renderer")
+        .and_then(Item::as_table_like_mut)
+    {
+        Some(renderers) => renderers,
+        None => return Vec::new(),
+    };
+    let mut changes = Vec::new();
+    for (name, renderer) in renderers.iter_mut() {
+        let renderer = match renderer.as_table_like_mut() {
+            Some(renderer) => renderer,
+            None => continue,
+        };
+        let action_mode = match renderer.remove("action_mode") {
+            Some(action_mode) => action_mode,
+            None => continue,
+        };
+        if renderer.contains_key("render_mode") {
+            changes.push(format!(
+                "Removed `renderer.{name}.action_mode`, which is overridden by `render_mode`"
+            ));
+        } else {
+            renderer.insert("render_mode", action_mode);
+            changes.push(format!(
+                "Renamed `renderer.{name}.action_mode` to `render_mode`"
+            ));
+        }
+    }
+    changes
+}
+
+/// Update installed assets in place, if they are from an older release.
+///
+/// Returns the files that should be written into the book.
+fn upgrade_assets(
+    proj_dir: &Path,
+    doc: &mut Document,
+    changes: &mut Vec<String>,
+) -> Vec<(PathBuf, &'static [u8])> {
+    let version = doc["preprocessor"]["admonish"]
+        .get("assets_version")
+        .and_then(Item::as_str);
+    if version.map(str::trim) == Some(ASSETS_VERSION.trim()) {
+        return Vec::new();
+    }
+
+    let mut assets = Vec::new();
+    for (key, files) in [
+        ("additional-css", ADMONISH_CSS_FILES),
+        ("additional-js", ADMONISH_JS_FILES),
+    ] {
+        let installed = doc
+            .get_mut("output")
+            .and_then(|output| output.get_mut("html"))
+            .and_then(|html| html.get_mut(key))
+            .and_then(Item::as_array_mut)
+            .into_iter()
+            .flat_map(|array| array.iter_mut());
+        for value in installed {
+            let path = match value.as_str() {
+                Some(path) => path.to_owned(),
+                None => continue,
+            };
+            for (name, content) in files {
+                let path = match asset_is_hashed(&path, name) {
+                    None => continue,
+                    Some(false) => path.clone(),
+                    // Hashed filenames must change along with the content
+                    Some(true) => {
+                        let file_name = path.rsplit(['/', '\\']).next().unwrap_or(&path);
+                        let path = format!(
+                            "{}{}",
+                            &path[..path.len() - file_name.len()],
+                            hashed_file_name(name, content)
+                        );
+                        *value = path.as_str().into();
+                        path
+                    }
+                };
+                // Normalize path to remove no-op components
+                let filepath = proj_dir.join(path).components().collect::<PathBuf>();
+                changes.push(format!("Updated asset '{}'", filepath.display()));
+                assets.push((filepath, *content));
+            }
+        }
+    }
+    if assets.is_empty() {
+        log::warn!("No installed assets found, run `mdbook-admonish install` to install them");
+        return assets;
+    }
+
+    doc["preprocessor"]["admonish"]["assets_version"] = toml_edit::value(
+        toml_edit::Value::from(ASSETS_VERSION.trim())
+            .decorated(" ", " # do not edit: managed by `mdbook-admonish install`"),
+    );
+    changes.push(format!(
+        "Updated `assets_version` to '{}'",
+        ASSETS_VERSION.trim()
+    ));
+    assets
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn migrate_config() {
+        let mut doc = r#"[preprocessor.admonish]
+
+[preprocessor.admonish.renderer.test]
+action_mode = "strip" # for doctests
+
+[preprocessor.admonish.renderer.epub]
+action_mode = "strip"
+render_mode = "preserve"
+"#
+        .parse::<Document>()
+        .unwrap();
+
+        assert_eq!(
+            migrate(&mut doc).unwrap(),
+            vec![
+                "Added required `command`",
+                "Renamed `renderer.test.action_mode` to `render_mode`",
+                "Removed `renderer.epub.action_mode`, which is overridden by `render_mode`",
+            ]
+        );
+        assert_eq!(
+            doc.to_string(),
+            r#"[preprocessor.admonish]
+command = "mdbook-admonish"
+
+[preprocessor.admonish.renderer.test]
+render_mode = "strip" # for doctests
+
+[preprocessor.admonish.renderer.epub]
+render_mode = "preserve"
+"#
+        );
+        assert_eq!(migrate(&mut doc).unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn migrate_requires_preprocessor() {
+        let mut doc = "[book]\n".parse::<Document>().unwrap();
+        assert!(migrate(&mut doc).is_err());
+    }
+
+    #[test]
+    fn upgrade_outdated_assets() {
+        let mut doc = r#"[preprocessor.admonish]
+command = "mdbook-admonish"
+assets_version = "1.0.0"
+
+[output.html]
+additional-css = ["./theme/custom.css", "./theme/mdbook-admonish.css"]
+"#
+        .parse::<Document>()
+        .unwrap();
+        let mut changes = Vec::new();
+
+        let assets = upgrade_assets(Path::new("book"), &mut doc, &mut changes);
+        assert_eq!(
+            assets
+                .into_iter()
+                .map(|(filepath, _)| filepath)
+                .collect::<Vec<_>>(),
+            vec![PathBuf::from("book/theme/mdbook-admonish.css")]
+        );
+        assert_eq!(
+            doc["preprocessor"]["admonish"]["assets_version"].as_str(),
+            Some(ASSETS_VERSION.trim())
+        );
+        assert_eq!(changes.len(), 2);
+
+        changes.clear();
+        assert!(upgrade_assets(Path::new("book"), &mut doc, &mut changes).is_empty());
+        assert!(changes.is_empty());
+    }
+}
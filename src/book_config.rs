@@ -1,21 +1,99 @@
-use anyhow::{Context, Result};
-use mdbook::preprocess::PreprocessorContext;
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::Lazy;
+use pulldown_cmark::escape::escape_html;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "mdbook")]
+use std::borrow::Cow;
 use std::collections::HashMap;
+#[cfg(feature = "mdbook")]
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml::{Table, Value};
 
-use crate::types::AdmonitionDefaults;
+use crate::i18n;
+use crate::types::{
+    AdmonitionDefaults, CustomDirectives, DefaultsOverride, Directive, Print, RenderModes,
+    RenderTextMode,
+};
+use crate::warnings::WarningCode;
 
 /// Loads the plugin configuration from mdbook internals.
 ///
 /// Roundtrips config to string, to avoid linking the plugin's internal version of toml
 /// to the one publically exposed by the mdbook library.
-pub(crate) fn admonish_config_from_context(ctx: &PreprocessorContext) -> Result<Config> {
+#[cfg(feature = "mdbook")]
+pub(crate) fn admonish_config_from_context(
+    ctx: &mdbook::preprocess::PreprocessorContext,
+) -> Result<Config> {
+    admonish_config(&ctx.root, &ctx.config)
+}
+
+/// Load the mdbook-admonish configuration of the book at `root`.
+#[cfg(feature = "mdbook")]
+pub(crate) fn admonish_config(root: &Path, book_config: &mdbook::Config) -> Result<Config> {
     let table: String = toml_mdbook::to_string(
-        ctx.config
+        book_config
             .get_preprocessor("admonish")
             .context("No configuration for mdbook-admonish in book.toml")?,
     )?;
-    toml::from_str(&table).context("Invalid mdbook-admonish configuration in book.toml")
+    let table: Table =
+        toml::from_str(&table).context("Invalid mdbook-admonish configuration in book.toml")?;
+    admonish_config_from_table(root, table, book_config.book.language.as_deref())
+}
+
+/// Load the mdbook-admonish configuration from the contents of a
+/// `[preprocessor.admonish]` table, for a book at `root` written in `language`.
+pub(crate) fn admonish_config_from_table(
+    root: &Path,
+    table: Table,
+    language: Option<&str>,
+) -> Result<Config> {
+    let table = with_shared_config(root, table)?;
+    let mut config: Config =
+        Table::try_into(table).context("Invalid mdbook-admonish configuration in book.toml")?;
+    config.default.directives = config.localized_directives(language)?;
+    config.default.directives.strict = config.strict;
+    Ok(config)
+}
+
+/// Merge the book configuration over a shared configuration file, if one is
+/// given with `config = "path/to/admonish.toml"`.
+///
+/// The path is relative to the book root directory.
+fn with_shared_config(root: &Path, mut table: Table) -> Result<Table> {
+    let path = match table.remove("config") {
+        None => return Ok(table),
+        Some(Value::String(path)) => root.join(path),
+        Some(_) => return Err(anyhow!("mdbook-admonish `config` must be a path")),
+    };
+    let shared = fs::read_to_string(&path)
+        .with_context(|| format!("Can't read shared configuration '{}'", path.display()))?;
+    let mut shared: Table = toml::from_str(&shared)
+        .with_context(|| format!("Invalid shared configuration '{}'", path.display()))?;
+    if shared.contains_key("config") {
+        return Err(anyhow!(
+            "Shared configuration '{}' can't include further configuration",
+            path.display()
+        ));
+    }
+    merge(&mut shared, table);
+    Ok(shared)
+}
+
+/// Recursively merge `overrides` into `base`. Tables are merged, and any
+/// other values replace those in `base`.
+fn merge(base: &mut Table, overrides: Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(overrides)) => merge(base, overrides),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
@@ -31,11 +109,808 @@ pub(crate) struct Config {
 
     #[serde(default)]
     pub assets_version: Option<String>,
+
+    /// Embed the stylesheet in each chapter, so no assets need installing.
+    #[serde(default)]
+    pub inline_css: bool,
+
+    #[serde(default)]
+    pub directive: HashMap<String, DirectiveConfig>,
+
+    /// Other names for directives, such as `nb = "note"`.
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
+
+    /// Default titles by language, then directive name, overriding the
+    /// builtin translations.
+    #[serde(default)]
+    pub translations: HashMap<String, HashMap<String, String>>,
+
+    #[serde(default)]
+    pub id_scheme: IdScheme,
+
+    #[serde(default)]
+    pub slug: SlugStrategy,
+
+    #[serde(default)]
+    pub anchor_links: AnchorLinks,
+
+    /// HTML to render inside each anchor link, in place of the stylesheet's
+    /// symbol.
+    #[serde(default)]
+    pub anchor_link_content: Option<String>,
+
+    /// Render a button in each title to copy the link to the block, for the
+    /// javascript asset to enable.
+    #[serde(default)]
+    pub copy_anchor_button: bool,
+
+    /// How the icon of each block is rendered.
+    #[serde(default)]
+    pub icon_mode: IconMode,
+
+    /// The visual style of blocks.
+    #[serde(default)]
+    pub preset: Preset,
+
+    #[serde(default)]
+    pub markup: Markup,
+
+    /// Render blocks with at least this many characters of content as
+    /// landmark regions.
+    #[serde(default)]
+    pub landmark_min_length: Option<usize>,
+
+    /// Treat blocks naming an unknown directive as invalid, rather than
+    /// rendering them as notes.
+    #[serde(default)]
+    pub strict: bool,
+
+    /// Render blocks as `aside` and `section` elements, with ARIA roles,
+    /// rather than `div` elements.
+    #[serde(default)]
+    pub semantic_html: bool,
+
+    #[serde(default)]
+    pub links: LinkPolicy,
+
+    #[serde(default)]
+    pub warnings: WarningsConfig,
+
+    #[serde(default)]
+    pub numbering: NumberingConfig,
+
+    #[serde(default)]
+    pub backlinks: BacklinksConfig,
+
+    #[serde(default)]
+    pub appendix: AppendixConfig,
+
+    #[serde(default)]
+    pub tag_index: TagIndexConfig,
+
+    #[serde(default)]
+    pub part: Vec<PartConfig>,
+
+    #[serde(default)]
+    pub wrapper: WrapperConfig,
+
+    /// Path to a template used to render all blocks, relative to the book
+    /// root. Directive templates take precedence.
+    #[serde(default)]
+    pub template: Option<PathBuf>,
+
+    /// Command to render each block, in place of the builtin HTML.
+    #[serde(default)]
+    pub external_renderer: Option<String>,
+
+    /// Directory to cache preprocessed chapters in between runs, relative to
+    /// the book root.
+    #[serde(default)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Annotate blocks with schema.org microdata.
+    #[serde(default)]
+    pub microdata: bool,
+
+    /// Other syntaxes to recognize as admonitions, as well as `admonish`
+    /// code blocks.
+    #[serde(default)]
+    pub syntax: Vec<Syntax>,
+
+    /// How to keep the title of blocks in `strip` render mode.
+    #[serde(default)]
+    pub strip_title: StripTitle,
+}
+
+/// Overrides for the chapters in part of the book.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub(crate) struct PartConfig {
+    /// Match chapters under this part title in `SUMMARY.md`.
+    #[serde(default)]
+    pub title: Option<String>,
+
+    /// Match chapters with a source path under this directory.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+
+    #[serde(default)]
+    pub default: DefaultsOverride,
+}
+
+#[cfg(feature = "mdbook")]
+impl PartConfig {
+    fn matches(&self, part_title: Option<&str>, chapter_path: Option<&Path>) -> bool {
+        let title_matches = self
+            .title
+            .as_deref()
+            .map_or(true, |title| part_title == Some(title));
+        let path_matches = self.path.as_deref().map_or(true, |prefix| {
+            chapter_path.map_or(false, |path| path.starts_with(prefix))
+        });
+        title_matches && path_matches
+    }
+}
+
+/// Automatic numbering of admonition titles, such as "Example 2".
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub(crate) struct NumberingConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Names of the directives to number. All directives are numbered if empty.
+    #[serde(default)]
+    pub directives: Vec<String>,
+
+    /// Prefix numbers with the chapter number, such as "Example 3.2".
+    #[serde(default)]
+    pub chapter_prefix: bool,
+}
+
+/// Links beneath each admonition to the chapters that reference it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub(crate) struct BacklinksConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Text before the links. Defaults to "Referenced from".
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+/// A generated chapter, appended to the book, listing every admonition.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub(crate) struct AppendixConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Title of the chapter. Defaults to "Admonitions".
+    #[serde(default)]
+    pub title: Option<String>,
+
+    /// Path of the chapter, relative to the book source directory. Defaults
+    /// to `admonitions.md`.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+
+    /// Names of the directives to list. All directives are listed if empty.
+    #[serde(default)]
+    pub directives: Vec<String>,
+
+    #[serde(default)]
+    pub group_by: AppendixGroup,
+
+    #[serde(default)]
+    pub sort: AppendixSort,
+}
+
+/// How admonitions are grouped in the appendix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AppendixGroup {
+    /// A section for each directive, builtin directives first.
+    #[default]
+    Directive,
+    /// A section for each chapter, in book order.
+    Chapter,
+    /// A single list, without sections.
+    None,
+}
+
+/// How admonitions are ordered within each group of the appendix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AppendixSort {
+    /// In the order they appear in the book.
+    #[default]
+    Book,
+    /// Alphabetically by title.
+    Title,
+}
+
+/// A generated chapter, appended to the book, listing tagged admonitions by
+/// tag.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub(crate) struct TagIndexConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Title of the chapter. Defaults to "Tags".
+    #[serde(default)]
+    pub title: Option<String>,
+
+    /// Path of the chapter, relative to the book source directory. Defaults
+    /// to `tags.md`.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub(crate) struct WarningsConfig {
+    /// Warnings which should not be reported anywhere in the book.
+    #[serde(default)]
+    pub allow: Vec<WarningCode>,
+
+    /// Fail the build if any warnings are reported.
+    #[serde(default)]
+    pub deny: bool,
+
+    #[serde(default)]
+    pub budget: BudgetConfig,
+}
+
+/// Maximum number of admonitions in each chapter.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub(crate) struct BudgetConfig {
+    #[serde(default)]
+    pub total: Option<usize>,
+
+    /// Keyed by directive name.
+    #[serde(default)]
+    pub directive: HashMap<String, usize>,
+}
+
+/// Attributes to add to links generated by the preprocessor.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub(crate) struct LinkPolicy {
+    /// Links within the book, such as anchor links.
+    #[serde(default)]
+    pub internal: LinkAttributes,
+
+    /// Links to other sites.
+    #[serde(default)]
+    pub external: LinkAttributes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub(crate) struct LinkAttributes {
+    #[serde(default)]
+    pub target: Option<String>,
+
+    #[serde(default)]
+    pub rel: Option<String>,
+}
+
+impl LinkPolicy {
+    /// HTML attributes to add to a link to `url`, including a leading space.
+    ///
+    /// `site_url` is the url the book is served from, so that absolute links
+    /// into the book are not external.
+    pub(crate) fn attributes(&self, url: &str, site_url: &str) -> String {
+        let LinkAttributes { target, rel } = if is_external(url, site_url) {
+            &self.external
+        } else {
+            &self.internal
+        };
+        let mut attributes = String::new();
+        for (name, value) in [("target", target), ("rel", rel)] {
+            if let Some(value) = value {
+                attributes.push_str(&format!(r#" {name}=""#));
+                escape_html(&mut attributes, value).expect("writing to a string is infallible");
+                attributes.push('"');
+            }
+        }
+        attributes
+    }
+}
+
+/// Whether a url points outside of the book, served from `site_url`.
+///
+/// Absolute urls on the host of `site_url` are part of the book.
+fn is_external(url: &str, site_url: &str) -> bool {
+    if url.starts_with("mailto:") {
+        return true;
+    }
+    match url_host(url) {
+        Some(host) => {
+            url_host(site_url).map_or(true, |site_host| !host.eq_ignore_ascii_case(site_host))
+        }
+        None => false,
+    }
+}
+
+/// The host of an absolute url, such as `example.com` for
+/// `https://example.com/docs/`, including any port.
+fn url_host(url: &str) -> Option<&str> {
+    let rest = match url.split_once("://") {
+        Some((scheme, rest))
+            if !scheme.is_empty()
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)) =>
+        {
+            rest
+        }
+        _ => url.strip_prefix("//")?,
+    };
+    rest.split(['/', '?', '#']).next()
+}
+
+/// How anchor ids are generated for admonitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum IdScheme {
+    /// Slugify the title, and prefix with `admonition-`.
+    #[default]
+    Prefixed,
+    /// Match mdbook heading anchors exactly, deduplicated against the
+    /// headings in each chapter.
+    Heading,
+    /// A hash of the directive, title and content, prefixed with
+    /// `admonition-`. Ids don't depend on the order of blocks.
+    Content,
+}
+
+/// How titles are turned into anchor ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum SlugStrategy {
+    /// Keep letters and digits from any script.
+    #[default]
+    KeepUnicode,
+    /// Replace accented Latin, Cyrillic and Greek letters with ASCII, else
+    /// use a hash of the title.
+    Transliterate,
+    /// Use a hash of the title.
+    Hash,
+}
+
+/// How links to the anchor of each admonition are generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AnchorLinks {
+    /// A bare fragment, such as `#admonition-note`.
+    #[default]
+    Fragment,
+    /// The path of the chapter from the site root, followed by the fragment,
+    /// such as `/guide/chapter.html#admonition-note`.
+    Absolute,
+    /// No anchor link is rendered.
+    None,
+}
+
+/// How the title of a block is kept when it is stripped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum StripTitle {
+    /// The title is removed, leaving only the content.
+    #[default]
+    None,
+    /// The title is kept as a bold paragraph.
+    Bold,
+    /// The title is kept as a heading.
+    Heading,
+}
+
+/// A visual style for blocks, applied by the stylesheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Preset {
+    /// A box with a shadow, rounded corners and a tinted title bar.
+    #[default]
+    Classic,
+    /// A box with a thin border, without a shadow or rounded corners.
+    Flat,
+    /// Classic, with less space around the title and content.
+    Compact,
+    /// Only a bar in the directive color beside the block, like a blockquote.
+    QuoteBar,
+}
+
+/// How the icon of a block is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum IconMode {
+    /// An SVG icon from the stylesheet.
+    #[default]
+    Css,
+    /// An emoji character in the title, which needs no stylesheet.
+    Emoji,
+}
+
+/// The flavour of markup generated for admonitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Markup {
+    /// HTML fragments, as expected by the mdbook HTML renderer.
+    #[default]
+    Html,
+    /// Well formed fragments, which can also be parsed as XML.
+    Xhtml,
+}
+
+/// A syntax for admonitions, other than `admonish` code blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Syntax {
+    /// GitHub alerts, such as `> [!NOTE]`.
+    Github,
+    /// MkDocs (python-markdown) admonitions, such as `!!! note "Title"`.
+    Mkdocs,
+    /// Pandoc fenced divs, such as `::: note`.
+    Pandoc,
+    /// Obsidian callouts, such as `> [!tip]- Title`.
+    Obsidian,
+}
+
+impl Syntax {
+    /// Text that every block written in this syntax contains.
+    pub(crate) fn markers(self) -> &'static [&'static str] {
+        match self {
+            Self::Github | Self::Obsidian => &["[!"],
+            Self::Mkdocs => &["!!!", "???"],
+            Self::Pandoc => &[":::"],
+        }
+    }
+}
+
+/// Configuration applied to all admonitions of a directive.
+///
+/// Configuring a directive that is not builtin defines a custom directive.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub(crate) struct DirectiveConfig {
+    /// Default title of blocks, in place of the directive name.
+    #[serde(default)]
+    pub title: Option<String>,
+
+    /// Other names for the directive.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+
+    /// CSS color of blocks, such as `"#e91e63"`.
+    #[serde(default)]
+    pub color: Option<String>,
+
+    /// CSS color of the title bar, in place of a tint of `color`.
+    #[serde(default)]
+    pub background: Option<String>,
+
+    /// CSS color of the icon, in place of `color`.
+    #[serde(default)]
+    pub icon_color: Option<String>,
+
+    /// Path to an SVG icon for blocks, relative to the book root.
+    #[serde(default)]
+    pub icon: Option<PathBuf>,
+
+    /// How the icon of blocks is rendered, in place of the global `icon_mode`.
+    #[serde(default)]
+    pub icon_mode: Option<IconMode>,
+
+    /// Emoji to render as the icon in `emoji` icon mode, such as `"🔒"`.
+    #[serde(default)]
+    pub emoji: Option<String>,
+
+    /// Path to a template file, relative to the book root.
+    #[serde(default)]
+    pub template: Option<PathBuf>,
+
+    #[serde(default)]
+    pub title_bar: TitleBarConfig,
+
+    /// Classnames to add to the body of blocks, if not set on the block.
+    #[serde(default)]
+    pub body_class: Option<String>,
+
+    /// Format for the title of blocks, such as `"⚠ {title}"`.
+    #[serde(default)]
+    pub title_format: Option<String>,
+
+    #[serde(default)]
+    pub wrapper: WrapperConfig,
+
+    /// Whether blocks are printed, if not set on the block.
+    #[serde(default)]
+    pub print: Option<bool>,
+
+    /// Whether blocks are only printed, if not set on the block.
+    #[serde(default)]
+    pub print_only: Option<bool>,
+
+    /// Whether blocks are placed in the page margin on wide screens, if not
+    /// set on the block.
+    #[serde(default)]
+    pub sidenote: Option<bool>,
+}
+
+/// Additional HTML to inject into the title bar, around the title text.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub(crate) struct TitleBarConfig {
+    #[serde(default)]
+    pub before: Option<String>,
+
+    #[serde(default)]
+    pub after: Option<String>,
+}
+
+/// Template snippets to render immediately before and after each block.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub(crate) struct WrapperConfig {
+    #[serde(default)]
+    pub before: Option<String>,
+
+    #[serde(default)]
+    pub after: Option<String>,
+}
+
+impl Config {
+    /// How blocks should be rendered for `renderer`.
+    pub(crate) fn render_modes(&self, renderer: &str) -> Result<RenderModes> {
+        let renderer_config = self.renderer.get(renderer);
+        // By default only render html for the html renderer
+        // For everything else, do nothing
+        let default = renderer_config
+            .and_then(|renderer| renderer.render_mode)
+            .unwrap_or(if renderer == "html" {
+                RenderMode::Html
+            } else {
+                RenderMode::Preserve
+            });
+        let directives = self.custom_directives()?;
+        let directive = renderer_config
+            .into_iter()
+            .flat_map(|renderer| renderer.directive.iter())
+            .map(|(name, mode)| {
+                let directive = directives.get(name).ok_or_else(|| {
+                    anyhow!("Unknown directive '{name}' in renderer '{renderer}' configuration")
+                })?;
+                Ok((directive, mode.text_mode()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(RenderModes {
+            default: default.text_mode(),
+            directive,
+        })
+    }
+
+    /// Ensure every directive with its own defaults exists.
+    pub(crate) fn check_defaults(&self) -> Result<()> {
+        match self
+            .default
+            .directive_overrides
+            .keys()
+            .find(|name| self.default.directives.get(name).is_none())
+        {
+            Some(name) => Err(anyhow!(
+                "Unknown directive '{name}' in mdbook-admonish default configuration"
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Ensure every part override matches by title or path.
+    pub(crate) fn check_parts(&self) -> Result<()> {
+        match self
+            .part
+            .iter()
+            .any(|part| part.title.is_none() && part.path.is_none())
+        {
+            true => Err(anyhow!(
+                "Each `part` in mdbook-admonish configuration must set `title` or `path`"
+            )),
+            false => Ok(()),
+        }
+    }
+
+    /// Defaults for a chapter, with the overrides of all matching parts
+    /// applied in order.
+    #[cfg(feature = "mdbook")]
+    pub(crate) fn chapter_defaults(
+        &self,
+        part_title: Option<&str>,
+        chapter_path: Option<&Path>,
+    ) -> Cow<'_, AdmonitionDefaults> {
+        self.part
+            .iter()
+            .filter(|part| part.matches(part_title, chapter_path))
+            .fold(Cow::Borrowed(&self.default), |defaults, part| {
+                Cow::Owned(defaults.with_overrides(&part.default))
+            })
+    }
+
+    /// Files named by the configuration that blocks are rendered with, such
+    /// as templates and icons, relative to the book root.
+    #[cfg(feature = "mdbook")]
+    pub(crate) fn rendering_files(&self) -> Vec<&Path> {
+        let mut files = self
+            .template
+            .iter()
+            .chain(
+                self.directive
+                    .values()
+                    .flat_map(|config| config.icon.iter().chain(config.template.iter())),
+            )
+            .map(PathBuf::as_path)
+            .collect::<Vec<_>>();
+        // Directives are unordered
+        files.sort();
+        files
+    }
+
+    /// Per-directive configuration, with directive names resolved.
+    pub(crate) fn directives(&self) -> Result<Vec<(Directive, &DirectiveConfig)>> {
+        self.directive
+            .iter()
+            .map(|(name, config)| {
+                let directive = directive_from_config_name(name)?;
+                for color in [&config.color, &config.background, &config.icon_color]
+                    .into_iter()
+                    .flatten()
+                {
+                    css_color(color)
+                        .map_err(|message| anyhow!("{message}, in directive '{name}'"))?;
+                }
+                Print::from_options(config.print, config.print_only)
+                    .map_err(|message| anyhow!("{message}, in directive '{name}'"))?;
+                Ok((directive, config))
+            })
+            .collect()
+    }
+
+    /// All directive names and aliases, including custom directives.
+    pub(crate) fn custom_directives(&self) -> Result<CustomDirectives> {
+        let mut directives = CustomDirectives::default();
+        for (name, config) in &self.directive {
+            let directive = directive_from_config_name(name)?;
+            if let Directive::Custom(_) = directive {
+                directives.names.insert(name.clone(), directive.clone());
+            }
+            for alias in &config.aliases {
+                if directives.get(alias).is_some() || self.directive.contains_key(alias) {
+                    return Err(anyhow!(
+                        "Alias '{alias}' of directive '{name}' is already a directive name or alias"
+                    ));
+                }
+                directives.names.insert(alias.clone(), directive.clone());
+            }
+            if let Some(title) = &config.title {
+                directives.titles.insert(directive, title.clone());
+            }
+        }
+        // Resolved once all directives are known, so they may alias custom
+        // directives, or their aliases
+        let mut aliases = Vec::with_capacity(self.aliases.len());
+        for (alias, target) in &self.aliases {
+            if directives.get(alias).is_some() || self.directive.contains_key(alias) {
+                return Err(anyhow!(
+                    "Alias '{alias}' is already a directive name or alias"
+                ));
+            }
+            let directive = directives
+                .get(target)
+                .ok_or_else(|| anyhow!("Unknown directive '{target}' for alias '{alias}'"))?;
+            aliases.push((alias.clone(), directive));
+        }
+        directives.names.extend(aliases);
+        Ok(directives)
+    }
+
+    /// The directives to list in the appendix, or `None` for all.
+    #[cfg(feature = "mdbook")]
+    pub(crate) fn appendix_directives(&self) -> Result<Option<HashSet<Directive>>> {
+        if self.appendix.directives.is_empty() {
+            return Ok(None);
+        }
+        let directives = self.custom_directives()?;
+        self.appendix
+            .directives
+            .iter()
+            .map(|name| {
+                directives
+                    .get(name)
+                    .ok_or_else(|| anyhow!("Unknown directive '{name}' in appendix configuration"))
+            })
+            .collect::<Result<_>>()
+            .map(Some)
+    }
+
+    /// Custom directives, with default titles translated for books in
+    /// `language`.
+    ///
+    /// Titles set with `directive.<name>.title` take precedence.
+    pub(crate) fn localized_directives(&self, language: Option<&str>) -> Result<CustomDirectives> {
+        let mut directives = self.custom_directives()?;
+        let language = match language {
+            Some(language) => language,
+            None => return Ok(directives),
+        };
+        for candidate in i18n::language_candidates(language) {
+            let translations = self
+                .translations
+                .iter()
+                .filter(|(code, _)| code.eq_ignore_ascii_case(candidate))
+                .flat_map(|(_, titles)| titles);
+            for (name, title) in translations {
+                let directive = directives.get(name).ok_or_else(|| {
+                    anyhow!("Unknown directive '{name}' in translations for '{candidate}'")
+                })?;
+                directives
+                    .titles
+                    .entry(directive)
+                    .or_insert_with(|| title.clone());
+            }
+        }
+        for directive in Directive::BUILTIN {
+            if let Some(title) = i18n::directive_title(language, directive) {
+                directives
+                    .titles
+                    .entry(directive.clone())
+                    .or_insert_with(|| title.to_owned());
+            }
+        }
+        Ok(directives)
+    }
+}
+
+/// The directive configured under `directive.<name>`, which is a custom
+/// directive if not builtin.
+fn directive_from_config_name(name: &str) -> Result<Directive> {
+    if let Ok(directive) = Directive::from_str(name) {
+        return Ok(directive);
+    }
+    static RX_DIRECTIVE_NAME: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r#"^[A-Za-z0-9_-]+$"#).expect("directive name regex"));
+    if !RX_DIRECTIVE_NAME.is_match(name) {
+        return Err(anyhow!(
+            "Invalid directive name '{name}'. Names may only contain letters, numbers, '-' and '_'"
+        ));
+    }
+    Ok(Directive::custom(name))
+}
+
+impl DirectiveConfig {
+    /// Whether blocks are styled with a custom color or icon.
+    pub(crate) fn is_styled(&self) -> bool {
+        self.color.is_some()
+            || self.background.is_some()
+            || self.icon_color.is_some()
+            || self.icon.is_some()
+    }
+}
+
+/// Check a color is a plain CSS color, such as `#e91e63` or `rgb(0 0 0)`.
+///
+/// Colors are rendered into a style attribute, so nothing else is permitted.
+fn css_color(color: &str) -> Result<(), String> {
+    static RX_CSS_COLOR: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r#"^(#[0-9A-Fa-f]{3,8}|[A-Za-z]+|[a-z]+\([0-9A-Za-z.,%/ -]*\))$"#)
+            .expect("css color regex")
+    });
+    match RX_CSS_COLOR.is_match(color) {
+        true => Ok(()),
+        false => Err(format!(
+            "Invalid color '{color}', expected a CSS color such as '#e91e63'"
+        )),
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub(crate) struct RendererConfig {
     pub render_mode: Option<RenderMode>,
+
+    /// Render modes for specific directives, keyed by directive name.
+    #[serde(default)]
+    pub directive: HashMap<String, RenderMode>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
@@ -44,17 +919,358 @@ pub(crate) enum RenderMode {
     Preserve,
     Strip,
     Html,
+    Drop,
+    Alerts,
+    Blockquote,
+    Latex,
+    Epub,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+impl RenderMode {
+    pub(crate) fn text_mode(self) -> RenderTextMode {
+        match self {
+            Self::Preserve => RenderTextMode::Preserve,
+            Self::Strip => RenderTextMode::Strip,
+            Self::Html => RenderTextMode::Html,
+            Self::Drop => RenderTextMode::Drop,
+            Self::Alerts => RenderTextMode::Alerts,
+            Self::Blockquote => RenderTextMode::Blockquote,
+            Self::Latex => RenderTextMode::Latex,
+            Self::Epub => RenderTextMode::Epub,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
 pub(crate) enum OnFailure {
     Bail,
+    #[default]
     Continue,
+    /// Leave the block as written, and warn.
+    Preserve,
 }
 
-impl Default for OnFailure {
-    fn default() -> Self {
-        Self::Continue
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn merge_shared_config() {
+        let mut shared: Table = toml::from_str(
+            r#"
+on_failure = "bail"
+assets_version = "3.0.0"
+
+[default]
+collapsible = true
+title = "Shared"
+
+[warnings]
+allow = ["deprecated-syntax"]
+"#,
+        )
+        .unwrap();
+        let book: Table = toml::from_str(
+            r#"
+assets_version = "3.1.0"
+
+[default]
+title = "Book"
+
+[warnings]
+allow = ["invalid-block"]
+"#,
+        )
+        .unwrap();
+        merge(&mut shared, book);
+
+        let config: Config = shared.try_into().unwrap();
+        assert_eq!(config.on_failure, OnFailure::Bail);
+        assert_eq!(config.assets_version.as_deref(), Some("3.1.0"));
+        assert_eq!(
+            config.default,
+            AdmonitionDefaults {
+                title: Some("Book".to_owned()),
+                collapsible: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(config.warnings.allow, vec![WarningCode::InvalidBlock]);
+    }
+
+    #[test]
+    fn shared_config_missing() {
+        let table: Table = toml::from_str(r#"config = "shared/admonish.toml""#).unwrap();
+        let error = with_shared_config(Path::new("/path/to/book"), table).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Can't read shared configuration '/path/to/book/shared/admonish.toml'"
+        );
+
+        let table: Table = toml::from_str(r#"on_failure = "bail""#).unwrap();
+        assert_eq!(
+            with_shared_config(Path::new("/path/to/book"), table.clone()).unwrap(),
+            table
+        );
+    }
+
+    #[cfg(feature = "mdbook")]
+    #[test]
+    fn part_defaults() {
+        let config: Config = toml::from_str(
+            r#"
+[default]
+title = "Book"
+
+[[part]]
+title = "Appendix"
+default = { collapsible = true }
+
+[[part]]
+path = "reference"
+default = { title = "Reference" }
+"#,
+        )
+        .unwrap();
+        config.check_parts().unwrap();
+
+        assert_eq!(
+            *config.chapter_defaults(None, Some(Path::new("intro.md"))),
+            config.default
+        );
+        assert_eq!(
+            *config.chapter_defaults(Some("Appendix"), Some(Path::new("glossary.md"))),
+            AdmonitionDefaults {
+                title: Some("Book".to_owned()),
+                collapsible: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            *config.chapter_defaults(Some("Appendix"), Some(Path::new("reference/api.md"))),
+            AdmonitionDefaults {
+                title: Some("Reference".to_owned()),
+                collapsible: true,
+                ..Default::default()
+            }
+        );
+        // Path prefixes match whole directory names only
+        assert_eq!(
+            *config.chapter_defaults(None, Some(Path::new("reference-guide.md"))),
+            config.default
+        );
+
+        let config: Config = toml::from_str("[[part]]\ndefault = { collapsible = true }").unwrap();
+        assert!(config.check_parts().is_err());
+    }
+
+    #[test]
+    fn render_modes_by_renderer() {
+        let config: Config = toml::from_str(
+            r#"
+[renderer.markdown]
+render_mode = "alerts"
+directive = { quote = "blockquote", note = "drop" }
+
+[renderer.epub]
+render_mode = "blockquote"
+"#,
+        )
+        .unwrap();
+        let modes = config.render_modes("markdown").unwrap();
+        assert_eq!(modes.get(&Directive::Warning), RenderTextMode::Alerts);
+        assert_eq!(modes.get(&Directive::Quote), RenderTextMode::Blockquote);
+        assert_eq!(modes.get(&Directive::Note), RenderTextMode::Drop);
+        let modes = config.render_modes("epub").unwrap();
+        assert_eq!(modes.get(&Directive::Warning), RenderTextMode::Blockquote);
+        // Unconfigured renderers keep the defaults
+        let modes = config.render_modes("html").unwrap();
+        assert_eq!(modes.get(&Directive::Warning), RenderTextMode::Html);
+        let modes = config.render_modes("test").unwrap();
+        assert_eq!(modes.get(&Directive::Warning), RenderTextMode::Preserve);
+    }
+
+    #[test]
+    fn custom_directives() {
+        let config: Config = toml::from_str(
+            r##"
+[directive.security]
+title = "Security notice"
+aliases = ["sec"]
+color = "#e91e63"
+
+[directive.warning]
+aliases = ["pitfall"]
+"##,
+        )
+        .unwrap();
+        let directives = config.custom_directives().unwrap();
+        let security = Directive::custom("security");
+        assert_eq!(directives.get("security"), Some(security.clone()));
+        assert_eq!(directives.get("sec"), Some(security.clone()));
+        assert_eq!(directives.get("pitfall"), Some(Directive::Warning));
+        assert_eq!(directives.get("caution"), Some(Directive::Warning));
+        assert_eq!(directives.get("unknown"), None);
+        assert_eq!(directives.title(&security), Some("Security notice"));
+        assert_eq!(directives.title(&Directive::Warning), None);
+        assert_eq!(config.directives().unwrap().len(), 2);
+
+        let config: Config = toml::from_str(
+            r#"
+directive.security = { aliases = ["sec"] }
+
+[aliases]
+nb = "note"
+achtung = "caution"
+secure = "sec"
+"#,
+        )
+        .unwrap();
+        let directives = config.custom_directives().unwrap();
+        assert_eq!(directives.get("nb"), Some(Directive::Note));
+        assert_eq!(directives.get("achtung"), Some(Directive::Warning));
+        assert_eq!(directives.get("secure"), Some(security));
+
+        for invalid in [
+            // Aliases must be unique
+            r#"directive.security.aliases = ["note"]"#,
+            "directive.security.aliases = [\"tip\"]\ndirective.tip = {}",
+            r#"aliases.tip = "note""#,
+            "aliases.sec = \"note\"\ndirective.security.aliases = [\"sec\"]",
+            // Aliases must name a directive
+            r#"aliases.nb = "unknown""#,
+            // Names and colors are written into the output
+            r#"directive."a b" = {}"#,
+            r#"directive.security.color = "red; display: none""#,
+        ] {
+            let config: Config = toml::from_str(invalid).unwrap();
+            assert!(
+                config
+                    .custom_directives()
+                    .and_then(|_| config.directives())
+                    .is_err(),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn localized_directives() {
+        let config: Config = toml::from_str(
+            r#"
+[directive.warning]
+title = "Achtung"
+
+[directive.security]
+
+[translations.de]
+tip = "Ratschlag"
+security = "Sicherheit"
+"#,
+        )
+        .unwrap();
+        let directives = config.localized_directives(Some("de-CH")).unwrap();
+        assert_eq!(directives.title(&Directive::Note), Some("Hinweis"));
+        assert_eq!(directives.title(&Directive::Tip), Some("Ratschlag"));
+        assert_eq!(directives.title(&Directive::Warning), Some("Achtung"));
+        assert_eq!(
+            directives.title(&Directive::custom("security")),
+            Some("Sicherheit")
+        );
+
+        let directives = config.localized_directives(Some("en")).unwrap();
+        assert_eq!(directives.title(&Directive::Note), None);
+        assert_eq!(directives.title(&Directive::Warning), Some("Achtung"));
+
+        let config: Config = toml::from_str("translations.de.unknown = \"?\"").unwrap();
+        assert!(config.localized_directives(Some("de")).is_err());
+    }
+
+    #[test]
+    fn directive_defaults() {
+        let mut config: Config = toml::from_str(
+            r#"
+[default.warning]
+collapsible = true
+
+[default.security]
+title = "Security"
+
+[directive.security]
+"#,
+        )
+        .unwrap();
+        config.default.directives = config.custom_directives().unwrap();
+        config.check_defaults().unwrap();
+
+        config.directive.clear();
+        config.default.directives = config.custom_directives().unwrap();
+        assert!(config.check_defaults().is_err());
+    }
+
+    #[test]
+    fn misspelled_default() {
+        let error = toml::from_str::<Config>(
+            r#"
+[default]
+colapsible = true
+"#,
+        )
+        .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("unknown key `colapsible` in [default]"),
+            "{error}"
+        );
+
+        let error = toml::from_str::<Config>(
+            r#"
+[default.warning]
+collapsible = "yes"
+"#,
+        )
+        .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("invalid type: string \"yes\", expected a boolean in [default.warning]"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn link_policy_attributes() {
+        let policy = LinkPolicy {
+            internal: LinkAttributes::default(),
+            external: LinkAttributes {
+                target: Some("_blank".to_owned()),
+                rel: Some("noopener \"noreferrer\"".to_owned()),
+            },
+        };
+        let external = r#" target="_blank" rel="noopener &quot;noreferrer&quot;""#;
+        assert_eq!(policy.attributes("#admonition-note", "/"), "");
+        assert_eq!(policy.attributes("../chapter.md", "/"), "");
+        assert_eq!(policy.attributes("https://example.com", "/"), external);
+        assert_eq!(policy.attributes("//example.com", "/"), external);
+        assert_eq!(policy.attributes("mailto:docs@example.com", "/"), external);
+
+        // Absolute links into the book, served from `site-url`
+        let site_url = "https://docs.example.com/book/";
+        assert_eq!(
+            policy.attributes(
+                "https://docs.example.com/book/intro.html#admonition-note",
+                site_url
+            ),
+            ""
+        );
+        assert_eq!(policy.attributes("//Docs.Example.com/book/", site_url), "");
+        assert_eq!(policy.attributes("https://example.com", site_url), external);
+        assert_eq!(
+            policy.attributes("https://docs.example.com.evil/", site_url),
+            external
+        );
     }
 }
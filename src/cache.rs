@@ -0,0 +1,139 @@
+use anyhow::{Context, Result};
+use mdbook::book::Chapter;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::{index::IndexEntry, slug::hash_hex};
+
+const CACHE_FILE: &str = "chapters.json";
+
+/// Chapters preprocessed by a previous run, so that rebuilds (such as with
+/// `mdbook serve`) only reprocess chapters that have changed.
+#[derive(Debug)]
+pub(crate) struct ChapterCache {
+    dir: PathBuf,
+    /// Hash of everything, other than the chapter, that affects the output.
+    ///
+    /// Hashes are stable across builds of the preprocessor, as the cache is
+    /// kept between them.
+    config_hash: String,
+    previous: HashMap<String, CachedChapter>,
+    /// Chapters seen by this run, which are kept for the next.
+    current: HashMap<String, CachedChapter>,
+}
+
+/// The output of preprocessing a single chapter.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct CachedChapter {
+    pub(crate) content: String,
+    /// The admonitions rendered in the chapter.
+    pub(crate) entries: Vec<IndexEntry>,
+}
+
+impl ChapterCache {
+    /// An empty cache, to be saved in `dir`.
+    ///
+    /// `config` describes everything, other than the chapter, that affects the
+    /// output, such as the whole book configuration.
+    pub(crate) fn new(dir: PathBuf, config: &str) -> Self {
+        let inputs = (env!("CARGO_PKG_VERSION"), config);
+        Self {
+            dir,
+            config_hash: hash_hex(&serde_json::to_vec(&inputs).expect("cache inputs to serialize")),
+            previous: HashMap::new(),
+            current: HashMap::new(),
+        }
+    }
+
+    /// The cache saved in `dir` by a previous run.
+    ///
+    /// A missing or unreadable cache is treated as empty, as it will be
+    /// rebuilt.
+    pub(crate) fn load(dir: PathBuf, config: &str) -> Self {
+        let mut cache = Self::new(dir, config);
+        let path = cache.dir.join(CACHE_FILE);
+        if let Ok(json) = std::fs::read_to_string(&path) {
+            match serde_json::from_str(&json) {
+                Ok(previous) => cache.previous = previous,
+                Err(error) => log::debug!("Ignoring cache '{}': {error}", path.display()),
+            }
+        }
+        cache
+    }
+
+    /// The key of `chapter`, in the part titled `part_title`.
+    pub(crate) fn key(&self, chapter: &Chapter, part_title: Option<&str>) -> String {
+        let inputs = (
+            &chapter.name,
+            &chapter.content,
+            chapter.number.as_ref().map(|number| &number.0),
+            &chapter.path,
+            part_title,
+        );
+        let chapter_hash = hash_hex(&serde_json::to_vec(&inputs).expect("chapter to serialize"));
+        format!("{}{chapter_hash}", self.config_hash)
+    }
+
+    /// The chapter preprocessed with `key`, if any.
+    pub(crate) fn get(&mut self, key: &str) -> Option<CachedChapter> {
+        let cached = self
+            .previous
+            .remove(key)
+            .or_else(|| self.current.get(key).cloned())?;
+        self.current.insert(key.to_owned(), cached.clone());
+        Some(cached)
+    }
+
+    pub(crate) fn insert(&mut self, key: String, chapter: CachedChapter) {
+        self.current.insert(key, chapter);
+    }
+
+    /// Save the chapters seen by this run, discarding any others.
+    pub(crate) fn save(&self) -> Result<()> {
+        let path = self.dir.join(CACHE_FILE);
+        std::fs::create_dir_all(&self.dir).with_context(|| {
+            format!("Unable to create cache directory '{}'", self.dir.display())
+        })?;
+        std::fs::write(&path, serde_json::to_string(&self.current)?)
+            .with_context(|| format!("Unable to write cache '{}'", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn chapter(content: &str) -> Chapter {
+        Chapter::new("Chapter", content.to_owned(), "chapter.md", vec![])
+    }
+
+    #[test]
+    fn key_depends_on_chapter_and_config() {
+        let cache = ChapterCache::new(PathBuf::from("cache"), "config");
+        let key = cache.key(&chapter("Content."), None);
+        assert_eq!(key, cache.key(&chapter("Content."), None));
+        assert_ne!(key, cache.key(&chapter("Changed."), None));
+        assert_ne!(key, cache.key(&chapter("Content."), Some("Part")));
+
+        let changed = ChapterCache::new(PathBuf::from("cache"), "changed config");
+        assert_ne!(key, changed.key(&chapter("Content."), None));
+    }
+
+    #[test]
+    fn get_keeps_chapters_seen() {
+        let mut cache = ChapterCache::new(PathBuf::from("cache"), "config");
+        let cached = CachedChapter {
+            content: "Rendered.".to_owned(),
+            entries: vec![],
+        };
+        cache.previous.insert("seen".to_owned(), cached.clone());
+        cache.previous.insert("unseen".to_owned(), cached.clone());
+
+        assert_eq!(Some(cached.clone()), cache.get("seen"));
+        assert_eq!(Some(cached), cache.get("seen"));
+        assert_eq!(None, cache.get("missing"));
+        assert_eq!(vec!["seen"], cache.current.keys().collect::<Vec<_>>());
+    }
+}
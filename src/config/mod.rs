@@ -1,20 +1,41 @@
 mod v1;
 mod v2;
 
+use crate::types::{Align, Attach, Float, Print, Size};
+
 /// Configuration as described by the instance of an admonition in markdown.
 ///
 /// This structure represents the configuration the user must provide in each
 /// instance.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Default)]
 pub(crate) struct InstanceConfig {
     pub(crate) directive: String,
     pub(crate) title: Option<String>,
+    /// Anchor id to use, rather than one generated from the title.
+    pub(crate) id: Option<String>,
     pub(crate) additional_classnames: Vec<String>,
     pub(crate) collapsible: Option<bool>,
+    /// Whether a collapsible block is initially open.
+    pub(crate) open: bool,
+    /// Whether the title has an icon.
+    pub(crate) icon: Option<bool>,
+    pub(crate) body_classnames: Vec<String>,
+    pub(crate) url: Option<String>,
+    pub(crate) attach: Option<Attach>,
+    pub(crate) size: Size,
+    pub(crate) align: Option<Align>,
+    pub(crate) float: Option<Float>,
+    /// Whether the block is placed in the page margin, on wide screens.
+    pub(crate) sidenote: Option<bool>,
+    pub(crate) numbered: Option<bool>,
+    pub(crate) print: Option<Print>,
+    pub(crate) tags: Vec<String>,
+    /// Whether this was written in the deprecated v1 syntax.
+    pub(crate) deprecated_syntax: bool,
 }
 
 /// Extract the remaining info string, if this is an admonition block.
-fn admonition_config_string(info_string: &str) -> Option<&str> {
+pub(crate) fn admonition_config_string(info_string: &str) -> Option<&str> {
     const ADMONISH_BLOCK_KEYWORD: &str = "admonish";
 
     // Get the rest of the info string if this is an admonition
@@ -71,6 +92,8 @@ mod test {
                 title: None,
                 additional_classnames: vec!["additional-classname".to_owned()],
                 collapsible: None,
+                deprecated_syntax: true,
+                ..Default::default()
             }
         );
         // v2 syntax is supported
@@ -83,6 +106,7 @@ mod test {
                 title: Some("Custom Title".to_owned()),
                 additional_classnames: Vec::new(),
                 collapsible: None,
+                ..Default::default()
             }
         );
     }
@@ -1,4 +1,5 @@
 use super::InstanceConfig;
+use crate::types::Size;
 use once_cell::sync::Lazy;
 use regex::Regex;
 
@@ -52,8 +53,22 @@ pub(crate) fn from_config_string(config_string: &str) -> Result<InstanceConfig,
     Ok(InstanceConfig {
         directive: directive.to_owned(),
         title,
+        id: None,
         additional_classnames,
         collapsible: None,
+        body_classnames: Vec::new(),
+        url: None,
+        attach: None,
+        align: None,
+        float: None,
+        sidenote: None,
+        size: Size::default(),
+        numbered: None,
+        print: None,
+        tags: Vec::new(),
+        open: false,
+        icon: None,
+        deprecated_syntax: true,
     })
 }
 
@@ -71,6 +86,8 @@ mod test {
                 title: None,
                 additional_classnames: Vec::new(),
                 collapsible: None,
+                deprecated_syntax: true,
+                ..Default::default()
             }
         );
         assert_eq!(
@@ -80,6 +97,8 @@ mod test {
                 title: None,
                 additional_classnames: Vec::new(),
                 collapsible: None,
+                deprecated_syntax: true,
+                ..Default::default()
             }
         );
         assert_eq!(
@@ -89,6 +108,8 @@ mod test {
                 title: None,
                 additional_classnames: Vec::new(),
                 collapsible: None,
+                deprecated_syntax: true,
+                ..Default::default()
             }
         );
         assert_eq!(
@@ -98,6 +119,8 @@ mod test {
                 title: None,
                 additional_classnames: Vec::new(),
                 collapsible: None,
+                deprecated_syntax: true,
+                ..Default::default()
             }
         );
         assert_eq!(
@@ -107,6 +130,8 @@ mod test {
                 title: None,
                 additional_classnames: vec!["additional-classname".to_owned()],
                 collapsible: None,
+                deprecated_syntax: true,
+                ..Default::default()
             }
         );
     }
@@ -1,18 +1,49 @@
 use super::InstanceConfig;
+use crate::types::{Align, Attach, Float, Print, Size};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::Deserialize;
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 struct UserInput {
-    #[serde(default)]
+    #[serde(default, alias = "directive")]
     r#type: Option<String>,
     #[serde(default)]
     title: Option<String>,
     #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
     class: Option<String>,
     #[serde(default)]
     collapsible: Option<bool>,
+    #[serde(default)]
+    open: Option<bool>,
+    #[serde(default)]
+    icon: Option<bool>,
+    #[serde(default)]
+    body_class: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    attach: Option<Attach>,
+    #[serde(default)]
+    width: Option<String>,
+    #[serde(default)]
+    max_width: Option<String>,
+    #[serde(default)]
+    align: Option<Align>,
+    #[serde(default)]
+    float: Option<Float>,
+    #[serde(default)]
+    sidenote: Option<bool>,
+    #[serde(default)]
+    numbered: Option<bool>,
+    #[serde(default)]
+    print: Option<bool>,
+    #[serde(default)]
+    print_only: Option<bool>,
+    #[serde(default)]
+    tags: Vec<String>,
 }
 
 /// Transform our config string into valid toml
@@ -71,12 +102,89 @@ pub(crate) fn from_config_string(config_string: &str) -> Result<InstanceConfig,
                 Ok(config) => config,
                 Err(_) => return original_error,
             };
+            if let Some(r#type) = config.r#type {
+                return Err(format!(
+                    "Directive given both positionally ('{directive}') and as a key ('{type}'), use only one"
+                ));
+            }
             config.r#type = Some(directive.to_owned());
             config
         }
     };
-    let additional_classnames = config
-        .class
+    let size = Size {
+        width: config.width.map(css_length).transpose()?,
+        max_width: config.max_width.map(css_length).transpose()?,
+    };
+    Ok(InstanceConfig {
+        directive: config.r#type.unwrap_or_default(),
+        title: config.title,
+        id: config.id.map(anchor_id).transpose()?,
+        additional_classnames: split_classnames(config.class),
+        // An open block is collapsible, unless set otherwise
+        collapsible: match (config.collapsible, config.open) {
+            (None, Some(true)) => Some(true),
+            (collapsible, _) => collapsible,
+        },
+        open: config.open.unwrap_or_default(),
+        icon: config.icon,
+        body_classnames: split_classnames(config.body_class),
+        url: config.url,
+        attach: config.attach,
+        size,
+        align: config.align,
+        float: config.float,
+        sidenote: config.sidenote,
+        numbered: config.numbered,
+        print: Print::from_options(config.print, config.print_only)?,
+        tags: config.tags.into_iter().map(tag).collect::<Result<_, _>>()?,
+        deprecated_syntax: false,
+    })
+}
+
+/// Check a size is a plain CSS length, such as `60%` or `40rem`.
+///
+/// Sizes are rendered into a style attribute, so nothing else is permitted.
+fn css_length(length: String) -> Result<String, String> {
+    static RX_CSS_LENGTH: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r#"^(0|[0-9]+(\.[0-9]+)?(%|px|em|rem|ch|ex|vw|vh|cm|mm|in|pt|pc))$"#)
+            .expect("css length regex")
+    });
+
+    if !RX_CSS_LENGTH.is_match(&length) {
+        return Err(format!(
+            "Invalid size '{length}', expected a CSS length such as '60%' or '40rem'"
+        ));
+    }
+    Ok(length)
+}
+
+/// Check an id is usable as an anchor, without escaping.
+fn anchor_id(id: String) -> Result<String, String> {
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        return Err(format!(
+            "Invalid id '{id}', expected only letters, digits, '-', '_' and '.'"
+        ));
+    }
+    Ok(id)
+}
+
+/// Check a tag is a single word, as tags are rendered space separated.
+fn tag(tag: String) -> Result<String, String> {
+    if tag.is_empty() || tag.contains(char::is_whitespace) {
+        return Err(format!(
+            "Invalid tag '{tag}', tags can't be empty or contain spaces"
+        ));
+    }
+    Ok(tag)
+}
+
+/// Split a space separated list of classnames.
+fn split_classnames(class: Option<String>) -> Vec<String> {
+    class
         .map(|class| {
             class
                 .split(' ')
@@ -84,13 +192,7 @@ pub(crate) fn from_config_string(config_string: &str) -> Result<InstanceConfig,
                 .map(|classname| classname.to_owned())
                 .collect()
         })
-        .unwrap_or_default();
-    Ok(InstanceConfig {
-        directive: config.r#type.unwrap_or_default(),
-        title: config.title,
-        additional_classnames,
-        collapsible: config.collapsible,
-    })
+        .unwrap_or_default()
 }
 
 #[cfg(test)]
@@ -107,6 +209,7 @@ mod test {
                 title: None,
                 additional_classnames: Vec::new(),
                 collapsible: None,
+                ..Default::default()
             }
         );
         assert_eq!(
@@ -116,6 +219,7 @@ mod test {
                 title: None,
                 additional_classnames: Vec::new(),
                 collapsible: None,
+                ..Default::default()
             }
         );
         assert_eq!(
@@ -128,8 +232,122 @@ mod test {
                 title: Some("Никита".to_owned()),
                 additional_classnames: vec!["additional".to_owned(), "classname".to_owned()],
                 collapsible: Some(true),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            from_config_string(r#"note body_class="scrollable wide""#).unwrap(),
+            InstanceConfig {
+                directive: "note".to_owned(),
+                body_classnames: vec!["scrollable".to_owned(), "wide".to_owned()],
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            from_config_string(r#"tip url="../guide.md""#).unwrap(),
+            InstanceConfig {
+                directive: "tip".to_owned(),
+                url: Some("../guide.md".to_owned()),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            from_config_string(r#"note attach="above""#).unwrap(),
+            InstanceConfig {
+                directive: "note".to_owned(),
+                attach: Some(Attach::Above),
+                ..Default::default()
+            }
+        );
+        assert!(from_config_string(r#"note attach="sideways""#).is_err());
+        assert_eq!(
+            from_config_string(r#"tip width="50%" align="center""#).unwrap(),
+            InstanceConfig {
+                directive: "tip".to_owned(),
+                size: Size {
+                    width: Some("50%".to_owned()),
+                    max_width: None,
+                },
+                align: Some(Align::Center),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            from_config_string(r#"tip float="right""#).unwrap(),
+            InstanceConfig {
+                directive: "tip".to_owned(),
+                float: Some(Float::Right),
+                ..Default::default()
             }
         );
+        assert!(from_config_string(r#"tip float="center""#).is_err());
+        assert_eq!(
+            from_config_string(r#"note sidenote=true"#).unwrap(),
+            InstanceConfig {
+                directive: "note".to_owned(),
+                sidenote: Some(true),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            from_config_string(r#"tip print=false"#).unwrap(),
+            InstanceConfig {
+                directive: "tip".to_owned(),
+                print: Some(Print::Never),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            from_config_string(r#"note print_only=true"#).unwrap(),
+            InstanceConfig {
+                directive: "note".to_owned(),
+                print: Some(Print::Only),
+                ..Default::default()
+            }
+        );
+        assert!(from_config_string(r#"note print=false print_only=true"#).is_err());
+        assert_eq!(
+            from_config_string(r#"warning id="stable-anchor""#).unwrap(),
+            InstanceConfig {
+                directive: "warning".to_owned(),
+                id: Some("stable-anchor".to_owned()),
+                ..Default::default()
+            }
+        );
+        assert!(from_config_string(r#"note id="two words""#).is_err());
+        assert_eq!(
+            from_config_string(r#"warning tags=["gotcha", "windows"]"#).unwrap(),
+            InstanceConfig {
+                directive: "warning".to_owned(),
+                tags: vec!["gotcha".to_owned(), "windows".to_owned()],
+                ..Default::default()
+            }
+        );
+        assert!(from_config_string(r#"note tags=["two words"]"#).is_err());
+        assert!(from_config_string(r#"note id="""#).is_err());
+        assert_eq!(
+            from_config_string(r#"example numbered=false"#).unwrap(),
+            InstanceConfig {
+                directive: "example".to_owned(),
+                numbered: Some(false),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            from_config_string(r#"note width="60%" max_width="40rem""#).unwrap(),
+            InstanceConfig {
+                directive: "note".to_owned(),
+                size: Size {
+                    width: Some("60%".to_owned()),
+                    max_width: Some("40rem".to_owned()),
+                },
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            from_config_string(r#"note width="1px;color:red""#).unwrap_err(),
+            "Invalid size '1px;color:red', expected a CSS length such as '60%' or '40rem'"
+        );
         // Specifying unknown keys is okay, as long as they're valid
         assert_eq!(
             from_config_string(r#"unkonwn="but valid toml""#).unwrap(),
@@ -138,6 +356,7 @@ mod test {
                 title: None,
                 additional_classnames: Vec::new(),
                 collapsible: None,
+                ..Default::default()
             }
         );
         // Just directive is fine
@@ -148,6 +367,7 @@ mod test {
                 title: None,
                 additional_classnames: Vec::new(),
                 collapsible: None,
+                ..Default::default()
             }
         );
         // Directive plus toml config
@@ -158,12 +378,69 @@ mod test {
                 title: Some("Information".to_owned()),
                 additional_classnames: Vec::new(),
                 collapsible: Some(false),
+                ..Default::default()
             }
         );
         // Directive after toml config is an error
         assert!(from_config_string(r#"title="Information" info"#).is_err());
     }
 
+    #[test]
+    fn test_from_config_string_directive_key() {
+        for config_string in [
+            r#"type="warning" title="Careful""#,
+            r#"title="Careful" directive="warning""#,
+        ] {
+            assert_eq!(
+                from_config_string(config_string).unwrap(),
+                InstanceConfig {
+                    directive: "warning".to_owned(),
+                    title: Some("Careful".to_owned()),
+                    ..Default::default()
+                }
+            );
+        }
+        assert_eq!(
+            from_config_string(r#"note type="warning""#).unwrap_err(),
+            "Directive given both positionally ('note') and as a key ('warning'), use only one"
+        );
+        assert!(from_config_string(r#"type="note" directive="warning""#).is_err());
+    }
+
+    #[test]
+    fn test_from_config_string_icon() {
+        assert_eq!(
+            from_config_string(r#"note icon=false"#).unwrap(),
+            InstanceConfig {
+                directive: "note".to_owned(),
+                icon: Some(false),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_from_config_string_open() {
+        assert_eq!(
+            from_config_string(r#"note open=true"#).unwrap(),
+            InstanceConfig {
+                directive: "note".to_owned(),
+                collapsible: Some(true),
+                open: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            from_config_string(r#"note collapsible=false open=true"#).unwrap(),
+            InstanceConfig {
+                directive: "note".to_owned(),
+                collapsible: Some(false),
+                open: true,
+                ..Default::default()
+            }
+        );
+    }
+
     #[test]
     fn test_from_config_string_invalid_toml_value() {
         assert_eq!(
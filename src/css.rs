@@ -0,0 +1,251 @@
+use anyhow::Result;
+use std::{collections::HashSet, path::Path};
+
+use crate::{
+    book_config::{Config, DirectiveConfig},
+    render::icon_url,
+    types::Directive,
+};
+
+/// The builtin stylesheet, as installed by `mdbook-admonish install`.
+pub(crate) const STYLESHEET: &str = include_str!("bin/assets/mdbook-admonish.css");
+
+/// Rules for the high contrast variant of the stylesheet.
+///
+/// Titles are untinted, so their text has the full contrast of the page, and
+/// are set apart by a border in the directive color instead.
+const HIGH_CONTRAST: &str = r#"
+:root {
+  --admonish-title-tint: 0%;
+}
+
+:is(.admonition) {
+  border-width: 0.2rem;
+  border-inline-start-width: 0.6rem;
+}
+
+:is(.admonition-title, summary.admonition-title) {
+  border-block-end: 0.2rem solid;
+  border-block-end-color: inherit;
+}
+
+button.admonition-copy-anchor {
+  opacity: 1;
+}
+"#;
+
+/// Options for [`generate_css`].
+#[derive(Debug, Default, Clone)]
+pub struct CssOptions {
+    /// Scan the book, and leave out styles for builtin directives it doesn't use.
+    pub used_only: bool,
+    /// Append rules for a high contrast variant, with untinted titles and
+    /// heavier borders.
+    pub high_contrast: bool,
+}
+
+/// Generate the stylesheet for the book at `book_root`, with the colors and
+/// icons configured for each directive in `book.toml`.
+pub fn generate_css(book_root: &Path, options: &CssOptions) -> Result<String> {
+    let book_config = mdbook::Config::from_disk(book_root.join("book.toml"))?;
+    let config = crate::book_config::admonish_config(book_root, &book_config)?;
+    let used = match options.used_only {
+        true => Some(crate::scan::used_directives(book_root)?),
+        false => None,
+    };
+    let mut css = stylesheet(book_root, &config, used.as_ref())?;
+    if options.high_contrast {
+        css.push_str(HIGH_CONTRAST);
+    }
+    Ok(css)
+}
+
+/// The builtin stylesheet, followed by rules for each configured directive.
+///
+/// If `used` is given, styles for other builtin directives are left out.
+pub(crate) fn stylesheet(
+    root: &Path,
+    config: &Config,
+    used: Option<&HashSet<Directive>>,
+) -> Result<String> {
+    let mut directives = config.directives()?;
+    directives.sort_by_key(|(directive, _)| directive.classname());
+    let mut css = match used {
+        Some(used) => shake(STYLESHEET, used),
+        None => STYLESHEET.to_owned(),
+    };
+    for (directive, directive_config) in directives {
+        css.push_str(&directive_rules(root, &directive, directive_config)?);
+    }
+    Ok(css)
+}
+
+/// Remove the styles for builtin directives not in `used` from `css`.
+///
+/// `note` is always kept, as custom directives fall back to its icon, and so
+/// is `bug`, as blocks that fail to parse are rendered with it.
+///
+/// This relies on the layout of the compiled stylesheet, with one declaration
+/// per line, and each flavour styled by rules selecting only its classnames.
+fn shake(css: &str, used: &HashSet<Directive>) -> String {
+    let unused = Directive::BUILTIN
+        .iter()
+        .filter(|directive| {
+            !matches!(directive, Directive::Note | Directive::Bug) && !used.contains(directive)
+        })
+        .map(|directive| directive.classname())
+        .collect::<Vec<_>>();
+    let selects_unused = |prelude: &str| {
+        unused.iter().any(|classname| {
+            prelude.contains(&format!(".{classname},"))
+                || prelude.contains(&format!(".{classname})"))
+        })
+    };
+    let declares_unused = |line: &str| {
+        let line = line.trim_start();
+        unused.iter().any(|classname| {
+            line.starts_with(&format!("--md-admonition-icon--{classname}:"))
+                || line.starts_with(&format!("--{classname}-color:"))
+        })
+    };
+
+    let mut shaken = String::with_capacity(css.len());
+    // The top level rule being read, kept until it is complete
+    let mut rule = String::new();
+    let mut depth = 0_usize;
+    for line in css.lines() {
+        if declares_unused(line) {
+            continue;
+        }
+        rule.push_str(line);
+        rule.push('\n');
+        depth += line.matches('{').count();
+        depth = depth.saturating_sub(line.matches('}').count());
+        if depth > 0 || (!rule.contains('{') && !line.trim().is_empty()) {
+            continue;
+        }
+        let prelude = rule.split('{').next().unwrap_or_default();
+        if !selects_unused(prelude) {
+            shaken.push_str(&rule);
+        }
+        rule.clear();
+    }
+    shaken.push_str(&rule);
+    shaken
+}
+
+/// Rules for the colors and icon configured for `directive`, if any.
+///
+/// These match the properties set on blocks of styled directives, so blocks
+/// look the same without them.
+fn directive_rules(root: &Path, directive: &Directive, config: &DirectiveConfig) -> Result<String> {
+    let block = format!(":is(.admonition).{}", directive.classname());
+    let title = format!("{block} > :is(.admonition-title, summary.admonition-title)");
+    let mut rules = Vec::new();
+    if let Some(color) = &config.color {
+        rules.push((block.clone(), vec![format!("border-color: {color}")]));
+    }
+    let background = match (&config.background, &config.color) {
+        (Some(background), _) => Some(background.clone()),
+        (None, Some(color)) => Some(format!(
+            "color-mix(in srgb, {color} var(--admonish-title-tint), transparent)"
+        )),
+        (None, None) => None,
+    };
+    if let Some(background) = background {
+        rules.push((
+            title.clone(),
+            vec![format!("background-color: {background}")],
+        ));
+    }
+    let mut icon = Vec::new();
+    if let Some(icon_color) = config.icon_color.as_ref().or(config.color.as_ref()) {
+        icon.push(format!("background-color: {icon_color}"));
+    }
+    if let Some(path) = &config.icon {
+        let url = icon_url(root, path)?;
+        icon.push(format!("mask-image: {url}"));
+        icon.push(format!("-webkit-mask-image: {url}"));
+    }
+    if !icon.is_empty() {
+        rules.push((format!("{title}::before"), icon));
+    }
+
+    let mut css = String::new();
+    for (selector, declarations) in rules {
+        css.push_str(&format!("\n{selector} {{\n"));
+        for declaration in declarations {
+            css.push_str(&format!("  {declaration};\n"));
+        }
+        css.push_str("}\n");
+    }
+    Ok(css)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn rules_for_directives() {
+        let config: Config = toml::from_str(
+            r##"
+[directive.warning]
+color = "#ff5722"
+icon_color = "black"
+
+[directive.security]
+background = "rgb(233 30 99 / 20%)"
+
+[directive.note]
+title = "Remember"
+"##,
+        )
+        .unwrap();
+        let css = stylesheet(Path::new("."), &config, None).unwrap();
+        assert_eq!(
+            css.strip_prefix(STYLESHEET).unwrap(),
+            r#"
+:is(.admonition).admonish-security > :is(.admonition-title, summary.admonition-title) {
+  background-color: rgb(233 30 99 / 20%);
+}
+
+:is(.admonition).admonish-warning {
+  border-color: #ff5722;
+}
+
+:is(.admonition).admonish-warning > :is(.admonition-title, summary.admonition-title) {
+  background-color: color-mix(in srgb, #ff5722 var(--admonish-title-tint), transparent);
+}
+
+:is(.admonition).admonish-warning > :is(.admonition-title, summary.admonition-title)::before {
+  background-color: black;
+}
+"#
+        );
+    }
+
+    #[test]
+    fn shake_unused_directives() {
+        let used = [Directive::Warning].into_iter().collect();
+        let css = shake(STYLESHEET, &used);
+        for kept in ["admonish-note", "admonish-bug", "admonish-warning"] {
+            assert!(css.contains(&format!("--md-admonition-icon--{kept}:")));
+            assert!(css.contains(&format!("--{kept}-color:")));
+            assert!(css.contains(&format!(":is(.admonition):is(.{kept}")));
+        }
+        for removed in ["admonish-tip", "admonish-quote"] {
+            assert!(!css.contains(&format!("--md-admonition-icon--{removed}:")));
+            assert!(!css.contains(&format!("--{removed}-color:")));
+            assert!(!css.contains(&format!(".{removed}")));
+        }
+        // Everything else is left as it was
+        assert!(css.starts_with("@charset \"UTF-8\";\n:root {\n"));
+        assert!(css.contains(":is(.admonition).admonish-custom {"));
+        assert_eq!(css.matches('{').count(), css.matches('}').count(),);
+
+        let all = Directive::BUILTIN.iter().cloned().collect();
+        assert_eq!(shake(STYLESHEET, &all), STYLESHEET);
+    }
+}
@@ -0,0 +1,181 @@
+//! Rendering admonitions in a stream of markdown events.
+
+use pulldown_cmark::{html::push_html, CodeBlockKind, Event, Parser, Tag};
+use std::collections::VecDeque;
+
+use crate::{
+    config::admonition_config_string,
+    markdown::{enclosing_fence, parser_options, Assigned},
+    process::{process_assigned, Config},
+};
+
+/// Renders admonitions in a stream of [`pulldown_cmark`] events, for tools
+/// that parse markdown themselves.
+///
+/// Each `admonish` code block is replaced with a single [`Event::Html`],
+/// containing the block rendered to HTML. Other events are passed through
+/// unchanged.
+///
+/// ```
+/// use pulldown_cmark::{html::push_html, Parser};
+///
+/// let config = mdbook_admonish::Config::builder().build()?;
+/// let events = Parser::new("```admonish tip\nContent.\n```\n");
+/// let mut html = String::new();
+/// push_html(&mut html, mdbook_admonish::AdmonishEvents::new(events, &config));
+/// assert!(html.contains(r#"class="admonition admonish-tip""#));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// Blocks are rendered apart from the rest of the document, so they can't use
+/// link reference definitions or footnotes defined outside them. If a block
+/// can't be rendered, such as when it fails to parse with `on_failure =
+/// "bail"`, the error is logged and the block is passed through unchanged.
+#[derive(Debug)]
+pub struct AdmonishEvents<'c, 'a, I> {
+    events: I,
+    config: &'c Config,
+    assigned: Assigned,
+    /// Events of a block that couldn't be rendered, still to pass through.
+    pending: VecDeque<Event<'a>>,
+}
+
+impl<'c, 'a, I> AdmonishEvents<'c, 'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    pub fn new(events: I, config: &'c Config) -> Self {
+        Self {
+            events,
+            config,
+            assigned: Assigned::default(),
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl<'c, 'a, I> Iterator for AdmonishEvents<'c, 'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        let event = self.events.next()?;
+        let info_string = match &event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info_string)))
+                if admonition_config_string(info_string).is_some() =>
+            {
+                info_string.to_string()
+            }
+            _ => return Some(event),
+        };
+
+        let mut block = vec![event];
+        let mut body = String::new();
+        for event in self.events.by_ref() {
+            let end = matches!(event, Event::End(Tag::CodeBlock(_)));
+            if let Event::Text(text) = &event {
+                body.push_str(text);
+            }
+            block.push(event);
+            if end {
+                break;
+            }
+        }
+        if !body.is_empty() && !body.ends_with('\n') {
+            body.push('\n');
+        }
+
+        // Write the block back out as markdown, to render as a chapter would
+        let fence = enclosing_fence(&body);
+        let markdown = format!("{fence}{info_string}\n{body}{fence}\n");
+        match process_assigned(&markdown, self.config, &mut self.assigned) {
+            Ok(rendered) => {
+                let mut html = String::new();
+                push_html(&mut html, Parser::new_ext(&rendered, parser_options()));
+                Some(Event::Html(html.into()))
+            }
+            Err(error) => {
+                log::error!("{error:#}");
+                self.pending.extend(block);
+                self.pending.pop_front()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn render(content: &str, config: &Config) -> String {
+        let mut html = String::new();
+        push_html(
+            &mut html,
+            AdmonishEvents::new(Parser::new_ext(content, parser_options()), config),
+        );
+        html
+    }
+
+    #[test]
+    fn renders_blocks_in_stream() {
+        let content = r#"# Title
+
+```admonish tip
+Some *markdown*.
+```
+
+```rust
+let x = 1;
+```
+
+```admonish tip
+Again.
+```
+"#;
+        let expected = r##"<h1>Title</h1>
+<div id="admonition-tip" class="admonition admonish-tip">
+<div class="admonition-title">
+<p>Tip</p>
+<p><a class="admonition-anchor-link" href="#admonition-tip"></a></p>
+</div>
+<div>
+<p>Some <em>markdown</em>.</p>
+</div>
+</div>
+<pre><code class="language-rust">let x = 1;
+</code></pre>
+<div id="admonition-tip-1" class="admonition admonish-tip">
+<div class="admonition-title">
+<p>Tip</p>
+<p><a class="admonition-anchor-link" href="#admonition-tip-1"></a></p>
+</div>
+<div>
+<p>Again.</p>
+</div>
+</div>
+"##;
+
+        assert_eq!(
+            expected,
+            render(content, &Config::builder().build().unwrap())
+        );
+    }
+
+    #[test]
+    fn passes_through_failed_blocks() {
+        let config = Config::builder()
+            .toml(r#"on_failure = "bail""#)
+            .build()
+            .unwrap();
+        assert_eq!(
+            "<pre><code class=\"language-admonish\">Content.\n</code></pre>\n",
+            render("```admonish title=\"Unclosed\nContent.\n```\n", &config)
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::{anyhow, Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::{
+    markdown::dedent_lines,
+    template::{indent_lines, TemplateData},
+};
+
+/// A user supplied command, which renders each admonition in place of the
+/// builtin HTML structure.
+///
+/// The command is given the admonition as JSON on stdin, and its stdout
+/// replaces the block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ExternalRenderer {
+    program: String,
+    args: Vec<String>,
+    /// The book root, which the command is run from.
+    root: PathBuf,
+}
+
+impl ExternalRenderer {
+    /// Parse a command line, split into arguments as a shell would.
+    pub(crate) fn new(root: &Path, command: &str) -> Result<Self> {
+        let mut args = shlex::split(command)
+            .filter(|args| !args.is_empty())
+            .ok_or_else(|| anyhow!("Invalid external renderer command '{command}'"))?;
+        let program = args.remove(0);
+        Ok(Self {
+            program,
+            args,
+            root: root.to_owned(),
+        })
+    }
+
+    /// Render a single admonition.
+    ///
+    /// Content is given to the command without any indent, and the output is
+    /// indented to match the original block.
+    pub(crate) fn render(&self, data: &TemplateData, indent: usize) -> Result<String> {
+        let content = dedent_lines(data.content, indent);
+        let input = serde_json::to_vec(&TemplateData {
+            content: &content,
+            ..*data
+        })?;
+
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .current_dir(&self.root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Can't run external renderer '{}'", self.program))?;
+        // Written from another thread, as the command may fill its output
+        // before reading all of its input
+        let mut stdin = child.stdin.take().expect("stdin to be piped");
+        let writer = thread::spawn(move || stdin.write_all(&input));
+        let output = child
+            .wait_with_output()
+            .context("Can't read from external renderer")?;
+        let written = writer.join().expect("writer thread not to panic");
+        // A command that fails may not read its input, so report the failure
+        if !output.status.success() {
+            return Err(anyhow!(
+                "External renderer '{}' failed with {}:\n{}",
+                self.program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            ));
+        }
+        written.context("Can't write to external renderer")?;
+        let output =
+            String::from_utf8(output.stdout).context("External renderer output is not UTF-8")?;
+        Ok(indent_lines(&output, indent))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn data(content: &str) -> TemplateData<'_> {
+        TemplateData {
+            id: "admonition-note",
+            anchor: "#admonition-note",
+            directive: "note",
+            classes: "admonition admonish-note",
+            body_classes: "",
+            style: "",
+            title: "Note",
+            url: None,
+            content,
+            collapsible: false,
+        }
+    }
+
+    #[test]
+    fn render_with_command() {
+        // Echo the input back, to check what the command receives
+        let renderer = ExternalRenderer::new(Path::new("."), "cat").unwrap();
+        assert_eq!(
+            renderer.render(&data("Line one\n  Line two"), 2).unwrap(),
+            r##"
+  {"id":"admonition-note","anchor":"#admonition-note","directive":"note","classes":"admonition admonish-note","body_classes":"","style":"","title":"Note","url":null,"content":"Line one\nLine two","collapsible":false}"##
+        );
+    }
+
+    #[test]
+    fn render_large_block() {
+        // Larger than a pipe buffer, so the command writes output before it
+        // has read all of its input
+        let content = "Line\n".repeat(100_000);
+        let renderer = ExternalRenderer::new(Path::new("."), "cat").unwrap();
+        let output = renderer.render(&data(&content), 0).unwrap();
+        assert!(output.len() > content.len());
+    }
+
+    #[test]
+    fn render_command_fails() {
+        let renderer =
+            ExternalRenderer::new(Path::new("."), "sh -c 'echo oops >&2; exit 3'").unwrap();
+        let error = renderer.render(&data("Content"), 0).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "External renderer 'sh' failed with exit status: 3:\noops"
+        );
+    }
+
+    #[test]
+    fn invalid_command() {
+        assert!(ExternalRenderer::new(Path::new("."), "").is_err());
+        assert!(ExternalRenderer::new(Path::new("."), "unclosed 'quote").is_err());
+    }
+}
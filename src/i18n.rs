@@ -0,0 +1,193 @@
+use crate::types::Directive;
+
+/// Translated default titles, in the order of `Directive::BUILTIN`.
+const TITLES: &[(&str, [&str; 12])] = &[
+    (
+        "de",
+        [
+            "Hinweis",
+            "Zusammenfassung",
+            "Info",
+            "Tipp",
+            "Erfolg",
+            "Frage",
+            "Warnung",
+            "Fehlschlag",
+            "Gefahr",
+            "Fehler",
+            "Beispiel",
+            "Zitat",
+        ],
+    ),
+    (
+        "es",
+        [
+            "Nota",
+            "Resumen",
+            "Información",
+            "Consejo",
+            "Éxito",
+            "Pregunta",
+            "Advertencia",
+            "Fallo",
+            "Peligro",
+            "Error",
+            "Ejemplo",
+            "Cita",
+        ],
+    ),
+    (
+        "fr",
+        [
+            "Remarque",
+            "Résumé",
+            "Info",
+            "Astuce",
+            "Succès",
+            "Question",
+            "Avertissement",
+            "Échec",
+            "Danger",
+            "Bogue",
+            "Exemple",
+            "Citation",
+        ],
+    ),
+    (
+        "it",
+        [
+            "Nota",
+            "Sommario",
+            "Informazioni",
+            "Suggerimento",
+            "Successo",
+            "Domanda",
+            "Avvertenza",
+            "Errore",
+            "Pericolo",
+            "Bug",
+            "Esempio",
+            "Citazione",
+        ],
+    ),
+    (
+        "ja",
+        [
+            "注記",
+            "概要",
+            "情報",
+            "ヒント",
+            "成功",
+            "質問",
+            "警告",
+            "失敗",
+            "危険",
+            "バグ",
+            "例",
+            "引用",
+        ],
+    ),
+    (
+        "ko",
+        [
+            "참고", "요약", "정보", "팁", "성공", "질문", "경고", "실패", "위험", "버그", "예시",
+            "인용",
+        ],
+    ),
+    (
+        "nl",
+        [
+            "Opmerking",
+            "Samenvatting",
+            "Info",
+            "Tip",
+            "Gelukt",
+            "Vraag",
+            "Waarschuwing",
+            "Mislukt",
+            "Gevaar",
+            "Bug",
+            "Voorbeeld",
+            "Citaat",
+        ],
+    ),
+    (
+        "pt",
+        [
+            "Nota",
+            "Resumo",
+            "Informação",
+            "Dica",
+            "Sucesso",
+            "Pergunta",
+            "Aviso",
+            "Falha",
+            "Perigo",
+            "Bug",
+            "Exemplo",
+            "Citação",
+        ],
+    ),
+    (
+        "ru",
+        [
+            "Примечание",
+            "Аннотация",
+            "Информация",
+            "Совет",
+            "Успех",
+            "Вопрос",
+            "Предупреждение",
+            "Неудача",
+            "Опасность",
+            "Ошибка",
+            "Пример",
+            "Цитата",
+        ],
+    ),
+    (
+        "zh",
+        [
+            "注意", "摘要", "信息", "提示", "成功", "问题", "警告", "失败", "危险", "缺陷", "示例",
+            "引用",
+        ],
+    ),
+];
+
+/// The builtin translation of the default title of `directive`, for books in
+/// `language`.
+///
+/// Regional variants fall back to the main language, so `de-AT` uses `de`.
+pub(crate) fn directive_title(language: &str, directive: &Directive) -> Option<&'static str> {
+    let index = Directive::BUILTIN
+        .iter()
+        .position(|builtin| builtin == directive)?;
+    language_candidates(language).find_map(|language| {
+        TITLES
+            .iter()
+            .find(|(code, _)| code.eq_ignore_ascii_case(language))
+            .map(|(_, titles)| titles[index])
+    })
+}
+
+/// `language`, followed by its primary subtag if it has a region.
+pub(crate) fn language_candidates(language: &str) -> impl Iterator<Item = &str> {
+    let primary = language.split_once(['-', '_']).map(|(primary, _)| primary);
+    std::iter::once(language).chain(primary)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn directive_titles() {
+        assert_eq!(directive_title("de", &Directive::Note), Some("Hinweis"));
+        assert_eq!(directive_title("ja", &Directive::Warning), Some("警告"));
+        assert_eq!(directive_title("de-AT", &Directive::Tip), Some("Tipp"));
+        assert_eq!(directive_title("pt_BR", &Directive::Tip), Some("Dica"));
+        assert_eq!(directive_title("en", &Directive::Note), None);
+        assert_eq!(directive_title("de", &Directive::custom("security")), None);
+    }
+}
@@ -0,0 +1,688 @@
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "mdbook")]
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "mdbook")]
+use crate::book_config::{AppendixGroup, AppendixSort};
+use crate::types::Directive;
+
+static RX_REFERENCE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(\\)?\{\{#admonish-ref\s+([^\s}]+)(?:\s+(title|number))?\s*\}\}")
+        .expect("reference regex")
+});
+
+/// Marks where the backlinks of block `id` go, until every reference in the
+/// book is known.
+pub(crate) fn backlinks_placeholder(id: &str) -> String {
+    format!("<!-- admonish-backlinks {id} -->")
+}
+
+/// Every admonition rendered in the book, for references between chapters.
+#[derive(Debug, Default)]
+pub(crate) struct AdmonitionIndex {
+    pub(crate) entries: Vec<IndexEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct IndexEntry {
+    /// Path of the chapter source, relative to the book source directory.
+    pub(crate) chapter_path: Option<PathBuf>,
+    pub(crate) chapter_name: Option<String>,
+    /// Line number of the start of the block in the chapter source.
+    pub(crate) line: usize,
+    pub(crate) id: String,
+    pub(crate) directive: Directive,
+    /// The default title of the directive, such as "Warning".
+    pub(crate) directive_title: String,
+    /// The title as displayed, including any number.
+    pub(crate) title: String,
+    /// The directive title and number, such as "Warning 3", if numbered.
+    pub(crate) number: Option<String>,
+    /// Whether the block has an anchor that can be linked to.
+    pub(crate) linked: bool,
+    pub(crate) tags: Vec<String>,
+    pub(crate) collapsible: bool,
+    /// Length of the block content, in characters.
+    pub(crate) length: usize,
+}
+
+impl AdmonitionIndex {
+    pub(crate) fn push(&mut self, entry: IndexEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Replace any `{{#admonish-ref id}}` references in `content`, the chapter
+    /// at `chapter_path`, with links to the admonitions they name.
+    ///
+    /// References may name a block in another chapter, either by a unique id
+    /// or by `path/to/chapter.md#id`. The link text is the block title, or
+    /// the number when written as `{{#admonish-ref id number}}`.
+    ///
+    /// References are escaped with a backslash, as `\{{#admonish-ref id}}`.
+    /// mdbook removes a backslash before `{{#` itself, so books need two.
+    pub(crate) fn expand_references(
+        &self,
+        content: &str,
+        chapter_path: Option<&Path>,
+    ) -> Result<String> {
+        let mut error = None;
+        let expanded = RX_REFERENCE.replace_all(content, |captures: &Captures| {
+            let reference = &captures[0];
+            if captures.get(1).is_some() {
+                return reference[1..].to_owned();
+            }
+            let target = &captures[2];
+            match self.resolve(target, chapter_path) {
+                Ok(entry) => {
+                    let text = match (captures.get(3).map(|text| text.as_str()), &entry.number) {
+                        (Some("number"), Some(number)) => number,
+                        _ => &entry.title,
+                    };
+                    let text = escape_link_text(text);
+                    if !entry.linked {
+                        return text;
+                    }
+                    let href = link(chapter_path, entry.chapter_path.as_deref(), &entry.id);
+                    format!("[{text}]({href})")
+                }
+                Err(err) => {
+                    error.get_or_insert(err);
+                    reference.to_owned()
+                }
+            }
+        });
+        match error {
+            Some(error) => Err(error),
+            None => Ok(expanded.into_owned()),
+        }
+    }
+
+    /// Record the admonitions referenced by `content`, the chapter named
+    /// `chapter_name` at `chapter_path`, in `backlinks`.
+    ///
+    /// References within a chapter, and references that don't resolve, are
+    /// not recorded.
+    #[cfg(feature = "mdbook")]
+    pub(crate) fn collect_backlinks(
+        &self,
+        content: &str,
+        chapter_path: &Path,
+        chapter_name: &str,
+        backlinks: &mut Backlinks,
+    ) {
+        for captures in RX_REFERENCE.captures_iter(content) {
+            if captures.get(1).is_some() {
+                continue;
+            }
+            let entry = match self.resolve(&captures[2], Some(chapter_path)) {
+                Ok(entry) if entry.chapter_path.as_deref() != Some(chapter_path) => entry,
+                _ => continue,
+            };
+            let referrers = backlinks
+                .referrers
+                .entry((entry.chapter_path.clone(), entry.id.clone()))
+                .or_default();
+            if !referrers.iter().any(|(path, _)| path == chapter_path) {
+                referrers.push((chapter_path.to_owned(), chapter_name.to_owned()));
+            }
+        }
+    }
+
+    /// The content of a chapter at `path`, listing every admonition of
+    /// `directives` (or all, if `None`), grouped and sorted as configured.
+    #[cfg(feature = "mdbook")]
+    pub(crate) fn appendix(
+        &self,
+        title: &str,
+        path: &Path,
+        directives: Option<&HashSet<Directive>>,
+        group_by: AppendixGroup,
+        sort: AppendixSort,
+    ) -> String {
+        let mut entries = self
+            .entries
+            .iter()
+            .filter(|entry| {
+                directives.map_or(true, |directives| directives.contains(&entry.directive))
+            })
+            .collect::<Vec<_>>();
+        if sort == AppendixSort::Title {
+            // Stable, so blocks with the same title stay in book order
+            entries.sort_by_cached_key(|entry| listed_title(entry).to_lowercase());
+        }
+
+        let groups = match group_by {
+            AppendixGroup::Directive => {
+                // Builtin directives first, in their usual order, then custom
+                // directives in the order they appear
+                let mut directives: Vec<Directive> = Vec::new();
+                for entry in &self.entries {
+                    if !directives.contains(&entry.directive) {
+                        directives.push(entry.directive.clone());
+                    }
+                }
+                directives.sort_by_key(|directive| {
+                    Directive::BUILTIN
+                        .iter()
+                        .position(|builtin| builtin == directive)
+                        .unwrap_or(usize::MAX)
+                });
+                directives
+                    .into_iter()
+                    .filter_map(|directive| {
+                        let entries = entries
+                            .iter()
+                            .copied()
+                            .filter(|entry| entry.directive == directive)
+                            .collect::<Vec<_>>();
+                        Some((entries.first()?.directive_title.clone(), entries))
+                    })
+                    .collect::<Vec<_>>()
+            }
+            AppendixGroup::Chapter => {
+                // Chapters in book order, whatever the order of their blocks
+                let mut chapters: Vec<(Option<&Path>, Option<&str>)> = Vec::new();
+                for entry in &self.entries {
+                    let chapter = (entry.chapter_path.as_deref(), entry.chapter_name.as_deref());
+                    if !chapters.contains(&chapter) {
+                        chapters.push(chapter);
+                    }
+                }
+                chapters
+                    .into_iter()
+                    .filter_map(|(chapter_path, chapter_name)| {
+                        let entries = entries
+                            .iter()
+                            .copied()
+                            .filter(|entry| entry.chapter_path.as_deref() == chapter_path)
+                            .collect::<Vec<_>>();
+                        if entries.is_empty() {
+                            return None;
+                        }
+                        let heading = match (chapter_path, chapter_name) {
+                            (Some(chapter_path), Some(chapter_name)) => format!(
+                                "[{}]({})",
+                                escape_link_text(chapter_name),
+                                relative_path(Some(path), chapter_path)
+                            ),
+                            (Some(chapter_path), None) => chapter_path.display().to_string(),
+                            (None, _) => String::new(),
+                        };
+                        Some((heading, entries))
+                    })
+                    .collect::<Vec<_>>()
+            }
+            AppendixGroup::None => vec![(String::new(), entries)],
+        };
+        listing(title, path, &groups, group_by != AppendixGroup::Chapter)
+    }
+
+    /// The content of a chapter at `path`, listing every tagged admonition,
+    /// grouped by tag.
+    #[cfg(feature = "mdbook")]
+    pub(crate) fn tag_index(&self, title: &str, path: &Path) -> String {
+        let mut tags = self
+            .entries
+            .iter()
+            .flat_map(|entry| entry.tags.iter())
+            .collect::<Vec<_>>();
+        tags.sort_unstable();
+        tags.dedup();
+        let groups = tags
+            .into_iter()
+            .map(|tag| {
+                let entries = self
+                    .entries
+                    .iter()
+                    .filter(|entry| entry.tags.contains(tag))
+                    .collect();
+                (tag.clone(), entries)
+            })
+            .collect::<Vec<_>>();
+        listing(title, path, &groups, true)
+    }
+
+    /// The admonition named by `target`, from the chapter at `chapter_path`.
+    fn resolve(&self, target: &str, chapter_path: Option<&Path>) -> Result<&IndexEntry> {
+        let chapter = chapter_path
+            .map(|path| format!(" in '{}'", path.display()))
+            .unwrap_or_default();
+        if let Some((path, id)) = target.split_once('#') {
+            return self
+                .entries
+                .iter()
+                .find(|entry| {
+                    entry.id == id && entry.chapter_path.as_deref() == Some(path.as_ref())
+                })
+                .ok_or_else(|| anyhow!("Reference '{target}'{chapter} to an unknown admonition"));
+        }
+        // Blocks in the same chapter take precedence
+        let mut matches = self
+            .entries
+            .iter()
+            .filter(|entry| entry.id == target)
+            .collect::<Vec<_>>();
+        if let Some(local) = matches
+            .iter()
+            .find(|entry| entry.chapter_path.as_deref() == chapter_path)
+        {
+            return Ok(local);
+        }
+        match matches.len() {
+            0 => Err(anyhow!(
+                "Reference '{target}'{chapter} to an unknown admonition"
+            )),
+            1 => Ok(matches.remove(0)),
+            _ => Err(anyhow!(
+                "Reference '{target}'{chapter} is ambiguous, use 'path/to/chapter.md#{target}' to choose one of: {}",
+                matches
+                    .iter()
+                    .filter_map(|entry| entry.chapter_path.as_deref())
+                    .map(|path| format!("'{}'", path.display()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+/// The chapters that reference each admonition.
+#[cfg(feature = "mdbook")]
+#[derive(Debug, Default)]
+pub(crate) struct Backlinks {
+    /// Paths and names of the referencing chapters, in book order.
+    referrers: HashMap<BlockKey, Vec<(PathBuf, String)>>,
+}
+
+/// The chapter path and id of an admonition.
+#[cfg(feature = "mdbook")]
+type BlockKey = (Option<PathBuf>, String);
+
+#[cfg(feature = "mdbook")]
+impl Backlinks {
+    /// Replace the backlinks placeholder of each block in `content`, the
+    /// chapter at `chapter_path`, with links to the chapters that reference
+    /// the block, after `title`.
+    pub(crate) fn expand(&self, content: &str, chapter_path: Option<&Path>, title: &str) -> String {
+        static RX_PLACEHOLDER: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r"(?m)^([ >]*)<!-- admonish-backlinks (\S+) -->\n?")
+                .expect("backlinks placeholder regex")
+        });
+
+        RX_PLACEHOLDER
+            .replace_all(content, |captures: &Captures| {
+                let prefix = &captures[1];
+                let key = (chapter_path.map(Path::to_owned), captures[2].to_owned());
+                let referrers = match self.referrers.get(&key) {
+                    Some(referrers) => referrers,
+                    None => return String::new(),
+                };
+                let links = referrers
+                    .iter()
+                    .map(|(path, name)| {
+                        format!(
+                            "[{}]({})",
+                            escape_link_text(name),
+                            relative_path(chapter_path, path)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                // Blank lines, so the links are rendered as markdown
+                [
+                    "",
+                    r#"<div class="admonition-backlinks">"#,
+                    "",
+                    &format!("{title}: {links}"),
+                    "",
+                    "</div>",
+                ]
+                .iter()
+                .map(|line| format!("{prefix}{line}\n"))
+                .collect()
+            })
+            .into_owned()
+    }
+}
+
+/// A chapter at `path`, with a section for each group of admonitions, linking
+/// to each admonition and, if `show_chapter`, its chapter.
+///
+/// Groups without a heading are listed without a section.
+#[cfg(feature = "mdbook")]
+fn listing(
+    title: &str,
+    path: &Path,
+    groups: &[(String, Vec<&IndexEntry>)],
+    show_chapter: bool,
+) -> String {
+    let mut content = format!("# {title}\n");
+    for (heading, entries) in groups {
+        if heading.is_empty() {
+            content.push('\n');
+        } else {
+            content.push_str(&format!("\n## {heading}\n\n"));
+        }
+        for entry in entries {
+            let text = escape_link_text(listed_title(entry));
+            let item = match (&entry.chapter_path, entry.linked) {
+                (Some(chapter_path), true) => {
+                    let href = relative_path(Some(path), chapter_path);
+                    format!("[{text}]({href}#{})", entry.id)
+                }
+                _ => text,
+            };
+            match (&entry.chapter_path, &entry.chapter_name, show_chapter) {
+                (Some(chapter_path), Some(chapter_name), true) => content.push_str(&format!(
+                    "- {item}, in [{}]({})\n",
+                    escape_link_text(chapter_name),
+                    relative_path(Some(path), chapter_path)
+                )),
+                _ => content.push_str(&format!("- {item}\n")),
+            }
+        }
+    }
+    content
+}
+
+/// The title of an admonition as listed, which is the directive title if the
+/// block has none.
+#[cfg(feature = "mdbook")]
+fn listed_title(entry: &IndexEntry) -> &str {
+    if entry.title.is_empty() {
+        &entry.directive_title
+    } else {
+        &entry.title
+    }
+}
+
+/// A link from the chapter at `from` to the block `id` in the chapter at `to`.
+///
+/// Links to other chapters are relative to the source files, as mdbook
+/// rewrites links to `.md` files for each renderer.
+fn link(from: Option<&Path>, to: Option<&Path>, id: &str) -> String {
+    match to {
+        Some(to) if Some(to) != from => format!("{}#{id}", relative_path(from, to)),
+        _ => format!("#{id}"),
+    }
+}
+
+/// The path of the chapter at `to`, relative to the chapter at `from`.
+fn relative_path(from: Option<&Path>, to: &Path) -> String {
+    let depth = from
+        .and_then(Path::parent)
+        .map_or(0, |parent| parent.components().count());
+    let path = to
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    format!("{}{path}", "../".repeat(depth))
+}
+
+/// Escape text so it stays inside the brackets of a markdown link.
+fn escape_link_text(text: &str) -> String {
+    text.replace('[', r"\[").replace(']', r"\]")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn entry(chapter_path: &str, id: &str, title: &str) -> IndexEntry {
+        IndexEntry {
+            chapter_path: Some(PathBuf::from(chapter_path)),
+            chapter_name: None,
+            line: 1,
+            id: id.to_owned(),
+            directive: Directive::Warning,
+            directive_title: "Warning".to_owned(),
+            title: title.to_owned(),
+            number: None,
+            linked: true,
+            tags: Vec::new(),
+            collapsible: false,
+            length: 0,
+        }
+    }
+
+    #[test]
+    fn expand_references() {
+        let mut index = AdmonitionIndex::default();
+        index.push(entry("intro.md", "backups", "Back up first"));
+        index.push(IndexEntry {
+            number: Some("Warning 2".to_owned()),
+            ..entry("guide/setup.md", "disk", "Warning 2: Disk [full]")
+        });
+        index.push(entry("guide/setup.md", "shared", "Setup"));
+        index.push(entry("guide/other.md", "shared", "Other"));
+
+        let chapter = Some(Path::new("guide/setup.md"));
+        assert_eq!(
+            index
+                .expand_references(
+                    r"See {{#admonish-ref backups}}, {{#admonish-ref disk}} and {{#admonish-ref disk number}}.",
+                    chapter
+                )
+                .unwrap(),
+            r"See [Back up first](../intro.md#backups), [Warning 2: Disk \[full\]](#disk) and [Warning 2](#disk)."
+        );
+        // The current chapter takes precedence
+        assert_eq!(
+            index
+                .expand_references("{{#admonish-ref shared}}", chapter)
+                .unwrap(),
+            "[Setup](#shared)"
+        );
+        assert_eq!(
+            index
+                .expand_references(
+                    "{{#admonish-ref guide/other.md#shared}}",
+                    Some(Path::new("intro.md"))
+                )
+                .unwrap(),
+            "[Other](guide/other.md#shared)"
+        );
+        assert_eq!(
+            index
+                .expand_references(r"\{{#admonish-ref missing}}", chapter)
+                .unwrap(),
+            "{{#admonish-ref missing}}"
+        );
+
+        // Dangling and ambiguous references are errors
+        assert!(index
+            .expand_references("{{#admonish-ref missing}}", chapter)
+            .is_err());
+        assert!(index
+            .expand_references("{{#admonish-ref shared}}", Some(Path::new("intro.md")))
+            .is_err());
+    }
+
+    #[cfg(feature = "mdbook")]
+    #[test]
+    fn backlinks() {
+        let mut index = AdmonitionIndex::default();
+        index.push(entry("guide/setup.md", "backups", "Back up first"));
+        index.push(entry("guide/setup.md", "unreferenced", "Unreferenced"));
+
+        let mut backlinks = Backlinks::default();
+        for (path, name) in [("intro.md", "Intro [1]"), ("guide/setup.md", "Setup")] {
+            index.collect_backlinks(
+                r"{{#admonish-ref backups}} {{#admonish-ref guide/setup.md#backups}} \{{#admonish-ref unreferenced}}",
+                Path::new(path),
+                name,
+                &mut backlinks,
+            );
+        }
+        index.collect_backlinks(
+            "{{#admonish-ref missing}}",
+            Path::new("other.md"),
+            "Other",
+            &mut backlinks,
+        );
+
+        let content = format!(
+            "{}\n> {}\n",
+            backlinks_placeholder("backups"),
+            backlinks_placeholder("unreferenced")
+        );
+        assert_eq!(
+            backlinks.expand(&content, Some(Path::new("guide/setup.md")), "Used in"),
+            r#"
+<div class="admonition-backlinks">
+
+Used in: [Intro \[1\]](../intro.md)
+
+</div>
+"#
+        );
+    }
+
+    #[cfg(feature = "mdbook")]
+    #[test]
+    fn appendix() {
+        let mut index = AdmonitionIndex::default();
+        index.push(IndexEntry {
+            chapter_name: Some("Setup".to_owned()),
+            ..entry("guide/setup.md", "disk", "Disk full")
+        });
+        index.push(IndexEntry {
+            directive: Directive::Note,
+            directive_title: "Note".to_owned(),
+            chapter_name: Some("Intro".to_owned()),
+            ..entry("intro.md", "admonition-note", "")
+        });
+        index.push(IndexEntry {
+            linked: false,
+            ..entry("intro.md", "stripped", "Stripped")
+        });
+
+        assert_eq!(
+            index.appendix(
+                "All admonitions",
+                Path::new("appendix/admonitions.md"),
+                None,
+                AppendixGroup::Directive,
+                AppendixSort::Book
+            ),
+            r#"# All admonitions
+
+## Note
+
+- [Note](../intro.md#admonition-note), in [Intro](../intro.md)
+
+## Warning
+
+- [Disk full](../guide/setup.md#disk), in [Setup](../guide/setup.md)
+- Stripped
+"#
+        );
+        assert_eq!(
+            index.appendix(
+                "Notes",
+                Path::new("notes.md"),
+                Some(&[Directive::Note].into_iter().collect()),
+                AppendixGroup::Directive,
+                AppendixSort::Book
+            ),
+            r#"# Notes
+
+## Note
+
+- [Note](intro.md#admonition-note), in [Intro](intro.md)
+"#
+        );
+    }
+
+    #[cfg(feature = "mdbook")]
+    #[test]
+    fn appendix_layout() {
+        let mut index = AdmonitionIndex::default();
+        index.push(IndexEntry {
+            chapter_name: Some("Setup".to_owned()),
+            ..entry("setup.md", "disk", "Disk full")
+        });
+        index.push(IndexEntry {
+            directive: Directive::Note,
+            directive_title: "Note".to_owned(),
+            chapter_name: Some("Intro".to_owned()),
+            ..entry("intro.md", "admonition-note", "")
+        });
+        index.push(IndexEntry {
+            chapter_name: Some("Setup".to_owned()),
+            ..entry("setup.md", "backups", "Back up first")
+        });
+
+        assert_eq!(
+            index.appendix(
+                "By chapter",
+                Path::new("appendix.md"),
+                None,
+                AppendixGroup::Chapter,
+                AppendixSort::Title
+            ),
+            r#"# By chapter
+
+## [Setup](setup.md)
+
+- [Back up first](setup.md#backups)
+- [Disk full](setup.md#disk)
+
+## [Intro](intro.md)
+
+- [Note](intro.md#admonition-note)
+"#
+        );
+        assert_eq!(
+            index.appendix(
+                "Alphabetical",
+                Path::new("appendix.md"),
+                None,
+                AppendixGroup::None,
+                AppendixSort::Title
+            ),
+            r#"# Alphabetical
+
+- [Back up first](setup.md#backups), in [Setup](setup.md)
+- [Disk full](setup.md#disk), in [Setup](setup.md)
+- [Note](intro.md#admonition-note), in [Intro](intro.md)
+"#
+        );
+    }
+
+    #[cfg(feature = "mdbook")]
+    #[test]
+    fn tag_index() {
+        let mut index = AdmonitionIndex::default();
+        index.push(IndexEntry {
+            tags: vec!["windows".to_owned(), "gotcha".to_owned()],
+            ..entry("setup.md", "paths", "Paths")
+        });
+        index.push(IndexEntry {
+            tags: vec!["gotcha".to_owned()],
+            ..entry("setup.md", "disk", "Disk")
+        });
+        index.push(entry("setup.md", "untagged", "Untagged"));
+
+        assert_eq!(
+            index.tag_index("Tags", Path::new("tags.md")),
+            r#"# Tags
+
+## gotcha
+
+- [Paths](setup.md#paths)
+- [Disk](setup.md#disk)
+
+## windows
+
+- [Paths](setup.md#paths)
+"#
+        );
+    }
+}
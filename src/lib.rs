@@ -1,10 +1,40 @@
 mod book_config;
+#[cfg(feature = "mdbook")]
+mod cache;
 mod config;
+#[cfg(feature = "mdbook")]
+mod css;
+mod events;
+mod external;
+mod i18n;
+mod index;
 mod markdown;
 mod parse;
+#[cfg(feature = "mdbook")]
 mod preprocessor;
+mod process;
 mod render;
 mod resolve;
+#[cfg(feature = "mdbook")]
+mod scan;
+mod slug;
+mod syntax;
+mod template;
 mod types;
+mod warnings;
+#[cfg(feature = "wasm")]
+mod wasm;
 
-pub use crate::preprocessor::Admonish;
+pub use crate::{
+    events::AdmonishEvents,
+    process::{process, Config, ConfigBuilder},
+    slug::hash_hex,
+    warnings::Diagnostic,
+};
+
+#[cfg(feature = "mdbook")]
+pub use crate::{
+    css::{generate_css, CssOptions},
+    preprocessor::Admonish,
+    scan::{check_book, list_admonitions, render_chapter, ListedAdmonition},
+};
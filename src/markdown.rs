@@ -1,68 +1,741 @@
-use mdbook::errors::Result as MdbookResult;
-use pulldown_cmark::{CodeBlockKind::*, Event, Options, Parser, Tag};
+use anyhow::Result;
+use pulldown_cmark::{html::push_html, CodeBlockKind::*, Event, Options, Parser, Tag};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::Path;
 
-pub use crate::preprocessor::Admonish;
 use crate::{
-    book_config::OnFailure,
-    parse::parse_admonition,
-    types::{AdmonitionDefaults, RenderTextMode},
+    book_config::{IdScheme, OnFailure, Syntax},
+    config::InstanceConfig,
+    index::{backlinks_placeholder, AdmonitionIndex, IndexEntry},
+    parse::{admonition_from_config, failed_admonition, parse_admonition, strict_error},
+    render::{Admonition, HtmlOptions},
+    slug::unique_id_from_content,
+    syntax::{github_alert, mkdocs_admonition, obsidian_callout, pandoc_div, SyntaxBlock},
+    types::{AdmonitionDefaults, Directive, RenderModes, RenderTextMode},
+    warnings::{ChapterWarnings, Location, WarningCode, Warnings},
 };
 
+/// The chapter that content is from.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ChapterInfo<'a> {
+    pub(crate) name: &'a str,
+    /// Path of the chapter source, relative to the book source directory.
+    pub(crate) path: Option<&'a Path>,
+    /// Section number, such as `[3, 1]` for "3.1."
+    pub(crate) number: Option<&'a [u32]>,
+}
+
+#[cfg(feature = "mdbook")]
+impl<'a> From<&'a mdbook::book::Chapter> for ChapterInfo<'a> {
+    fn from(chapter: &'a mdbook::book::Chapter) -> Self {
+        Self {
+            name: &chapter.name,
+            path: chapter.path.as_deref(),
+            number: chapter.number.as_ref().map(|number| number.0.as_slice()),
+        }
+    }
+}
+
+#[cfg(any(feature = "mdbook", test))]
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn preprocess(
     content: &str,
     on_failure: OnFailure,
     admonition_defaults: &AdmonitionDefaults,
-    render_text_mode: RenderTextMode,
-) -> MdbookResult<String> {
-    let mut id_counter = Default::default();
+    render_modes: &RenderModes,
+    html_options: &HtmlOptions,
+    chapter: Option<ChapterInfo>,
+    warnings: &mut Warnings,
+    index: &mut AdmonitionIndex,
+) -> Result<String> {
+    preprocess_assigned(
+        content,
+        on_failure,
+        admonition_defaults,
+        render_modes,
+        html_options,
+        chapter,
+        warnings,
+        index,
+        &mut Assigned::default(),
+    )
+}
+
+/// Anchor ids and numbers given to blocks so far, so that blocks of a chapter
+/// preprocessed separately don't repeat them.
+#[derive(Debug, Default)]
+pub(crate) struct Assigned {
+    id_counter: HashMap<String, usize>,
+    /// Explicit ids of blocks, which generated ids must not take.
+    reserved_ids: HashSet<String>,
+    /// Every id given to a block so far.
+    ids: HashSet<String>,
+    /// Each directive is numbered independently
+    numbers: HashMap<Directive, usize>,
+}
+
+impl Assigned {
+    /// The anchor id of `admonition`.
+    ///
+    /// Generated ids are unique in the chapter, and never take an explicit id
+    /// of another block. Explicit ids are kept as given, with a warning if
+    /// already used.
+    fn anchor_id(
+        &mut self,
+        admonition: &Admonition,
+        html_options: &HtmlOptions,
+        warnings: &mut ChapterWarnings,
+    ) -> String {
+        let id = match &admonition.id {
+            Some(id) => {
+                if self.ids.contains(id) {
+                    warnings.warn(
+                        WarningCode::DuplicateId,
+                        format!("Anchor id '{id}' is already used in this chapter"),
+                    );
+                }
+                id.clone()
+            }
+            None => loop {
+                let id = admonition.anchor_id(&mut self.id_counter, html_options);
+                if !self.ids.contains(&id) && !self.reserved_ids.contains(&id) {
+                    break id;
+                }
+            },
+        };
+        self.ids.insert(id.clone());
+        id
+    }
+}
+
+/// As [`preprocess`], following on from the ids and numbers in `assigned`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn preprocess_assigned(
+    content: &str,
+    on_failure: OnFailure,
+    admonition_defaults: &AdmonitionDefaults,
+    render_modes: &RenderModes,
+    html_options: &HtmlOptions,
+    chapter: Option<ChapterInfo>,
+    warnings: &mut Warnings,
+    index: &mut AdmonitionIndex,
+    assigned: &mut Assigned,
+) -> Result<String> {
+    // Most chapters of a large book have no blocks, so avoid parsing them
+    if !may_contain_blocks(content, html_options) {
+        return Ok(content.to_owned());
+    }
+
+    let chapter_path = chapter.and_then(|chapter| chapter.path);
+    let number_prefix = chapter
+        .and_then(|chapter| chapter.number)
+        .filter(|_| html_options.chapter_prefix)
+        .map(|number| {
+            number
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>()
+                .join(".")
+        });
+    let opts = parser_options();
+
+    let mut state = ChapterState {
+        on_failure,
+        admonition_defaults,
+        render_modes,
+        html_options,
+        chapter,
+        chapter_path,
+        warnings: warnings.chapter(chapter_path, content),
+        anchor_base: html_options.anchor_base(chapter_path),
+        assigned,
+        number_prefix,
+        opts,
+        index,
+    };
+
+    if render_modes.any_html() && html_options.id_scheme == IdScheme::Heading {
+        // Heading anchors are assigned by mdbook independently of us, so
+        // reserve them up front to avoid any collisions.
+        reserve_heading_ids(content, opts, &mut state.assigned.id_counter);
+    }
+    // Blocks with an explicit id may come after blocks given a generated id
+    reserve_explicit_ids(content, opts, &mut state.assigned.reserved_ids);
+
+    let content = preprocess_blocks(content, 0, &mut state)?;
+    state.warnings.finish();
+
+    Ok(content)
+}
+
+/// Markdown extensions enabled by mdbook, so content is parsed as it will be
+/// rendered.
+pub(crate) fn parser_options() -> Options {
     let mut opts = Options::empty();
     opts.insert(Options::ENABLE_TABLES);
     opts.insert(Options::ENABLE_FOOTNOTES);
     opts.insert(Options::ENABLE_STRIKETHROUGH);
     opts.insert(Options::ENABLE_TASKLISTS);
+    opts
+}
+
+/// Whether `content` may contain blocks, in any enabled syntax.
+///
+/// This is a cheap scan for text that every block contains, so may have false
+/// positives, but never false negatives.
+fn may_contain_blocks(content: &str, html_options: &HtmlOptions) -> bool {
+    content.contains("admonish")
+        || html_options
+            .syntax
+            .iter()
+            .flat_map(|syntax| syntax.markers())
+            .any(|marker| content.contains(marker))
+}
+
+/// State shared by the blocks of a chapter, including blocks nested in the
+/// content of others.
+struct ChapterState<'a, 'w> {
+    on_failure: OnFailure,
+    admonition_defaults: &'a AdmonitionDefaults,
+    render_modes: &'a RenderModes,
+    html_options: &'a HtmlOptions,
+    chapter: Option<ChapterInfo<'a>>,
+    chapter_path: Option<&'a Path>,
+    warnings: ChapterWarnings<'w>,
+    anchor_base: String,
+    assigned: &'a mut Assigned,
+    number_prefix: Option<String>,
+    opts: Options,
+    index: &'a mut AdmonitionIndex,
+}
 
+/// Render the admonitions in `content`, and any nested in their content.
+///
+/// `line_offset` is the line of the chapter before `content` starts, so that
+/// warnings and the index refer to lines of the chapter.
+fn preprocess_blocks(
+    content: &str,
+    line_offset: usize,
+    state: &mut ChapterState,
+) -> Result<String> {
+    let ChapterState {
+        on_failure,
+        admonition_defaults,
+        render_modes,
+        html_options,
+        chapter,
+        chapter_path,
+        opts,
+        ..
+    } = *state;
     let mut admonish_blocks = vec![];
 
     let events = Parser::new_ext(content, opts);
 
+    // Code blocks inside raw HTML are not reported as code blocks, so scan
+    // each region of raw HTML for them separately.
+    let mut fences = Vec::new();
+    let mut html_region: Option<Range<usize>> = None;
+    let mut syntax_blocks = Vec::new();
+    let mut blockquote_depth = 0;
     for (event, span) in events.into_offset_iter() {
-        if let Event::Start(Tag::CodeBlock(Fenced(info_string))) = event.clone() {
-            let span_content = &content[span.start..span.end];
-            const INDENT_SCAN_MAX: usize = 1024;
-            let indent = indent_of(content, span.start, INDENT_SCAN_MAX);
-
-            let admonition = match parse_admonition(
-                info_string.as_ref(),
-                admonition_defaults,
-                span_content,
-                on_failure,
-                indent,
+        if let Event::Html(_) = event {
+            html_region = Some(match html_region.take() {
+                Some(region)
+                    if content
+                        .get(region.end..span.start)
+                        .map_or(false, |between| between.trim().is_empty()) =>
+                {
+                    region.start..span.end
+                }
+                Some(region) => {
+                    fences.extend(fences_in_html(content, region));
+                    span
+                }
+                None => span,
+            });
+            continue;
+        }
+        if let Some(region) = html_region.take() {
+            fences.extend(fences_in_html(content, region));
+        }
+        match event {
+            Event::Start(Tag::CodeBlock(Fenced(info_string))) => {
+                let (quote, indent) = quote_of(content, span.start);
+                fences.push(FencedBlock {
+                    span,
+                    info_string: info_string.into_string(),
+                    indent,
+                    dedent: 0,
+                    quote,
+                });
+            }
+            Event::Start(Tag::BlockQuote) => {
+                // Alerts can't be nested in other blockquotes
+                if blockquote_depth == 0 {
+                    let indent = indent_of(content, span.start, INDENT_SCAN_MAX);
+                    // Obsidian callouts are a superset of GitHub alerts
+                    if html_options.syntax.contains(&Syntax::Obsidian) {
+                        syntax_blocks.extend(obsidian_callout(content, span, indent));
+                    } else if html_options.syntax.contains(&Syntax::Github) {
+                        syntax_blocks.extend(github_alert(content, span, indent));
+                    }
+                }
+                blockquote_depth += 1;
+            }
+            Event::End(Tag::BlockQuote) => blockquote_depth -= 1,
+            Event::Start(Tag::Paragraph) => {
+                let indent = indent_of(content, span.start, INDENT_SCAN_MAX);
+                if html_options.syntax.contains(&Syntax::Mkdocs) {
+                    syntax_blocks.extend(mkdocs_admonition(content, span.start, indent));
+                }
+                if html_options.syntax.contains(&Syntax::Pandoc) {
+                    syntax_blocks.extend(pandoc_div(content, span.start, indent));
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(region) = html_region {
+        fences.extend(fences_in_html(content, region));
+    }
+
+    let mut blocks = fences
+        .into_iter()
+        .map(Block::Fenced)
+        .chain(
+            syntax_blocks
+                .into_iter()
+                .map(|block| Block::Syntax(Box::new(block))),
+        )
+        .collect::<Vec<_>>();
+    // Number blocks in document order
+    blocks.sort_by_key(|block| block.span().start);
+    // Blocks written in other syntaxes may contain further blocks, which are
+    // rendered along with their content.
+    let mut enclosing_end = 0;
+    blocks.retain(|block| {
+        if block.span().start < enclosing_end {
+            return false;
+        }
+        if let Block::Syntax(block) = block {
+            enclosing_end = block.span.end;
+        }
+        true
+    });
+
+    for block in blocks {
+        let dedented;
+        let info_string;
+        let body;
+        let mut location = location_of(content, block.span().start);
+        location.line += line_offset;
+        state.warnings.set_location(Some(location));
+        let source_location = html_options.source_location(chapter_path, location.line);
+        let (span, quote, mut admonition) = match block {
+            Block::Fenced(fence) => {
+                info_string = fence.info_string;
+                let span_content = if let Some(quote) = &fence.quote {
+                    dedented = unquote_lines(&content[fence.span.clone()], quote);
+                    dedented.as_str()
+                } else if fence.dedent == 0 {
+                    &content[fence.span.clone()]
+                } else {
+                    dedented = dedent_lines(&content[fence.span.clone()], fence.dedent);
+                    dedented.as_str()
+                };
+                match parse_admonition(
+                    &info_string,
+                    admonition_defaults,
+                    span_content,
+                    on_failure,
+                    fence.indent,
+                    &source_location,
+                    &mut state.warnings,
+                ) {
+                    Some(admonition) => (fence.span, fence.quote, admonition?),
+                    None => continue,
+                }
+            }
+            Block::Syntax(block) => {
+                body = block.body;
+                let admonition =
+                    match strict_error(&admonition_defaults.directives, &block.config.directive) {
+                        Some(message) => {
+                            let original = &content[block.span.clone()];
+                            match failed_admonition(
+                                &message,
+                                original,
+                                &enclosing_fence(original),
+                                on_failure,
+                                block.indent,
+                                &source_location,
+                                &mut state.warnings,
+                            ) {
+                                Some(admonition) => admonition?,
+                                None => continue,
+                            }
+                        }
+                        None => admonition_from_config(
+                            block.config,
+                            admonition_defaults,
+                            &body,
+                            block.indent,
+                            &mut state.warnings,
+                        ),
+                    };
+                (block.span, None, admonition)
+            }
+        };
+
+        let render_text_mode = render_modes.get(&admonition.directive);
+        if render_text_mode == RenderTextMode::Preserve {
+            continue;
+        }
+        state.warnings.admonition(&admonition.directive);
+        if matches!(
+            render_text_mode,
+            RenderTextMode::Html | RenderTextMode::Epub
+        ) && admonition.numbered
+            && !admonition.title.is_empty()
+            && html_options.numbering.applies(&admonition.directive)
+        {
+            let number = state
+                .assigned
+                .numbers
+                .entry(admonition.directive.clone())
+                .or_default();
+            *number += 1;
+            admonition.number = Some(match &state.number_prefix {
+                Some(prefix) => format!("{prefix}.{number}"),
+                None => number.to_string(),
+            });
+        }
+
+        // Stripped blocks are indexed too, so references to them still resolve
+        let id = match render_text_mode {
+            RenderTextMode::Html
+            | RenderTextMode::Strip
+            | RenderTextMode::Alerts
+            | RenderTextMode::Blockquote
+            | RenderTextMode::Latex
+            | RenderTextMode::Epub => {
+                let id = state
+                    .assigned
+                    .anchor_id(&admonition, html_options, &mut state.warnings);
+                state.index.push(IndexEntry {
+                    chapter_path: chapter_path.map(Path::to_owned),
+                    chapter_name: chapter.map(|chapter| chapter.name.to_owned()),
+                    line: location.line,
+                    id: id.clone(),
+                    directive: admonition.directive.clone(),
+                    directive_title: admonition.directive_title.clone(),
+                    title: admonition.reference_title(html_options),
+                    number: admonition
+                        .number
+                        .as_ref()
+                        .map(|number| format!("{} {number}", admonition.directive_title)),
+                    linked: matches!(
+                        render_text_mode,
+                        RenderTextMode::Html | RenderTextMode::Epub
+                    ),
+                    tags: admonition.tags.clone(),
+                    collapsible: admonition.collapsible,
+                    length: admonition.content.chars().count(),
+                });
+                id
+            }
+            RenderTextMode::Drop | RenderTextMode::Preserve => String::new(),
+        };
+
+        // Blocks nested in the content, such as inside a longer outer fence,
+        // are rendered in document order, after this one
+        if render_text_mode != RenderTextMode::Drop {
+            let nested = preprocess_blocks(&admonition.content, location.line, state)?;
+            admonition.content = Cow::Owned(nested);
+            state.warnings.set_location(Some(location));
+        }
+
+        // Once we've identitified admonition blocks, handle them differently
+        // depending on our render mode
+        let new_content = match render_text_mode {
+            RenderTextMode::Html => {
+                admonition.html_with_id(&id, html_options, &state.anchor_base)?
+            }
+            RenderTextMode::Strip => admonition.strip(html_options.strip_title),
+            RenderTextMode::Alerts => admonition.alert(),
+            RenderTextMode::Blockquote => admonition.blockquote(),
+            RenderTextMode::Latex => admonition.latex(html_options),
+            RenderTextMode::Epub => admonition.epub(&id, html_options),
+            RenderTextMode::Drop | RenderTextMode::Preserve => String::new(),
+        };
+        let new_content = if html_options.backlinks
+            && matches!(
+                render_text_mode,
+                RenderTextMode::Html | RenderTextMode::Epub
             ) {
-                Some(admonition) => admonition,
-                None => continue,
-            };
+            format!(
+                "{new_content}\n{}{}",
+                " ".repeat(admonition.indent),
+                backlinks_placeholder(&id)
+            )
+        } else {
+            new_content
+        };
+        let new_content = match quote {
+            Some(quote) => quote_lines(&new_content, &quote),
+            None => new_content,
+        };
+
+        admonish_blocks.push((span, new_content));
+    }
+
+    // Blocks are in document order, and don't overlap, so the output can be
+    // assembled in a single pass
+    let mut output = String::with_capacity(
+        content.len()
+            + admonish_blocks
+                .iter()
+                .map(|(_, block)| block.len())
+                .sum::<usize>(),
+    );
+    let mut copied = 0;
+    for (span, block) in &admonish_blocks {
+        output.push_str(&content[copied..span.start]);
+        output.push_str(block);
+        copied = span.end;
+    }
+    output.push_str(&content[copied..]);
 
-            let admonition = admonition?;
+    Ok(output)
+}
+
+const INDENT_SCAN_MAX: usize = 1024;
 
-            // Once we've identitified admonition blocks, handle them differently
-            // depending on our render mode
-            let new_content = match render_text_mode {
-                RenderTextMode::Html => admonition.html_with_unique_ids(&mut id_counter),
-                RenderTextMode::Strip => admonition.strip(),
-            };
+/// A block which may be an admonition, in any supported syntax.
+enum Block {
+    Fenced(FencedBlock),
+    Syntax(Box<SyntaxBlock>),
+}
 
-            admonish_blocks.push((span, new_content));
+impl Block {
+    fn span(&self) -> &Range<usize> {
+        match self {
+            Block::Fenced(fence) => &fence.span,
+            Block::Syntax(block) => &block.span,
         }
     }
+}
 
-    let mut content = content.to_string();
-    for (span, block) in admonish_blocks.iter().rev() {
-        let pre_content = &content[..span.start];
-        let post_content = &content[span.end..];
-        content = format!("{}{}{}", pre_content, block, post_content);
+/// A fenced code block, which may be an admonition.
+#[derive(Debug, PartialEq)]
+struct FencedBlock {
+    /// From the start of the opening fence, to the end of the closing fence.
+    span: Range<usize>,
+    info_string: String,
+    indent: usize,
+    /// Number of spaces to remove from the start of each content line.
+    dedent: usize,
+    /// Blockquote markers before the opening fence, such as `> `, which
+    /// start each line of the block.
+    quote: Option<String>,
+}
+
+/// Find all fenced code blocks in a region of raw HTML.
+///
+/// The markdown parser treats everything up to the end of an HTML block as
+/// raw HTML, including any code fences. Fences are matched in the same way as
+/// the commonmark spec, and each block is treated as if it was indented
+/// (and contained) by the start of the HTML block.
+fn fences_in_html(content: &str, region: Range<usize>) -> Vec<FencedBlock> {
+    let html = &content[region.clone()];
+    let indent = indent_of(content, region.start, INDENT_SCAN_MAX) + leading_spaces(html);
+
+    let mut fences = Vec::new();
+    // The opening fence, if we're inside a code block
+    let mut open: Option<(usize, usize, char, usize, &str)> = None;
+    let mut offset = region.start;
+    for line in html.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+
+        let fence_indent = leading_spaces(line);
+        let rest = line[fence_indent..].trim_end();
+        let fence_character = match rest.chars().next() {
+            Some(character @ ('`' | '~')) => character,
+            _ => continue,
+        };
+        let fence_length = rest.chars().take_while(|c| *c == fence_character).count();
+        if fence_length < 3 {
+            continue;
+        }
+        let info_string = rest[fence_length..].trim();
+
+        match open {
+            None => {
+                if fence_character == '`' && info_string.contains('`') {
+                    continue;
+                }
+                open = Some((
+                    line_start + fence_indent,
+                    fence_indent,
+                    fence_character,
+                    fence_length,
+                    info_string,
+                ));
+            }
+            Some((start, open_indent, open_character, open_length, open_info_string)) => {
+                if fence_character != open_character
+                    || fence_length < open_length
+                    || !info_string.is_empty()
+                {
+                    continue;
+                }
+                fences.push(FencedBlock {
+                    span: start..line_start + fence_indent + rest.len(),
+                    info_string: open_info_string.to_owned(),
+                    indent,
+                    dedent: open_indent.saturating_sub(indent),
+                    quote: None,
+                });
+                open = None;
+            }
+        }
     }
+    fences
+}
 
-    Ok(content)
+fn leading_spaces(text: &str) -> usize {
+    text.len() - text.trim_start_matches(' ').len()
+}
+
+/// Remove up to `amount` leading spaces from each line of `text`, after the
+/// first line.
+pub(crate) fn dedent_lines(text: &str, amount: usize) -> String {
+    let mut lines = text.split_inclusive('\n');
+    let mut dedented = lines.next().unwrap_or_default().to_owned();
+    for line in lines {
+        dedented.push_str(&line[leading_spaces(line).min(amount)..]);
+    }
+    dedented
+}
+
+/// Split the text before `position` on its line into any blockquote markers,
+/// and the indent after them.
+///
+/// Without blockquote markers, this is the same as [`indent_of`].
+fn quote_of(content: &str, position: usize) -> (Option<String>, usize) {
+    let line_start = content[..position]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let prefix = &content[line_start..position];
+    match prefix.rfind('>') {
+        Some(marker) => {
+            // A single space after the last marker belongs to it
+            let end = marker + 1 + usize::from(prefix[marker + 1..].starts_with(' '));
+            (
+                Some(prefix[..end].to_owned()),
+                prefix[end..].chars().count(),
+            )
+        }
+        None => (None, indent_of(content, position, INDENT_SCAN_MAX)),
+    }
+}
+
+/// Remove the blockquote markers in `quote` from the start of each line of
+/// `text`, after the first line.
+///
+/// Lazy continuation lines, without markers, are left as they are.
+fn unquote_lines(text: &str, quote: &str) -> String {
+    let depth = quote.matches('>').count();
+    let mut lines = text.split_inclusive('\n');
+    let mut unquoted = lines.next().unwrap_or_default().to_owned();
+    for line in lines {
+        let mut rest = line;
+        for _ in 0..depth {
+            match rest.trim_start_matches(' ').strip_prefix('>') {
+                Some(after) => rest = after.strip_prefix(' ').unwrap_or(after),
+                None => break,
+            }
+        }
+        unquoted.push_str(rest);
+    }
+    unquoted
+}
+
+/// Start each line of `text` after the first with the blockquote markers in
+/// `quote`, the inverse of [`unquote_lines`].
+///
+/// Any list markers before the blockquote markers are replaced with spaces, as
+/// they only appear on the first line of a list item.
+fn quote_lines(text: &str, quote: &str) -> String {
+    let markers = quote
+        .chars()
+        .map(|c| if c == '>' { c } else { ' ' })
+        .collect::<String>();
+    let mut lines = text.split('\n');
+    let mut quoted = lines.next().unwrap_or_default().to_owned();
+    for line in lines {
+        quoted.push('\n');
+        if line.is_empty() {
+            quoted.push_str(markers.trim_end());
+        } else {
+            quoted.push_str(&markers);
+        }
+        quoted.push_str(line);
+    }
+    quoted
+}
+
+/// Register the anchor id of every heading in `content` with `id_counter`.
+///
+/// Ids are generated in the same way as the mdbook HTML renderer.
+fn reserve_heading_ids(content: &str, opts: Options, id_counter: &mut HashMap<String, usize>) {
+    let mut heading: Option<Vec<Event>> = None;
+    for event in Parser::new_ext(content, opts) {
+        match event {
+            Event::Start(Tag::Heading(..)) => heading = Some(Vec::new()),
+            Event::End(Tag::Heading(..)) => {
+                let mut html = String::new();
+                push_html(&mut html, heading.take().unwrap_or_default().into_iter());
+                unique_id_from_content(&html, id_counter);
+            }
+            event => {
+                if let Some(heading) = heading.as_mut() {
+                    heading.push(event);
+                }
+            }
+        }
+    }
+}
+
+/// Register the explicit id of every fenced block in `content`, and of any
+/// nested in them, with `ids`.
+fn reserve_explicit_ids(content: &str, opts: Options, ids: &mut HashSet<String>) {
+    let mut body: Option<String> = None;
+    for event in Parser::new_ext(content, opts) {
+        match event {
+            Event::Start(Tag::CodeBlock(Fenced(info_string))) => {
+                if let Some(config) = InstanceConfig::from_info_string(&info_string) {
+                    ids.extend(config.ok().and_then(|config| config.id));
+                    body = Some(String::new());
+                }
+            }
+            Event::Text(text) => {
+                if let Some(body) = body.as_mut() {
+                    body.push_str(&text);
+                }
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                if let Some(body) = body.take() {
+                    reserve_explicit_ids(&body, opts, ids);
+                }
+            }
+            _ => {}
+        }
+    }
 }
 
 /// Returns the indent of the given position.
@@ -89,10 +762,43 @@ fn indent_of(content: &str, position: usize, max: usize) -> usize {
         .unwrap_or_default()
 }
 
+/// A code fence of backticks, long enough to enclose `content`.
+pub(crate) fn enclosing_fence(content: &str) -> String {
+    let longest_run = content
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    "`".repeat(longest_run.max(2) + 1)
+}
+
+/// The one-based line and column of `position` in `content`.
+fn location_of(content: &str, position: usize) -> Location {
+    let before = &content[..position];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    Location {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    #[cfg(feature = "templates")]
+    use crate::{book_config::WrapperConfig, template::Templates};
+    use crate::{
+        book_config::{
+            AnchorLinks, DirectiveConfig, IconMode, LinkAttributes, LinkPolicy, Markup, Preset,
+            StripTitle, TitleBarConfig,
+        },
+        render::Numbering,
+        slug::hash_hex,
+        types::CustomDirectives,
+        warnings::WarningCode,
+    };
     use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
 
     #[test]
     fn indent_of_samples() {
@@ -132,12 +838,46 @@ mod test {
         }
     }
 
+    #[test]
+    fn indexes_line_numbers() {
+        let content = r#"# Chapter
+
+```admonish
+First
+```
+
+Text
+
+  ```admonish warning
+  Second
+  ```
+"#;
+        let mut index = AdmonitionIndex::default();
+        preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &HtmlOptions::default(),
+            None,
+            &mut Warnings::default(),
+            &mut index,
+        )
+        .unwrap();
+        let lines: Vec<_> = index.entries.iter().map(|entry| entry.line).collect();
+        assert_eq!(lines, vec![3, 9]);
+    }
+
     fn prep(content: &str) -> String {
         preprocess(
             content,
             OnFailure::Continue,
             &AdmonitionDefaults::default(),
-            RenderTextMode::Html,
+            &RenderTextMode::Html.into(),
+            &HtmlOptions::default(),
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
         )
         .unwrap()
     }
@@ -591,7 +1331,7 @@ Error rendering admonishment
 </div>
 <div>
 
-Failed with:
+Failed at line 2 with:
 
 ```log
 TOML parsing error: TOML parse error at line 1, column 8
@@ -630,129 +1370,593 @@ Bonus content!
                 content,
                 OnFailure::Bail,
                 &AdmonitionDefaults::default(),
-                RenderTextMode::Html
+                &RenderTextMode::Html.into(),
+                &HtmlOptions::default(),
+                None,
+                &mut Warnings::default(),
+                &mut AdmonitionIndex::default(),
             )
             .unwrap_err()
             .to_string(),
-            r#"Error processing admonition, bailing:
+            r#"Error processing admonition at line 2, bailing:
 ```admonish title="
 Bonus content!
 ```"#
                 .to_owned()
+        );
+
+        let error = preprocess(
+            content,
+            OnFailure::Bail,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &HtmlOptions {
+                src_dir: PathBuf::from("src"),
+                ..Default::default()
+            },
+            Some(ChapterInfo {
+                name: "Chapter",
+                path: Some(Path::new("guide/chapter.md")),
+                number: None,
+            }),
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
         )
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Error processing admonition at src/guide/chapter.md:2, bailing:"));
+        // The reason is kept in the error chain
+        assert!(error
+            .chain()
+            .nth(1)
+            .unwrap()
+            .to_string()
+            .starts_with("TOML parsing error"));
     }
 
     #[test]
-    fn test_renderer_strip_explicit() {
+    fn preserve_on_error_output() {
         let content = r#"
-````admonish title="Title"
-```rust
-let x = 10;
-x = 20;
+```admonish title="
+Bonus content!
 ```
-````
+
+```admonish
+Valid
+```
+"#;
+        let mut warnings = Warnings::default();
+        let output = preprocess(
+            content,
+            OnFailure::Preserve,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Strip.into(),
+            &HtmlOptions::default(),
+            None,
+            &mut warnings,
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            r#"
+```admonish title="
+Bonus content!
+```
+
+
+Valid
+
+"#
+        );
+        let diagnostics = warnings.into_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0]
+            .message
+            .starts_with("Error processing admonition at line 2, leaving it as written."));
+    }
+
+    #[test]
+    fn render_alerts() {
+        let content = r#"# Chapter
+
+```admonish warning title="Data loss"
+Back up first.
+
+Then continue.
+```
+
+- Item
+
+  ```admonish tip
+  Nested
+  ```
 "#;
         assert_eq!(
             preprocess(
                 content,
-                OnFailure::Bail,
+                OnFailure::Continue,
                 &AdmonitionDefaults::default(),
-                RenderTextMode::Strip
+                &RenderTextMode::Alerts.into(),
+                &HtmlOptions::default(),
+                None,
+                &mut Warnings::default(),
+                &mut AdmonitionIndex::default(),
             )
             .unwrap(),
-            r#"
+            r#"# Chapter
 
-```rust
-let x = 10;
-x = 20;
-```
+
+> [!WARNING]
+> **Data loss**
+>
+> Back up first.
+>
+> Then continue.
+
+
+- Item
+
+  
+  > [!TIP]
+  > Nested
 
 "#
-            .to_owned()
-        )
+        );
     }
 
     #[test]
-    fn block_collapsible() {
-        let content = r#"
-```admonish collapsible=true
-Hidden
+    fn render_blockquote() {
+        let content = r#"```admonish warning
+Back up first.
 ```
-"#;
-
-        let expected = r##"
 
-<details id="admonition-note" class="admonition admonish-note">
-<summary class="admonition-title">
-
-Note
+```admonish tip title=""
+Untitled
+```
+"#;
+        assert_eq!(
+            preprocess(
+                content,
+                OnFailure::Continue,
+                &AdmonitionDefaults::default(),
+                &RenderTextMode::Blockquote.into(),
+                &HtmlOptions::default(),
+                None,
+                &mut Warnings::default(),
+                &mut AdmonitionIndex::default(),
+            )
+            .unwrap(),
+            r#"
+> **Warning**
+>
+> Back up first.
 
-<a class="admonition-anchor-link" href="#admonition-note"></a>
-</summary>
-<div>
 
-Hidden
 
-</div>
-</details>
-"##;
+> Untitled
 
-        assert_eq!(expected, prep(content));
+"#
+        );
     }
 
     #[test]
-    fn default_toml_title() {
-        let content = r#"# Chapter
-```admonish
-A simple admonition.
+    fn render_latex() {
+        let content = r#"```admonish warning title="50% off & more"
+Back up **first**.
+```
+
+```admonish security title=""
+Custom
 ```
-Text
 "#;
+        let security = Directive::custom("security");
+        let admonition_defaults = AdmonitionDefaults {
+            directives: CustomDirectives {
+                names: [("security".to_owned(), security.clone())]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let html_options = HtmlOptions {
+            directive: [(
+                security,
+                DirectiveConfig {
+                    color: Some("#e16".to_owned()),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            preprocess(
+                content,
+                OnFailure::Continue,
+                &admonition_defaults,
+                &RenderTextMode::Latex.into(),
+                &html_options,
+                None,
+                &mut Warnings::default(),
+                &mut AdmonitionIndex::default(),
+            )
+            .unwrap(),
+            r#"
+```{=latex}
+\definecolor{admonish-warning}{HTML}{FF9100}
+\begin{tcolorbox}[colframe=admonish-warning, colback=admonish-warning!5!white, title={50\% off \& more}]
+```
 
-        let expected = r##"# Chapter
+Back up **first**.
 
-<div id="admonition-admonish" class="admonition admonish-note">
-<div class="admonition-title">
+```{=latex}
+\end{tcolorbox}
+```
 
-Admonish
 
-<a class="admonition-anchor-link" href="#admonition-admonish"></a>
-</div>
-<div>
 
-A simple admonition.
+```{=latex}
+\definecolor{admonish-security}{HTML}{EE1166}
+\begin{tcolorbox}[colframe=admonish-security, colback=admonish-security!5!white]
+```
 
-</div>
-</div>
-Text
-"##;
+Custom
 
-        let preprocess_result = preprocess(
-            content,
-            OnFailure::Continue,
-            &AdmonitionDefaults {
-                title: Some("Admonish".to_owned()),
-                collapsible: false,
-            },
-            RenderTextMode::Html,
-        )
-        .unwrap();
-        assert_eq!(expected, preprocess_result);
+```{=latex}
+\end{tcolorbox}
+```
+
+"#
+        );
     }
 
     #[test]
-    fn empty_explicit_title_with_default() {
-        let content = r#"# Chapter
-```admonish title=""
-A simple admonition.
-```
-Text
+    fn render_latex_indented() {
+        let content = r#"- Item
+
+  ```admonish tip
+  Nested
+  ```
 "#;
+        assert_eq!(
+            preprocess(
+                content,
+                OnFailure::Continue,
+                &AdmonitionDefaults::default(),
+                &RenderTextMode::Latex.into(),
+                &HtmlOptions::default(),
+                None,
+                &mut Warnings::default(),
+                &mut AdmonitionIndex::default(),
+            )
+            .unwrap(),
+            r#"- Item
+
+  
+  ```{=latex}
+  \definecolor{admonish-tip}{HTML}{00BFA5}
+  \begin{tcolorbox}[colframe=admonish-tip, colback=admonish-tip!5!white, title={Tip}]
+  ```
+  
+  Nested
+  
+  ```{=latex}
+  \end{tcolorbox}
+  ```
 
-        let expected = r#"# Chapter
+"#
+        );
+    }
 
-<div id="admonition-default" class="admonition admonish-note">
-<div>
+    #[test]
+    fn render_epub() {
+        let content = r#"```admonish warning collapsible=true title="Mind the *gap*"
+Back up **first**.
+```
+
+```admonish tip title=""
+Untitled
+```
+"#;
+        assert_eq!(
+            preprocess(
+                content,
+                OnFailure::Continue,
+                &AdmonitionDefaults::default(),
+                &RenderTextMode::Epub.into(),
+                &HtmlOptions::default(),
+                None,
+                &mut Warnings::default(),
+                &mut AdmonitionIndex::default(),
+            )
+            .unwrap(),
+            r#"
+<div id="admonition-mind-the-gap" class="admonition admonish-warning" style="margin: 1em 0; padding: 0.1em 0.8em; border-left: 0.25em solid #FF9100; background-color: #FFF4E6;">
+<p class="admonition-title" style="margin: 0.6em 0; font-weight: bold;">Mind the <em>gap</em></p>
+
+Back up **first**.
+
+</div>
+
+
+<div id="admonition-default" class="admonition admonish-tip" style="margin: 1em 0; padding: 0.1em 0.8em; border-left: 0.25em solid #00BFA5; background-color: #E6F9F6;">
+
+Untitled
+
+</div>
+"#
+        );
+    }
+
+    #[test]
+    fn test_renderer_strip_explicit() {
+        let content = r#"
+````admonish title="Title"
+```rust
+let x = 10;
+x = 20;
+```
+````
+"#;
+        assert_eq!(
+            preprocess(
+                content,
+                OnFailure::Bail,
+                &AdmonitionDefaults::default(),
+                &RenderTextMode::Strip.into(),
+                &HtmlOptions::default(),
+                None,
+                &mut Warnings::default(),
+                &mut AdmonitionIndex::default(),
+            )
+            .unwrap(),
+            r#"
+
+```rust
+let x = 10;
+x = 20;
+```
+
+"#
+            .to_owned()
+        )
+    }
+
+    #[test]
+    fn strip_keeps_titles() {
+        let content = r#"
+```admonish danger title="Data loss"
+Back up first.
+```
+
+```admonish note title=""
+Untitled
+```
+"#;
+        let strip = |strip_title| {
+            preprocess(
+                content,
+                OnFailure::Bail,
+                &AdmonitionDefaults::default(),
+                &RenderTextMode::Strip.into(),
+                &HtmlOptions {
+                    strip_title,
+                    ..Default::default()
+                },
+                None,
+                &mut Warnings::default(),
+                &mut AdmonitionIndex::default(),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            strip(StripTitle::Bold),
+            r#"
+**Data loss**
+
+Back up first.
+
+
+Untitled
+
+"#
+        );
+        assert_eq!(
+            strip(StripTitle::Heading),
+            r#"
+#### Data loss
+Back up first.
+
+
+
+Untitled
+
+"#
+        );
+        // Line numbering of the source is kept, for test output and warnings
+        for strip_title in [StripTitle::None, StripTitle::Heading, StripTitle::Bold] {
+            assert_eq!(
+                strip(strip_title).lines().count(),
+                content.lines().count(),
+                "{strip_title:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn block_collapsible() {
+        let content = r#"
+```admonish collapsible=true
+Hidden
+```
+"#;
+
+        let expected = r##"
+
+<details id="admonition-note" class="admonition admonish-note">
+<summary class="admonition-title">
+
+Note
+
+<a class="admonition-anchor-link" href="#admonition-note"></a>
+</summary>
+<div>
+
+Hidden
+
+</div>
+</details>
+"##;
+
+        assert_eq!(expected, prep(content));
+    }
+
+    #[test]
+    fn block_open() {
+        let content = r#"
+```admonish open=true
+Shown
+```
+"#;
+
+        let expected = r##"
+
+<details id="admonition-note" class="admonition admonish-note" open>
+<summary class="admonition-title">
+
+Note
+
+<a class="admonition-anchor-link" href="#admonition-note"></a>
+</summary>
+<div>
+
+Shown
+
+</div>
+</details>
+"##;
+
+        assert_eq!(expected, prep(content));
+    }
+
+    #[test]
+    fn block_no_icon() {
+        let content = r#"
+```admonish tip icon=false
+Plain
+```
+"#;
+
+        let expected = r##"
+
+<div id="admonition-tip" class="admonition admonish-tip admonish-no-icon">
+<div class="admonition-title">
+
+Tip
+
+<a class="admonition-anchor-link" href="#admonition-tip"></a>
+</div>
+<div>
+
+Plain
+
+</div>
+</div>
+"##;
+
+        assert_eq!(expected, prep(content));
+    }
+
+    #[test]
+    fn block_float() {
+        let content = r#"
+```admonish tip float="right" width="30%"
+Beside the text
+```
+"#;
+
+        let expected = r##"
+
+<div id="admonition-tip" class="admonition admonish-tip admonish-sized admonish-float-right" style="--admonish-width: 30%">
+<div class="admonition-title">
+
+Tip
+
+<a class="admonition-anchor-link" href="#admonition-tip"></a>
+</div>
+<div>
+
+Beside the text
+
+</div>
+</div>
+"##;
+
+        assert_eq!(expected, prep(content));
+    }
+
+    #[test]
+    fn default_toml_title() {
+        let content = r#"# Chapter
+```admonish
+A simple admonition.
+```
+Text
+"#;
+
+        let expected = r##"# Chapter
+
+<div id="admonition-admonish" class="admonition admonish-note">
+<div class="admonition-title">
+
+Admonish
+
+<a class="admonition-anchor-link" href="#admonition-admonish"></a>
+</div>
+<div>
+
+A simple admonition.
+
+</div>
+</div>
+Text
+"##;
+
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults {
+                title: Some("Admonish".to_owned()),
+                collapsible: false,
+                ..Default::default()
+            },
+            &RenderTextMode::Html.into(),
+            &HtmlOptions::default(),
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn empty_explicit_title_with_default() {
+        let content = r#"# Chapter
+```admonish title=""
+A simple admonition.
+```
+Text
+"#;
+
+        let expected = r#"# Chapter
+
+<div id="admonition-default" class="admonition admonish-note">
+<div>
 
 A simple admonition.
 
@@ -767,8 +1971,13 @@ Text
             &AdmonitionDefaults {
                 title: Some("Admonish".to_owned()),
                 collapsible: false,
+                ..Default::default()
             },
-            RenderTextMode::Html,
+            &RenderTextMode::Html.into(),
+            &HtmlOptions::default(),
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
         )
         .unwrap();
         assert_eq!(expected, preprocess_result);
@@ -841,7 +2050,7 @@ Text
    </div>
    <div>
    
-      Thing two
+   Thing two
    
    </div>
    </div>
@@ -855,4 +2064,2452 @@ Text
 
         assert_eq!(expected, prep(content));
     }
+
+    #[cfg(feature = "templates")]
+    #[test]
+    fn directive_template() {
+        let content = r#"# Chapter
+```admonish danger
+Do not touch.
+```
+
+```admonish note
+Not templated.
+```
+"#;
+
+        let expected = r##"# Chapter
+
+<section id="admonition-danger" class="admonition admonish-danger" role="alert">
+<h4>Danger</h4>
+
+Do not touch.
+
+</section>
+
+
+<div id="admonition-note" class="admonition admonish-note">
+<div class="admonition-title">
+
+Note
+
+<a class="admonition-anchor-link" href="#admonition-note"></a>
+</div>
+<div>
+
+Not templated.
+
+</div>
+</div>
+"##;
+
+        let html_options = HtmlOptions {
+            templates: Templates {
+                directive: [(
+                    Directive::Danger,
+                    r#"<section id="{{id}}" class="{{classes}}" role="alert">
+{{#if title}}<h4>{{title}}</h4>{{/if}}
+
+{{content}}
+
+</section>
+"#
+                    .to_owned(),
+                )]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[cfg(feature = "templates")]
+    #[test]
+    fn wrappers() {
+        let content = r#"# Chapter
+- Item
+  ```admonish tip
+  Tip.
+  ```
+```admonish example
+Example.
+```
+"#;
+
+        let expected = r##"# Chapter
+- Item
+  
+  <div class="callout" data-directive="tip">
+  <div id="admonition-tip" class="admonition admonish-tip">
+  <div class="admonition-title">
+  
+  Tip
+  
+  <a class="admonition-anchor-link" href="#admonition-tip"></a>
+  </div>
+  <div>
+  
+  Tip.
+  
+  </div>
+  </div>
+  </div>
+
+<figure>
+<div id="admonition-example" class="admonition admonish-example">
+<div class="admonition-title">
+
+Example
+
+<a class="admonition-anchor-link" href="#admonition-example"></a>
+</div>
+<div>
+
+Example.
+
+</div>
+</div>
+</div>
+"##;
+
+        let html_options = HtmlOptions {
+            templates: Templates {
+                wrapper: WrapperConfig {
+                    before: Some(
+                        r#"<div class="callout" data-directive="{{directive}}">"#.to_owned(),
+                    ),
+                    after: Some("</div>".to_owned()),
+                },
+                directive_wrapper: [(
+                    Directive::Example,
+                    WrapperConfig {
+                        before: Some("<figure>".to_owned()),
+                        ..Default::default()
+                    },
+                )]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn directive_title_bar() {
+        let content = r#"# Chapter
+```admonish warning
+Read the policy.
+```
+"#;
+
+        let expected = r##"# Chapter
+
+<div id="admonition-warning" class="admonition admonish-warning">
+<div class="admonition-title">
+
+<span class="badge">Policy</span>Warning<a href="/policy.html">More</a>
+
+<a class="admonition-anchor-link" href="#admonition-warning"></a>
+</div>
+<div>
+
+Read the policy.
+
+</div>
+</div>
+"##;
+
+        let html_options = HtmlOptions {
+            directive: [(
+                Directive::Warning,
+                DirectiveConfig {
+                    title_bar: TitleBarConfig {
+                        before: Some(r#"<span class="badge">Policy</span>"#.to_owned()),
+                        after: Some(r#"<a href="/policy.html">More</a>"#.to_owned()),
+                    },
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn heading_id_scheme() {
+        let content = r#"# Warning
+```admonish warning
+Avoids the heading id.
+```
+
+~~~admonish tip title="Use `foo` *carefully*"
+Markup is stripped.
+~~~
+"#;
+
+        let expected = r##"# Warning
+
+<div id="warning-1" class="admonition admonish-warning">
+<div class="admonition-title">
+
+Warning
+
+<a class="admonition-anchor-link" href="#warning-1"></a>
+</div>
+<div>
+
+Avoids the heading id.
+
+</div>
+</div>
+
+
+<div id="use-foo-carefully" class="admonition admonish-tip">
+<div class="admonition-title">
+
+Use `foo` *carefully*
+
+<a class="admonition-anchor-link" href="#use-foo-carefully"></a>
+</div>
+<div>
+
+Markup is stripped.
+
+</div>
+</div>
+"##;
+
+        let html_options = HtmlOptions {
+            id_scheme: IdScheme::Heading,
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn body_class() {
+        let content = r#"
+```admonish body_class="scrollable"
+Wide content.
+```
+
+```admonish example
+Uses the directive default.
+```
+"#;
+
+        let expected = r##"
+
+<div id="admonition-note" class="admonition admonish-note">
+<div class="admonition-title">
+
+Note
+
+<a class="admonition-anchor-link" href="#admonition-note"></a>
+</div>
+<div class="scrollable">
+
+Wide content.
+
+</div>
+</div>
+
+
+<div id="admonition-example" class="admonition admonish-example">
+<div class="admonition-title">
+
+Example
+
+<a class="admonition-anchor-link" href="#admonition-example"></a>
+</div>
+<div class="grid">
+
+Uses the directive default.
+
+</div>
+</div>
+"##;
+
+        let html_options = HtmlOptions {
+            directive: [(
+                Directive::Example,
+                DirectiveConfig {
+                    body_class: Some("grid".to_owned()),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn alerts_round_trip() {
+        let content = "```admonish abstract\nSummary.\n```\n\n```admonish danger\nStop.\n```\n";
+        let alerts = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Alerts.into(),
+            &HtmlOptions::default(),
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert!(alerts.contains("> [!IMPORTANT]") && alerts.contains("> [!CAUTION]"));
+
+        let html_options = HtmlOptions {
+            syntax: [Syntax::Github].into_iter().collect(),
+            ..Default::default()
+        };
+        let mut index = AdmonitionIndex::default();
+        preprocess(
+            &alerts,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut index,
+        )
+        .unwrap();
+        assert_eq!(
+            index
+                .entries
+                .iter()
+                .map(|entry| entry.directive.clone())
+                .collect::<Vec<_>>(),
+            vec![Directive::Abstract, Directive::Danger]
+        );
+    }
+
+    #[test]
+    fn github_alerts() {
+        let content = r#"# Chapter
+> [!IMPORTANT]
+> Read this first.
+>
+> ```admonish note
+> Nested blocks are rendered too.
+> ```
+
+> Plain quote
+"#;
+
+        let expected = r##"# Chapter
+
+<div id="admonition-important" class="admonition admonish-abstract">
+<div class="admonition-title">
+
+Important
+
+<a class="admonition-anchor-link" href="#admonition-important"></a>
+</div>
+<div>
+
+Read this first.
+
+
+<div id="admonition-note" class="admonition admonish-note">
+<div class="admonition-title">
+
+Note
+
+<a class="admonition-anchor-link" href="#admonition-note"></a>
+</div>
+<div>
+
+Nested blocks are rendered too.
+
+</div>
+</div>
+
+</div>
+</div>
+
+> Plain quote
+"##;
+
+        let html_options = HtmlOptions {
+            syntax: [Syntax::Github].into_iter().collect(),
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+
+        // Alerts are only recognized when enabled
+        let content = "> [!NOTE]\n> Content\n";
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &HtmlOptions::default(),
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(content, preprocess_result);
+    }
+
+    #[test]
+    fn mkdocs_admonitions() {
+        let content = r#"# Chapter
+
+!!! warning "Careful"
+    Indented content.
+
+    ```rust
+    let code = "kept";
+    ```
+
+??? example
+    Collapsed.
+
+After.
+"#;
+
+        let expected = r##"# Chapter
+
+
+<div id="admonition-careful" class="admonition admonish-warning">
+<div class="admonition-title">
+
+Careful
+
+<a class="admonition-anchor-link" href="#admonition-careful"></a>
+</div>
+<div>
+
+Indented content.
+
+```rust
+let code = "kept";
+```
+
+</div>
+</div>
+
+
+<details id="admonition-example" class="admonition admonish-example">
+<summary class="admonition-title">
+
+Example
+
+<a class="admonition-anchor-link" href="#admonition-example"></a>
+</summary>
+<div>
+
+Collapsed.
+
+</div>
+</details>
+
+After.
+"##;
+
+        let html_options = HtmlOptions {
+            syntax: [Syntax::Mkdocs].into_iter().collect(),
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn pandoc_divs() {
+        let content = r#"# Chapter
+
+::: {.note title="Pandoc"}
+Content.
+:::
+
+::: sidebar
+Not an admonition.
+:::
+"#;
+
+        let expected = r##"# Chapter
+
+
+<div id="admonition-pandoc" class="admonition admonish-note">
+<div class="admonition-title">
+
+Pandoc
+
+<a class="admonition-anchor-link" href="#admonition-pandoc"></a>
+</div>
+<div>
+
+Content.
+
+</div>
+</div>
+
+::: sidebar
+Not an admonition.
+:::
+"##;
+
+        let html_options = HtmlOptions {
+            syntax: [Syntax::Pandoc].into_iter().collect(),
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn obsidian_callouts() {
+        let content = r#"# Chapter
+> [!tip]+ Expanded
+> Shown initially.
+"#;
+
+        let expected = r##"# Chapter
+
+<details id="admonition-expanded" class="admonition admonish-tip" open>
+<summary class="admonition-title">
+
+Expanded
+
+<a class="admonition-anchor-link" href="#admonition-expanded"></a>
+</summary>
+<div>
+
+Shown initially.
+
+</div>
+</details>
+"##;
+
+        let html_options = HtmlOptions {
+            syntax: [Syntax::Obsidian].into_iter().collect(),
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn custom_directive() {
+        let content = r#"# Chapter
+```admonish sec
+Rotate your keys.
+```
+"#;
+
+        let expected = r##"# Chapter
+
+<div id="admonition-security-notice" class="admonition admonish-security admonish-custom" style="--admonish-color: #e91e63">
+<div class="admonition-title">
+
+Security notice
+
+<a class="admonition-anchor-link" href="#admonition-security-notice"></a>
+</div>
+<div>
+
+Rotate your keys.
+
+</div>
+</div>
+"##;
+
+        let security = Directive::custom("security");
+        let admonition_defaults = AdmonitionDefaults {
+            directives: CustomDirectives {
+                names: [("sec".to_owned(), security.clone())].into_iter().collect(),
+                titles: [(security.clone(), "Security notice".to_owned())]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let html_options = HtmlOptions {
+            directive_style: [(security, "--admonish-color: #e91e63".to_owned())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &admonition_defaults,
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn print_visibility() {
+        let content = r#"# Chapter
+```admonish tip
+Hidden in print by default.
+```
+```admonish tip print=true title="Printed tip"
+Printed.
+```
+```admonish note print_only=true
+Print only.
+```
+"#;
+
+        let expected = r##"# Chapter
+
+<div id="admonition-tip" class="admonition admonish-tip admonish-no-print">
+<div class="admonition-title">
+
+Tip
+
+<a class="admonition-anchor-link" href="#admonition-tip"></a>
+</div>
+<div>
+
+Hidden in print by default.
+
+</div>
+</div>
+
+<div id="admonition-printed-tip" class="admonition admonish-tip">
+<div class="admonition-title">
+
+Printed tip
+
+<a class="admonition-anchor-link" href="#admonition-printed-tip"></a>
+</div>
+<div>
+
+Printed.
+
+</div>
+</div>
+
+<div id="admonition-note" class="admonition admonish-note admonish-print-only">
+<div class="admonition-title">
+
+Note
+
+<a class="admonition-anchor-link" href="#admonition-note"></a>
+</div>
+<div>
+
+Print only.
+
+</div>
+</div>
+"##;
+
+        let html_options = HtmlOptions {
+            directive: [(
+                Directive::Tip,
+                DirectiveConfig {
+                    print: Some(false),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn sidenotes() {
+        let content = r#"# Chapter
+```admonish tip
+In the margin by default.
+```
+```admonish tip sidenote=false title="Inline tip"
+Inline.
+```
+```admonish note sidenote=true
+In the margin.
+```
+"#;
+
+        let expected = r##"# Chapter
+
+<div id="admonition-tip" class="admonition admonish-tip admonish-sidenote">
+<div class="admonition-title">
+
+Tip
+
+<a class="admonition-anchor-link" href="#admonition-tip"></a>
+</div>
+<div>
+
+In the margin by default.
+
+</div>
+</div>
+
+<div id="admonition-inline-tip" class="admonition admonish-tip">
+<div class="admonition-title">
+
+Inline tip
+
+<a class="admonition-anchor-link" href="#admonition-inline-tip"></a>
+</div>
+<div>
+
+Inline.
+
+</div>
+</div>
+
+<div id="admonition-note" class="admonition admonish-note admonish-sidenote">
+<div class="admonition-title">
+
+Note
+
+<a class="admonition-anchor-link" href="#admonition-note"></a>
+</div>
+<div>
+
+In the margin.
+
+</div>
+</div>
+"##;
+
+        let html_options = HtmlOptions {
+            directive: [(
+                Directive::Tip,
+                DirectiveConfig {
+                    sidenote: Some(true),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn block_size() {
+        let content = r#"# Chapter
+```admonish note width="60%" max_width="40rem"
+Narrow.
+```
+```admonish note width="1px;color:red"
+Invalid.
+```
+"#;
+
+        let expected = r##"# Chapter
+
+<div id="admonition-note" class="admonition admonish-note admonish-sized" style="--admonish-width: 60%; --admonish-max-width: 40rem">
+<div class="admonition-title">
+
+Note
+
+<a class="admonition-anchor-link" href="#admonition-note"></a>
+</div>
+<div>
+
+Narrow.
+
+</div>
+</div>
+"##;
+
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &HtmlOptions::default(),
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert!(preprocess_result.starts_with(expected));
+        assert!(preprocess_result.contains("Invalid size '1px;color:red'"));
+    }
+
+    #[test]
+    fn microdata() {
+        let content = r#"# Chapter
+```admonish question title="Why?"
+Because.
+```
+"#;
+
+        let expected = r##"# Chapter
+
+<div id="admonition-why" class="admonition admonish-question" itemscope itemtype="https://schema.org/Question">
+<div class="admonition-title">
+
+<span itemprop="name">Why?</span>
+
+<a class="admonition-anchor-link" href="#admonition-why"></a>
+</div>
+<div itemprop="text">
+
+Because.
+
+</div>
+</div>
+"##;
+
+        let html_options = HtmlOptions {
+            microdata: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            expected,
+            preprocess(
+                content,
+                OnFailure::Continue,
+                &AdmonitionDefaults::default(),
+                &RenderTextMode::Html.into(),
+                &html_options,
+                None,
+                &mut Warnings::default(),
+                &mut AdmonitionIndex::default(),
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn microdata_without_title() {
+        let content = r#"# Chapter
+```admonish question title=""
+Because.
+```
+"#;
+
+        let expected = r##"# Chapter
+
+<div id="admonition-default" class="admonition admonish-question" itemscope itemtype="https://schema.org/Question">
+<div itemprop="text">
+
+Because.
+
+</div>
+</div>
+"##;
+
+        let html_options = HtmlOptions {
+            microdata: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            expected,
+            preprocess(
+                content,
+                OnFailure::Continue,
+                &AdmonitionDefaults::default(),
+                &RenderTextMode::Html.into(),
+                &html_options,
+                None,
+                &mut Warnings::default(),
+                &mut AdmonitionIndex::default(),
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn title_url() {
+        let content = r#"
+```admonish tip title="See the full guide" url="guide.md#setup&more"
+Content
+```
+"#;
+
+        let expected = r##"
+
+<div id="admonition-see-the-full-guide" class="admonition admonish-tip">
+<div class="admonition-title">
+
+<a class="admonition-title-link" href="guide.md#setup&amp;more">See the full guide</a>
+
+<a class="admonition-anchor-link" href="#admonition-see-the-full-guide"></a>
+</div>
+<div>
+
+Content
+
+</div>
+</div>
+"##;
+
+        assert_eq!(expected, prep(content));
+    }
+
+    #[test]
+    fn link_policy() {
+        let content = r#"
+```admonish tip title="Guide" url="https://example.com/guide"
+Content
+```
+"#;
+
+        let expected = r##"
+
+<div id="admonition-guide" class="admonition admonish-tip">
+<div class="admonition-title">
+
+<a class="admonition-title-link" href="https://example.com/guide" target="_blank" rel="noopener">Guide</a>
+
+<a class="admonition-anchor-link" href="#admonition-guide" target="_self"></a>
+</div>
+<div>
+
+Content
+
+</div>
+</div>
+"##;
+
+        let html_options = HtmlOptions {
+            links: LinkPolicy {
+                internal: LinkAttributes {
+                    target: Some("_self".to_owned()),
+                    rel: None,
+                },
+                external: LinkAttributes {
+                    target: Some("_blank".to_owned()),
+                    rel: Some("noopener".to_owned()),
+                },
+            },
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn deprecated_syntax_warnings() {
+        let content = r#"# Chapter
+```admonish note.custom "Title"
+One
+```
+```admonish note.custom "Title"
+Two
+```
+```admonish note class="custom" title="Title"
+Three
+```
+"#;
+
+        let mut warnings = Warnings::default();
+        preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &HtmlOptions::default(),
+            None,
+            &mut warnings,
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            warnings.unique(),
+            vec![(
+                WarningCode::DeprecatedSyntax,
+                r#"Deprecated admonition syntax 'admonish note.custom "Title"'. Use key/value options instead, such as 'admonish note title="..." class="..."'"#,
+                2
+            )]
+        );
+
+        let mut warnings = Warnings::default();
+        preprocess(
+            &format!("<!-- admonish-allow: deprecated-syntax -->\n{content}"),
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &HtmlOptions::default(),
+            None,
+            &mut warnings,
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(warnings.unique(), vec![]);
+    }
+
+    #[test]
+    fn block_warnings() {
+        let content = r#"# Chapter
+
+```admonish warnign
+Typo
+```
+
+  ```admonish tip
+  ```
+
+```admonish title="\j"
+Invalid
+```
+"#;
+
+        let mut warnings = Warnings::default();
+        preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &HtmlOptions::default(),
+            Some(ChapterInfo {
+                name: "Chapter",
+                path: Some(Path::new("chapter.md")),
+                number: None,
+            }),
+            &mut warnings,
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        let diagnostics = warnings.into_diagnostics();
+        assert_eq!(
+            diagnostics[0].message,
+            "Unknown directive 'warnign', rendering as 'note'. Did you mean 'warning'?"
+        );
+        let diagnostics = diagnostics
+            .into_iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.column, diagnostic.code))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            vec![
+                (Some(3), Some(1), "unknown-directive"),
+                (Some(7), Some(3), "empty-block"),
+                (Some(10), Some(1), "invalid-block"),
+            ]
+        );
+    }
+
+    #[test]
+    fn strict_unknown_directives() {
+        let content = r#"```admonish warnign
+Typo
+```
+
+!!! warnign
+    Typo
+"#;
+        let defaults = AdmonitionDefaults {
+            directives: CustomDirectives {
+                strict: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let html_options = HtmlOptions {
+            syntax: [Syntax::Mkdocs].into_iter().collect(),
+            ..Default::default()
+        };
+
+        let mut warnings = Warnings::default();
+        let mut index = AdmonitionIndex::default();
+        preprocess(
+            content,
+            OnFailure::Continue,
+            &defaults,
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut warnings,
+            &mut index,
+        )
+        .unwrap();
+        // Both are rendered as errors
+        assert_eq!(
+            index
+                .entries
+                .iter()
+                .map(|entry| entry.directive.clone())
+                .collect::<Vec<_>>(),
+            vec![Directive::Bug, Directive::Bug]
+        );
+        assert!(warnings
+            .into_diagnostics()
+            .iter()
+            .all(|diagnostic| diagnostic.code == "invalid-block"
+                && diagnostic
+                    .message
+                    .ends_with("Unknown directive 'warnign'. Did you mean 'warning'?")));
+
+        for content in [
+            "```admonish warnign\nTypo\n```\n",
+            "!!! warnign\n    Typo\n",
+        ] {
+            let error = preprocess(
+                content,
+                OnFailure::Bail,
+                &defaults,
+                &RenderTextMode::Html.into(),
+                &html_options,
+                None,
+                &mut Warnings::default(),
+                &mut AdmonitionIndex::default(),
+            )
+            .unwrap_err();
+            assert!(error
+                .to_string()
+                .starts_with("Error processing admonition at line 1, bailing:"));
+        }
+    }
+
+    #[test]
+    fn list_embed_rust_playground() {
+        let content = r#"# Chapter
+
+-   Item
+
+    ````admonish example
+    ```rust,editable
+    # fn hidden() {}
+    fn main() {}
+    ```
+    ````
+"#;
+
+        let expected = r##"# Chapter
+
+-   Item
+
+    
+    <div id="admonition-example" class="admonition admonish-example">
+    <div class="admonition-title">
+    
+    Example
+    
+    <a class="admonition-anchor-link" href="#admonition-example"></a>
+    </div>
+    <div>
+    
+    ```rust,editable
+    # fn hidden() {}
+    fn main() {}
+    ```
+    
+    </div>
+    </div>
+"##;
+
+        assert_eq!(expected, prep(content));
+    }
+
+    #[test]
+    fn admonish_in_html_block() {
+        let content = r#"# Chapter
+<div class="wrapper">
+```admonish tip
+Inside *HTML*
+```
+</div>
+Text
+"#;
+
+        let expected = r##"# Chapter
+<div class="wrapper">
+
+<div id="admonition-tip" class="admonition admonish-tip">
+<div class="admonition-title">
+
+Tip
+
+<a class="admonition-anchor-link" href="#admonition-tip"></a>
+</div>
+<div>
+
+Inside *HTML*
+
+</div>
+</div>
+</div>
+Text
+"##;
+
+        assert_eq!(expected, prep(content));
+    }
+
+    #[test]
+    fn fences_in_html_samples() {
+        let content = r#"<details>
+  ~~~admonish note
+  ```admonish
+  Not a block
+  ```
+    Content
+  ~~~~
+  ```rust
+  ```
+</details>"#;
+        assert_eq!(
+            fences_in_html(content, 0..content.len()),
+            vec![
+                FencedBlock {
+                    span: 12..81,
+                    info_string: "admonish note".to_owned(),
+                    indent: 0,
+                    dedent: 2,
+                    quote: None,
+                },
+                FencedBlock {
+                    span: 84..97,
+                    info_string: "rust".to_owned(),
+                    indent: 0,
+                    dedent: 2,
+                    quote: None,
+                },
+            ]
+        );
+        assert_eq!(
+            dedent_lines(&content[12..81], 2),
+            "~~~admonish note\n```admonish\nNot a block\n```\n  Content\n~~~~"
+        );
+    }
+
+    #[test]
+    fn xhtml_markup() {
+        let content = r#"# Chapter
+~~~admonish note title="Line<br>break &amp; `<code>`"
+Content
+~~~
+"#;
+
+        let expected = r##"# Chapter
+
+<div id="admonition-linebreak--" class="admonition admonish-note">
+<div class="admonition-title">
+
+Line<br />break &amp; <code>&lt;code&gt;</code>
+
+<a class="admonition-anchor-link" href="#admonition-linebreak--"></a>
+</div>
+<div class="a&amp;b">
+
+Content
+
+</div>
+</div>
+"##;
+
+        let html_options = HtmlOptions {
+            markup: Markup::Xhtml,
+            directive: [(
+                Directive::Note,
+                DirectiveConfig {
+                    body_class: Some("a&b".to_owned()),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn directive_title_format() {
+        let content = r#"# Chapter
+```admonish warning
+Default title.
+```
+```admonish warning title="Data loss"
+Explicit title.
+```
+```admonish warning title=""
+No title.
+```
+"#;
+
+        let expected = r##"# Chapter
+
+<div id="admonition-warning" class="admonition admonish-warning">
+<div class="admonition-title">
+
+⚠ Warning: Warning
+
+<a class="admonition-anchor-link" href="#admonition-warning"></a>
+</div>
+<div>
+
+Default title.
+
+</div>
+</div>
+
+<div id="admonition-data-loss" class="admonition admonish-warning">
+<div class="admonition-title">
+
+⚠ Warning: Data loss
+
+<a class="admonition-anchor-link" href="#admonition-data-loss"></a>
+</div>
+<div>
+
+Explicit title.
+
+</div>
+</div>
+
+<div id="admonition-default" class="admonition admonish-warning">
+<div>
+
+No title.
+
+</div>
+</div>
+"##;
+
+        let html_options = HtmlOptions {
+            directive: [(
+                Directive::Warning,
+                DirectiveConfig {
+                    title_format: Some("⚠ {directive_title}: {title}".to_owned()),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn numbering_opt_out() {
+        let content = r#"# Chapter
+```admonish example numbered=false title="Aside"
+Not numbered.
+```
+```admonish example class="unnumbered"
+Not numbered.
+```
+```admonish example
+First.
+```
+"#;
+
+        let html_options = HtmlOptions {
+            numbering: Numbering::All,
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        let titles = preprocess_result
+            .lines()
+            .filter(|line| line.starts_with("Aside") || line.starts_with("Example"))
+            .collect::<Vec<_>>();
+        assert_eq!(titles, vec!["Aside", "Example", "Example 1"]);
+    }
+
+    #[test]
+    fn numbering_chapter_prefix() {
+        let content = r#"# Chapter
+```admonish example
+First.
+```
+```admonish example title="Parsing"
+Second.
+```
+"#;
+        let chapter = ChapterInfo {
+            name: "Chapter",
+            path: Some(Path::new("chapter.md")),
+            number: Some(&[3, 1]),
+        };
+
+        let html_options = HtmlOptions {
+            numbering: Numbering::All,
+            chapter_prefix: true,
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            Some(chapter),
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        let titles = preprocess_result
+            .lines()
+            .filter(|line| line.starts_with("Example"))
+            .collect::<Vec<_>>();
+        assert_eq!(titles, vec!["Example 3.1.1", "Example 3.1.2: Parsing"]);
+    }
+
+    #[test]
+    fn numbering_by_directive() {
+        let content = r#"# Chapter
+```admonish example
+First.
+```
+```admonish note
+Not numbered.
+```
+```admonish example title="Parsing"
+Second.
+```
+```admonish example title=""
+No title.
+```
+"#;
+
+        let expected = r##"# Chapter
+
+<div id="admonition-example" class="admonition admonish-example">
+<div class="admonition-title">
+
+Example 1
+
+<a class="admonition-anchor-link" href="#admonition-example"></a>
+</div>
+<div>
+
+First.
+
+</div>
+</div>
+
+<div id="admonition-note" class="admonition admonish-note">
+<div class="admonition-title">
+
+Note
+
+<a class="admonition-anchor-link" href="#admonition-note"></a>
+</div>
+<div>
+
+Not numbered.
+
+</div>
+</div>
+
+<div id="admonition-parsing" class="admonition admonish-example">
+<div class="admonition-title">
+
+Example 2: Parsing
+
+<a class="admonition-anchor-link" href="#admonition-parsing"></a>
+</div>
+<div>
+
+Second.
+
+</div>
+</div>
+
+<div id="admonition-default" class="admonition admonish-example">
+<div>
+
+No title.
+
+</div>
+</div>
+"##;
+
+        let html_options = HtmlOptions {
+            numbering: Numbering::Directives([Directive::Example].into_iter().collect()),
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn auto_title_collapsible() {
+        let content = r#"# Chapter
+```admonish collapsible=true
+Install the toolchain first. Then run the build.
+```
+"#;
+
+        let expected = r##"# Chapter
+
+<details id="admonition-install-the-toolchain-first" class="admonition admonish-note">
+<summary class="admonition-title">
+
+Install the toolchain first
+
+<a class="admonition-anchor-link" href="#admonition-install-the-toolchain-first"></a>
+</summary>
+<div>
+
+Then run the build.
+
+</div>
+</details>
+"##;
+
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults {
+                auto_title: true,
+                ..Default::default()
+            },
+            &RenderTextMode::Html.into(),
+            &HtmlOptions::default(),
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn landmark_regions() {
+        let content = r#"# Chapter
+```admonish
+Short.
+```
+```admonish warning title="Data loss"
+This block is long enough to be a landmark.
+```
+```admonish warning title=""
+This block is long enough to be a landmark.
+```
+"#;
+
+        let expected = r##"# Chapter
+
+<div id="admonition-note" class="admonition admonish-note">
+<div class="admonition-title">
+
+Note
+
+<a class="admonition-anchor-link" href="#admonition-note"></a>
+</div>
+<div>
+
+Short.
+
+</div>
+</div>
+
+<div id="admonition-data-loss" class="admonition admonish-warning" role="region" aria-labelledby="admonition-data-loss-title">
+<div id="admonition-data-loss-title" class="admonition-title">
+
+Data loss
+
+<a class="admonition-anchor-link" href="#admonition-data-loss"></a>
+</div>
+<div>
+
+This block is long enough to be a landmark.
+
+</div>
+</div>
+
+<div id="admonition-default" class="admonition admonish-warning" role="region" aria-label="Warning">
+<div>
+
+This block is long enough to be a landmark.
+
+</div>
+</div>
+"##;
+
+        let html_options = HtmlOptions {
+            landmark_min_length: Some(20),
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn semantic_html() {
+        let content = r#"# Chapter
+```admonish
+Short.
+```
+```admonish warning title="Data loss"
+This block is long enough to be a landmark.
+```
+```admonish tip title="" collapsible=true
+Collapsed.
+```
+"#;
+
+        let expected = r##"# Chapter
+
+<aside id="admonition-note" class="admonition admonish-note" role="note" aria-labelledby="admonition-note-title">
+<div id="admonition-note-title" class="admonition-title">
+
+Note
+
+<a class="admonition-anchor-link" href="#admonition-note"></a>
+</div>
+<div>
+
+Short.
+
+</div>
+</aside>
+
+<section id="admonition-data-loss" class="admonition admonish-warning" role="region" aria-labelledby="admonition-data-loss-title">
+<div id="admonition-data-loss-title" class="admonition-title">
+
+Data loss
+
+<a class="admonition-anchor-link" href="#admonition-data-loss"></a>
+</div>
+<div>
+
+This block is long enough to be a landmark.
+
+</div>
+</section>
+
+<details id="admonition-default" class="admonition admonish-tip">
+<div>
+
+Collapsed.
+
+</div>
+</details>
+"##;
+
+        let html_options = HtmlOptions {
+            landmark_min_length: Some(20),
+            semantic_html: true,
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn explicit_id() {
+        let content = r#"# Chapter
+```admonish warning title="Back up" id="backups"
+Kept.
+```
+```admonish warning title="Back up"
+Generated.
+```
+"#;
+
+        let expected = r##"# Chapter
+
+<div id="backups" class="admonition admonish-warning">
+<div class="admonition-title">
+
+Back up
+
+<a class="admonition-anchor-link" href="#backups"></a>
+</div>
+<div>
+
+Kept.
+
+</div>
+</div>
+
+<div id="admonition-back-up" class="admonition admonish-warning">
+<div class="admonition-title">
+
+Back up
+
+<a class="admonition-anchor-link" href="#admonition-back-up"></a>
+</div>
+<div>
+
+Generated.
+
+</div>
+</div>
+"##;
+
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &HtmlOptions::default(),
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert_eq!(expected, preprocess_result);
+    }
+
+    #[test]
+    fn explicit_id_collisions() {
+        let content = r#"# Chapter
+```admonish note
+Generated, before the explicit id.
+```
+```admonish note id="admonition-note"
+Explicit.
+```
+```admonish tip id="shared"
+First.
+```
+```admonish tip id="shared"
+Second.
+```
+"#;
+
+        let mut warnings = Warnings::default();
+        let mut index = AdmonitionIndex::default();
+        preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &HtmlOptions::default(),
+            None,
+            &mut warnings,
+            &mut index,
+        )
+        .unwrap();
+        assert_eq!(
+            vec!["admonition-note-1", "admonition-note", "shared", "shared"],
+            index
+                .entries
+                .iter()
+                .map(|entry| entry.id.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            warnings.unique(),
+            vec![(
+                WarningCode::DuplicateId,
+                "Anchor id 'shared' is already used in this chapter",
+                1
+            )]
+        );
+    }
+
+    #[test]
+    fn content_id_scheme() {
+        let html_options = HtmlOptions {
+            id_scheme: IdScheme::Content,
+            ..Default::default()
+        };
+        let preprocess = |content: &str| {
+            preprocess(
+                content,
+                OnFailure::Continue,
+                &AdmonitionDefaults::default(),
+                &RenderTextMode::Html.into(),
+                &html_options,
+                None,
+                &mut Warnings::default(),
+                &mut AdmonitionIndex::default(),
+            )
+            .unwrap()
+        };
+        let block = r#"```admonish warning title="Data loss"
+Back up first.
+```
+"#;
+        let before = preprocess(block);
+        let after = preprocess(&format!(
+            r#"```admonish note title="Data loss"
+Inserted earlier.
+```
+{block}"#
+        ));
+
+        let hash = hash_hex("warning\nData loss\nBack up first.".as_bytes());
+        let id = format!(r#"id="admonition-{hash}""#);
+        assert!(before.contains(&id));
+        assert!(after.contains(&id));
+        // Identical blocks are still unique
+        let repeated = preprocess(&format!("{block}{block}"));
+        assert!(repeated.contains(&id));
+        assert!(repeated.contains(&format!(r#"id="admonition-{hash}-1""#)));
+    }
+
+    #[test]
+    fn emoji_icons() {
+        let content = r#"# Chapter
+```admonish warning
+Careful.
+```
+
+```admonish tip
+Tip.
+```
+"#;
+        let expected = r##"# Chapter
+
+<div id="admonition-warning" class="admonition admonish-warning admonish-emoji">
+<div class="admonition-title">
+
+<span class="admonition-emoji" aria-hidden="true">🔥</span> Warning
+
+<a class="admonition-anchor-link" href="#admonition-warning"></a>
+</div>
+<div>
+
+Careful.
+
+</div>
+</div>
+
+
+<div id="admonition-tip" class="admonition admonish-tip">
+<div class="admonition-title">
+
+Tip
+
+<a class="admonition-anchor-link" href="#admonition-tip"></a>
+</div>
+<div>
+
+Tip.
+
+</div>
+</div>
+"##;
+        let html_options = HtmlOptions {
+            icon_mode: IconMode::Emoji,
+            directive: [
+                (
+                    Directive::Warning,
+                    DirectiveConfig {
+                        emoji: Some("🔥".to_owned()),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    Directive::Tip,
+                    DirectiveConfig {
+                        icon_mode: Some(IconMode::Css),
+                        ..Default::default()
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            expected,
+            preprocess(
+                content,
+                OnFailure::Continue,
+                &AdmonitionDefaults::default(),
+                &RenderTextMode::Html.into(),
+                &html_options,
+                None,
+                &mut Warnings::default(),
+                &mut AdmonitionIndex::default(),
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn preset_classes() {
+        let content = r#"# Chapter
+```admonish tip
+A tip.
+```
+"#;
+        let expected = r##"# Chapter
+
+<div id="admonition-tip" class="admonition admonish-tip admonish-preset-quote-bar">
+<div class="admonition-title">
+
+Tip
+
+<a class="admonition-anchor-link" href="#admonition-tip"></a>
+</div>
+<div>
+
+A tip.
+
+</div>
+</div>
+"##;
+        assert_eq!(
+            expected,
+            preprocess(
+                content,
+                OnFailure::Continue,
+                &AdmonitionDefaults::default(),
+                &RenderTextMode::Html.into(),
+                &HtmlOptions {
+                    preset: Preset::QuoteBar,
+                    ..Default::default()
+                },
+                None,
+                &mut Warnings::default(),
+                &mut AdmonitionIndex::default(),
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn anchor_link_options() {
+        let content = r#"# Chapter
+```admonish
+A note.
+```
+"#;
+        let preprocess = |html_options: &HtmlOptions| {
+            preprocess(
+                content,
+                OnFailure::Continue,
+                &AdmonitionDefaults::default(),
+                &RenderTextMode::Html.into(),
+                html_options,
+                None,
+                &mut Warnings::default(),
+                &mut AdmonitionIndex::default(),
+            )
+            .unwrap()
+        };
+
+        let expected = r##"# Chapter
+
+<div id="admonition-note" class="admonition admonish-note">
+<div class="admonition-title">
+
+Note
+
+<a class="admonition-anchor-link" href="#admonition-note">#</a>
+</div>
+<div>
+
+A note.
+
+</div>
+</div>
+"##;
+        assert_eq!(
+            expected,
+            preprocess(&HtmlOptions {
+                anchor_link_content: Some("#".to_owned()),
+                ..Default::default()
+            })
+        );
+
+        let expected = r##"# Chapter
+
+<div id="admonition-note" class="admonition admonish-note">
+<div class="admonition-title">
+
+Note
+
+</div>
+<div>
+
+A note.
+
+</div>
+</div>
+"##;
+        assert_eq!(
+            expected,
+            preprocess(&HtmlOptions {
+                anchor_links: AnchorLinks::None,
+                ..Default::default()
+            })
+        );
+
+        let expected = r##"# Chapter
+
+<div id="admonition-note" class="admonition admonish-note">
+<div class="admonition-title">
+
+Note
+
+<a class="admonition-anchor-link" href="#admonition-note"></a>
+<button type="button" class="admonition-copy-anchor" data-anchor="#admonition-note" aria-label="Copy link" title="Copy link" hidden></button>
+</div>
+<div>
+
+A note.
+
+</div>
+</div>
+"##;
+        assert_eq!(
+            expected,
+            preprocess(&HtmlOptions {
+                copy_anchor_button: true,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn tags() {
+        let content = r#"# Chapter
+```admonish warning tags=["gotcha", "windows"]
+Paths are case insensitive.
+```
+"#;
+
+        let mut index = AdmonitionIndex::default();
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &AdmonitionDefaults::default(),
+            &RenderTextMode::Html.into(),
+            &HtmlOptions::default(),
+            None,
+            &mut Warnings::default(),
+            &mut index,
+        )
+        .unwrap();
+        assert!(preprocess_result.contains(
+            r#"<div id="admonition-warning" class="admonition admonish-warning" data-tags="gotcha windows">"#
+        ));
+        assert_eq!(index.entries[0].tags, vec!["gotcha", "windows"]);
+    }
+
+    #[test]
+    fn escaped_attributes() {
+        let content = r#"# Chapter
+```admonish qa title="" tags=['say"hi"', "<b>&"]
+Content.
+```
+"#;
+
+        let qa = Directive::custom("qa");
+        let admonition_defaults = AdmonitionDefaults {
+            directives: CustomDirectives {
+                names: [("qa".to_owned(), qa.clone())].into_iter().collect(),
+                titles: [(qa, r#"Q&A "live""#.to_owned())].into_iter().collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let html_options = HtmlOptions {
+            landmark_min_length: Some(0),
+            ..Default::default()
+        };
+        let preprocess_result = preprocess(
+            content,
+            OnFailure::Continue,
+            &admonition_defaults,
+            &RenderTextMode::Html.into(),
+            &html_options,
+            None,
+            &mut Warnings::default(),
+            &mut AdmonitionIndex::default(),
+        )
+        .unwrap();
+        assert!(
+            preprocess_result.contains(
+                r#"<div id="admonition-default" class="admonition admonish-qa admonish-custom" data-tags="say&quot;hi&quot; &lt;b&gt;&amp;" role="region" aria-label="Q&amp;A &quot;live&quot;">"#
+            ),
+            "{preprocess_result}"
+        );
+    }
+
+    #[test]
+    fn nested_admonitions() {
+        let content = r#"# Chapter
+````admonish example collapsible=true
+Outer content.
+
+```admonish warning
+Inner content.
+```
+````
+"#;
+
+        let expected = r##"# Chapter
+
+<details id="admonition-example" class="admonition admonish-example">
+<summary class="admonition-title">
+
+Example
+
+<a class="admonition-anchor-link" href="#admonition-example"></a>
+</summary>
+<div>
+
+Outer content.
+
+
+<div id="admonition-warning" class="admonition admonish-warning">
+<div class="admonition-title">
+
+Warning
+
+<a class="admonition-anchor-link" href="#admonition-warning"></a>
+</div>
+<div>
+
+Inner content.
+
+</div>
+</div>
+
+</div>
+</details>
+"##;
+
+        assert_eq!(expected, prep(content));
+    }
+
+    #[test]
+    fn block_in_blockquote() {
+        let content = r#"# Chapter
+> Quote
+>
+> ```admonish note
+> Inner *text*.
+>
+> More.
+> ```
+>
+> After
+"#;
+
+        let expected = r##"# Chapter
+> Quote
+>
+> 
+> <div id="admonition-note" class="admonition admonish-note">
+> <div class="admonition-title">
+>
+> Note
+>
+> <a class="admonition-anchor-link" href="#admonition-note"></a>
+> </div>
+> <div>
+>
+> Inner *text*.
+>
+> More.
+>
+> </div>
+> </div>
+>
+> After
+"##;
+
+        assert_eq!(expected, prep(content));
+    }
+
+    #[test]
+    fn block_in_nested_containers() {
+        let content = r#"# Chapter
+1. > - item
+   >
+   >   ```admonish tip
+   >   Quoted list.
+   >   ```
+"#;
+
+        let expected = r##"# Chapter
+1. > - item
+   >
+   >   
+   >   <div id="admonition-tip" class="admonition admonish-tip">
+   >   <div class="admonition-title">
+   >   
+   >   Tip
+   >   
+   >   <a class="admonition-anchor-link" href="#admonition-tip"></a>
+   >   </div>
+   >   <div>
+   >   
+   >   Quoted list.
+   >   
+   >   </div>
+   >   </div>
+"##;
+
+        assert_eq!(expected, prep(content));
+    }
+
+    #[test]
+    fn block_in_deeply_nested_list() {
+        let content = r#"# Chapter
+- one
+  - two
+    - three
+      - four
+
+        ```admonish tip
+        Deep.
+
+        Para.
+        ```
+
+        After
+"#;
+
+        let expected = r##"# Chapter
+- one
+  - two
+    - three
+      - four
+
+        
+        <div id="admonition-tip" class="admonition admonish-tip">
+        <div class="admonition-title">
+        
+        Tip
+        
+        <a class="admonition-anchor-link" href="#admonition-tip"></a>
+        </div>
+        <div>
+        
+        Deep.
+
+        Para.
+        
+        </div>
+        </div>
+
+        After
+"##;
+
+        assert_eq!(expected, prep(content));
+    }
+
+    #[test]
+    fn unquote_lines_lazy_continuation() {
+        assert_eq!(
+            "```admonish\nquoted\nlazy\n\n```",
+            unquote_lines("```admonish\n> quoted\nlazy\n>\n>```", "> ")
+        );
+        assert_eq!(
+            "```admonish\n  text",
+            unquote_lines("```admonish\n   > >   text", "> > ")
+        );
+    }
+
+    #[test]
+    fn may_contain_blocks_by_syntax() {
+        let html_options = HtmlOptions::default();
+        assert!(may_contain_blocks("```admonish\n```", &html_options));
+        assert!(!may_contain_blocks("# Chapter\n\nText.\n", &html_options));
+        assert!(!may_contain_blocks("> [!NOTE]\n> Text.\n", &html_options));
+
+        let html_options = HtmlOptions {
+            syntax: [Syntax::Github, Syntax::Pandoc].into_iter().collect(),
+            ..Default::default()
+        };
+        assert!(may_contain_blocks("> [!NOTE]\n> Text.\n", &html_options));
+        assert!(may_contain_blocks("::: note\nText.\n:::\n", &html_options));
+        assert!(!may_contain_blocks("!!! note\n    Text.\n", &html_options));
+    }
 }
@@ -1,12 +1,15 @@
 use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
 use std::borrow::Cow;
 
-pub use crate::preprocessor::Admonish;
 use crate::{
     book_config::OnFailure,
+    config::InstanceConfig,
     render::Admonition,
     resolve::AdmonitionMeta,
-    types::{AdmonitionDefaults, Directive},
+    types::{AdmonitionDefaults, CustomDirectives, Directive, Size},
+    warnings::{ChapterWarnings, WarningCode},
 };
 
 /// Given the content in the span of the code block, and the info string,
@@ -18,19 +21,33 @@ use crate::{
 /// - If configured, break the build.
 ///
 /// If the code block is not an admonition, return `None`.
+///
+/// `location` describes where the block is, such as `src/intro.md:12`, for
+/// error messages.
 pub(crate) fn parse_admonition<'a>(
     info_string: &'a str,
     admonition_defaults: &'a AdmonitionDefaults,
     content: &'a str,
     on_failure: OnFailure,
     indent: usize,
+    location: &str,
+    warnings: &mut ChapterWarnings,
 ) -> Option<Result<Admonition<'a>>> {
     // We need to know fence details anyway for error messages
     let extracted = extract_admonish_body(content);
 
-    let info = AdmonitionMeta::from_info_string(info_string, admonition_defaults)?;
-    let info = match info {
-        Ok(info) => info,
+    let raw = match InstanceConfig::from_info_string(info_string)? {
+        Ok(raw) => {
+            if raw.deprecated_syntax {
+                warnings.warn(
+                    WarningCode::DeprecatedSyntax,
+                    format!(
+                        r#"Deprecated admonition syntax '{info_string}'. Use key/value options instead, such as 'admonish note title="..." class="..."'"#
+                    ),
+                );
+            }
+            raw
+        }
         Err(message) => {
             // Construct a fence capable of enclosing whatever we wrote for the
             // actual input block
@@ -38,40 +55,35 @@ pub(crate) fn parse_admonition<'a>(
             let enclosing_fence: String = std::iter::repeat(fence.character)
                 .take(fence.length + 1)
                 .collect();
-            return Some(match on_failure {
-                OnFailure::Continue => {
-                    log::warn!(
-                        r#"Error processing admonition. To fail the build instead of continuing, set 'on_failure = "bail"'"#
-                    );
-                    Ok(Admonition {
-                        directive: Directive::Bug,
-                        title: "Error rendering admonishment".to_owned(),
-                        additional_classnames: Vec::new(),
-                        collapsible: false,
-                        content: Cow::Owned(format!(
-                            r#"Failed with:
-
-```log
-{message}
-```
-
-Original markdown input:
-
-{enclosing_fence}markdown
-{content}
-{enclosing_fence}
-"#
-                        )),
-                        indent,
-                    })
-                }
-                OnFailure::Bail => Err(anyhow!("Error processing admonition, bailing:\n{content}")),
-            });
+            return failed_admonition(
+                &message,
+                content,
+                &enclosing_fence,
+                on_failure,
+                indent,
+                location,
+                warnings,
+            );
         }
     };
+    if let Some(message) = strict_error(&admonition_defaults.directives, &raw.directive) {
+        let enclosing_fence: String = std::iter::repeat(extracted.fence.character)
+            .take(extracted.fence.length + 1)
+            .collect();
+        return failed_admonition(
+            &message,
+            content,
+            &enclosing_fence,
+            on_failure,
+            indent,
+            location,
+            warnings,
+        );
+    }
 
-    Some(Ok(Admonition::new(
-        info,
+    Some(Ok(admonition_from_config(
+        raw,
+        admonition_defaults,
         extracted.body,
         // Note that this is a bit hacky - the fence information comes from the start
         // of the block, and includes the whole line.
@@ -87,9 +99,212 @@ Original markdown input:
         // not relative to the context of some containing item. But I think that's what we
         // want for now, anyway.
         indent,
+        warnings,
     )))
 }
 
+/// Handle a block that could not be processed, with the error `message`.
+///
+/// Depending on `on_failure`, either render an error in place of the block,
+/// fail the build, or return `None` to leave the block as written.
+/// `content` is the original markdown of the block, and `enclosing_fence` a
+/// code fence able to enclose it.
+pub(crate) fn failed_admonition<'a>(
+    message: &str,
+    content: &str,
+    enclosing_fence: &str,
+    on_failure: OnFailure,
+    indent: usize,
+    location: &str,
+    warnings: &mut ChapterWarnings,
+) -> Option<Result<Admonition<'a>>> {
+    Some(match on_failure {
+        OnFailure::Continue => {
+            // Only a path is code, not a bare line number
+            let shown_location = match location.starts_with("line ") {
+                true => location.to_owned(),
+                false => format!("`{location}`"),
+            };
+            warnings.warn(
+                WarningCode::InvalidBlock,
+                format!(
+                    r#"Error processing admonition. To fail the build instead of continuing, set 'on_failure = "bail"'. Failed with: {}"#,
+                    message.trim_end()
+                ),
+            );
+            Ok(Admonition {
+                directive: Directive::Bug,
+                title: "Error rendering admonishment".to_owned(),
+                directive_title: "Bug".to_owned(),
+                id: None,
+                additional_classnames: Vec::new(),
+                collapsible: false,
+                open: false,
+                icon: true,
+                body_classnames: Vec::new(),
+                url: None,
+                attach: None,
+                align: None,
+                float: None,
+                sidenote: None,
+                size: Size::default(),
+                numbered: false,
+                print: None,
+                tags: Vec::new(),
+                number: None,
+                content: Cow::Owned(format!(
+                    r#"Failed at {shown_location} with:
+
+```log
+{message}
+```
+
+Original markdown input:
+
+{enclosing_fence}markdown
+{content}
+{enclosing_fence}
+"#
+                )),
+                indent,
+            })
+        }
+        OnFailure::Bail => Err(anyhow!("{message}").context(format!(
+            "Error processing admonition at {location}, bailing:\n{content}"
+        ))),
+        OnFailure::Preserve => {
+            warnings.warn(
+                WarningCode::InvalidBlock,
+                format!(
+                    "Error processing admonition at {location}, leaving it as written. Failed with: {}",
+                    message.trim_end()
+                ),
+            );
+            return None;
+        }
+    })
+}
+
+/// The error for a block naming `directive`, if it is unknown and unknown
+/// directives are not allowed.
+pub(crate) fn strict_error(directives: &CustomDirectives, directive: &str) -> Option<String> {
+    if !directives.strict || !is_unknown(directives, directive) {
+        return None;
+    }
+    Some(format!(
+        "Unknown directive '{directive}'{}",
+        did_you_mean(directives, directive)
+    ))
+}
+
+/// Whether `directive` is given, but not builtin or configured.
+fn is_unknown(directives: &CustomDirectives, directive: &str) -> bool {
+    !directive.is_empty() && directives.get(directive).is_none()
+}
+
+/// A suggestion of a known directive, if `directive` looks like a typo.
+fn did_you_mean(directives: &CustomDirectives, directive: &str) -> String {
+    directives
+        .suggest(directive)
+        .map(|name| format!(". Did you mean '{name}'?"))
+        .unwrap_or_default()
+}
+
+/// Resolve the configuration of a block with its body, which may have been
+/// written in any supported syntax.
+pub(crate) fn admonition_from_config<'a>(
+    raw: InstanceConfig,
+    admonition_defaults: &AdmonitionDefaults,
+    mut body: &'a str,
+    indent: usize,
+    warnings: &mut ChapterWarnings,
+) -> Admonition<'a> {
+    let directives = &admonition_defaults.directives;
+    if is_unknown(directives, &raw.directive) {
+        warnings.warn(
+            WarningCode::UnknownDirective,
+            format!(
+                "Unknown directive '{}', rendering as 'note'{}",
+                raw.directive,
+                did_you_mean(directives, &raw.directive)
+            ),
+        );
+    }
+    if body.trim().is_empty() {
+        warnings.warn(WarningCode::EmptyBlock, "Admonition has no content");
+    }
+    let explicit_title = raw.title.is_some();
+    let mut info = AdmonitionMeta::resolve(raw, admonition_defaults);
+    for classname in info.normalize_classnames() {
+        warnings.warn(
+            WarningCode::InvalidClassname,
+            format!(
+                "Ignoring invalid classname '{classname}'. Classnames may only contain letters, numbers, '-' and '_'"
+            ),
+        );
+    }
+    if admonition_defaults
+        .for_directive(&info.directive)
+        .auto_title
+        && info.collapsible
+        && !explicit_title
+    {
+        if let Some((title, rest)) = auto_title(body) {
+            info.title = title;
+            body = rest;
+        }
+    }
+    Admonition::new(info, body, indent)
+}
+
+/// Maximum number of words in a title generated from content.
+const AUTO_TITLE_MAX_WORDS: usize = 12;
+
+/// Generate a title from the first sentence of `body`.
+///
+/// Returns the title, and the remaining body. The sentence is removed from
+/// the body if it was used as the title in full, and other content remains.
+///
+/// Returns `None` if the body does not start with a paragraph of text.
+fn auto_title(body: &str) -> Option<(String, &str)> {
+    let body = body.trim_start();
+    if !body.starts_with(|c: char| c.is_alphanumeric() || c == '`' || c == '"')
+        || body.starts_with("```")
+    {
+        return None;
+    }
+    static RX_BLANK_LINE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"\n[ \t]*\n").expect("blank line regex"));
+    let paragraph_end = RX_BLANK_LINE
+        .find(body)
+        .map_or(body.len(), |blank_line| blank_line.start());
+    let paragraph = &body[..paragraph_end];
+
+    let mut chars = paragraph.char_indices().peekable();
+    let mut sentence_end = paragraph.len();
+    while let Some((index, c)) = chars.next() {
+        let at_boundary = chars.peek().map_or(true, |(_, next)| next.is_whitespace());
+        if matches!(c, '.' | '!' | '?') && at_boundary {
+            sentence_end = index + c.len_utf8();
+            break;
+        }
+    }
+    let sentence = &paragraph[..sentence_end];
+
+    let words = sentence.split_whitespace().collect::<Vec<_>>();
+    if words.len() > AUTO_TITLE_MAX_WORDS {
+        let title = format!("{}…", words[..AUTO_TITLE_MAX_WORDS].join(" "));
+        return Some((title, body));
+    }
+    let title = words.join(" ");
+    let title = title.strip_suffix('.').unwrap_or(&title).to_owned();
+    let rest = body[sentence_end..].trim_start();
+    if rest.is_empty() {
+        return Some((title, body));
+    }
+    Some((title, rest))
+}
+
 /// We can't trust the info string length to find the start of the body
 /// it may change length if it contains HTML or character escapes.
 ///
@@ -167,8 +382,65 @@ fn extract_admonish_body(content: &str) -> Extracted<'_> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::warnings::Warnings;
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn test_auto_title() {
+        for (body, expected) in [
+            (
+                "First sentence. Second sentence.\n\nMore content.",
+                Some(("First sentence", "Second sentence.\n\nMore content.")),
+            ),
+            ("Is this\nwrapped? Yes.", Some(("Is this wrapped?", "Yes."))),
+            // The whole body is kept, if the title is the only content
+            ("Only sentence.", Some(("Only sentence", "Only sentence."))),
+            // Versions are not sentence ends
+            (
+                "Requires v1.2 or later.\n\nMore",
+                Some(("Requires v1.2 or later", "More")),
+            ),
+            // Long sentences are truncated, and the whole body is kept
+            (
+                "one two three four five six seven eight nine ten eleven twelve thirteen.",
+                Some((
+                    "one two three four five six seven eight nine ten eleven twelve…",
+                    "one two three four five six seven eight nine ten eleven twelve thirteen.",
+                )),
+            ),
+            // Not a paragraph
+            ("```rust\nfn main() {}\n```", None),
+            ("- list item", None),
+            ("", None),
+        ] {
+            let actual = auto_title(body);
+            let actual = actual.as_ref().map(|(title, rest)| (title.as_str(), *rest));
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn failure_location() {
+        for (location, expected) in [
+            ("line 2", "Failed at line 2 with:"),
+            ("src/intro.md:2", "Failed at `src/intro.md:2` with:"),
+        ] {
+            let mut warnings = Warnings::default();
+            let admonition = failed_admonition(
+                "invalid",
+                "```admonish\n```",
+                "````",
+                OnFailure::Continue,
+                0,
+                location,
+                &mut warnings.chapter(None, ""),
+            )
+            .unwrap()
+            .unwrap();
+            assert!(admonition.content.starts_with(expected));
+        }
+    }
+
     #[test]
     fn test_extract_start() {
         for (text, expected) in [
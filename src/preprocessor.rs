@@ -1,18 +1,34 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use mdbook::{
-    book::{Book, BookItem},
+    book::{Book, BookItem, Chapter},
     errors::Result as MdbookResult,
     preprocess::{Preprocessor, PreprocessorContext},
 };
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
 
 use crate::{
-    book_config::{admonish_config_from_context, Config, RenderMode},
-    markdown::preprocess,
-    types::RenderTextMode,
+    book_config::{admonish_config_from_context, Config},
+    cache::{CachedChapter, ChapterCache},
+    css,
+    index::{AdmonitionIndex, Backlinks},
+    markdown::{preprocess, ChapterInfo},
+    render::HtmlOptions,
+    types::RenderModes,
+    warnings::Warnings,
 };
 
 pub struct Admonish;
 
+const DEFAULT_APPENDIX_TITLE: &str = "Admonitions";
+const DEFAULT_APPENDIX_PATH: &str = "admonitions.md";
+const DEFAULT_TAG_INDEX_TITLE: &str = "Tags";
+const DEFAULT_TAG_INDEX_PATH: &str = "tags.md";
+const DEFAULT_BACKLINKS_TITLE: &str = "Referenced from";
+
 impl Preprocessor for Admonish {
     fn name(&self) -> &str {
         "admonish"
@@ -20,53 +36,127 @@ impl Preprocessor for Admonish {
 
     fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> MdbookResult<Book> {
         let config = admonish_config_from_context(ctx)?;
-        ensure_compatible_assets_version(&config)?;
-
-        let on_failure = config.on_failure;
-        let admonition_defaults = config.default;
+        // Inline styles always match this version
+        if !config.inline_css {
+            ensure_compatible_assets_version(&config)?;
+        }
+        config.check_defaults()?;
+        config.check_parts()?;
 
         // Load what rendering we should do from config, falling back to a default
-        let render_mode = config
-            .renderer
-            .get(&ctx.renderer)
-            .and_then(|renderer| renderer.render_mode)
-            .unwrap_or_else(|| {
-                // By default only render html for the html renderer
-                // For everything else, do nothing
-                if &ctx.renderer == "html" {
-                    RenderMode::Html
-                } else {
-                    RenderMode::Preserve
-                }
-            });
-        let render_text_mode = match render_mode {
-            RenderMode::Preserve => return Ok(book),
-            RenderMode::Html => RenderTextMode::Html,
-            RenderMode::Strip => RenderTextMode::Strip,
+        let render_modes = config.render_modes(&ctx.renderer)?;
+        if render_modes.all_preserved() {
+            return Ok(book);
+        }
+        let site_url = ctx
+            .config
+            .get("output.html.site-url")
+            .and_then(|site_url| site_url.as_str());
+        let mut html_options = HtmlOptions::from_config(&ctx.root, site_url, &config)?;
+        html_options.src_dir = ctx.config.book.src.clone();
+        let mut warnings = Warnings::from_config(&config.warnings, &config.default.directives)?;
+        let mut index = AdmonitionIndex::default();
+        let mut cache = match (&config.cache_dir, &config.external_renderer) {
+            // The output of an external renderer may change at any time
+            (Some(cache_dir), None) => Some(ChapterCache::load(
+                ctx.root.join(cache_dir),
+                &cache_inputs(ctx, &config)?,
+            )),
+            _ => None,
         };
 
-        let mut res = None;
-        book.for_each_mut(|item: &mut BookItem| {
-            if let Some(Err(_)) = res {
-                return;
-            }
+        let result = preprocess_chapters(
+            &config,
+            &render_modes,
+            &html_options,
+            &mut book,
+            &mut warnings,
+            &mut index,
+            cache.as_mut(),
+        );
+        warnings.log();
+        warnings.ensure_none_denied()?;
+        result?;
+        if let Some(Err(error)) = cache.map(|cache| cache.save()) {
+            log::warn!("{error:#}");
+        }
 
-            if let BookItem::Chapter(ref mut chapter) = *item {
-                res = Some(
-                    preprocess(
-                        &chapter.content,
-                        on_failure,
-                        &admonition_defaults,
-                        render_text_mode,
-                    )
-                    .map(|md| {
-                        chapter.content = md;
-                    }),
-                );
+        let mut backlinks = Backlinks::default();
+        if html_options.backlinks {
+            for item in book.iter() {
+                if let BookItem::Chapter(Chapter {
+                    name,
+                    content,
+                    path: Some(path),
+                    ..
+                }) = item
+                {
+                    index.collect_backlinks(content, path, name, &mut backlinks);
+                }
+            }
+        }
+        let backlinks_title = config
+            .backlinks
+            .title
+            .as_deref()
+            .unwrap_or(DEFAULT_BACKLINKS_TITLE);
+
+        // References can be to any chapter, so are expanded once all
+        // admonitions are known
+        let mut res = Ok(());
+        book.for_each_mut(|item: &mut BookItem| {
+            if let (Ok(()), BookItem::Chapter(ref mut chapter)) = (&res, item) {
+                match index.expand_references(&chapter.content, chapter.path.as_deref()) {
+                    Ok(content) if html_options.backlinks => {
+                        chapter.content =
+                            backlinks.expand(&content, chapter.path.as_deref(), backlinks_title)
+                    }
+                    Ok(content) => chapter.content = content,
+                    Err(error) => res = Err(error),
+                }
             }
         });
+        res?;
 
-        res.unwrap_or(Ok(())).map(|_| book)
+        if config.inline_css && render_modes.any_html() {
+            inline_stylesheet(&ctx.root, &config, &mut book, &index)?;
+        }
+
+        if config.appendix.enabled {
+            let title = config
+                .appendix
+                .title
+                .as_deref()
+                .unwrap_or(DEFAULT_APPENDIX_TITLE);
+            let path = config
+                .appendix
+                .path
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_APPENDIX_PATH));
+            let content = index.appendix(
+                title,
+                &path,
+                config.appendix_directives()?.as_ref(),
+                config.appendix.group_by,
+                config.appendix.sort,
+            );
+            book.push_item(generated_chapter(title, content, path));
+        }
+        if config.tag_index.enabled {
+            let title = config
+                .tag_index
+                .title
+                .as_deref()
+                .unwrap_or(DEFAULT_TAG_INDEX_TITLE);
+            let path = config
+                .tag_index
+                .path
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_TAG_INDEX_PATH));
+            let content = index.tag_index(title, &path);
+            book.push_item(generated_chapter(title, content, path));
+        }
+        Ok(book)
     }
 
     fn supports_renderer(&self, _renderer: &str) -> bool {
@@ -76,6 +166,140 @@ impl Preprocessor for Admonish {
     }
 }
 
+/// Render the admonitions in every chapter of `book`, recording them in
+/// `index`.
+///
+/// Chapters found in `cache` are not rendered again. Chapters with warnings
+/// are not cached, so that their warnings are reported on every run.
+pub(crate) fn preprocess_chapters(
+    config: &Config,
+    render_modes: &RenderModes,
+    html_options: &HtmlOptions,
+    book: &mut Book,
+    warnings: &mut Warnings,
+    index: &mut AdmonitionIndex,
+    mut cache: Option<&mut ChapterCache>,
+) -> Result<()> {
+    let mut res = None;
+    // Part titles are only found at the top level, before their chapters
+    let mut part_title = None;
+    book.for_each_mut(|item: &mut BookItem| {
+        if let Some(Err(_)) = res {
+            return;
+        }
+
+        if let BookItem::PartTitle(ref title) = *item {
+            part_title = Some(title.clone());
+        }
+        if let BookItem::Chapter(ref mut chapter) = *item {
+            let key = cache
+                .as_deref()
+                .map(|cache| cache.key(chapter, part_title.as_deref()));
+            if let (Some(cache), Some(key)) = (cache.as_deref_mut(), &key) {
+                if let Some(cached) = cache.get(key) {
+                    chapter.content = cached.content;
+                    index.entries.extend(cached.entries);
+                    return;
+                }
+            }
+            let entries_before = index.entries.len();
+            let warnings_before = warnings.count();
+            let admonition_defaults =
+                config.chapter_defaults(part_title.as_deref(), chapter.path.as_deref());
+            res = Some(
+                preprocess(
+                    &chapter.content,
+                    config.on_failure,
+                    &admonition_defaults,
+                    render_modes,
+                    html_options,
+                    Some(ChapterInfo::from(&*chapter)),
+                    warnings,
+                    index,
+                )
+                .map(|md| {
+                    chapter.content = md;
+                }),
+            );
+            if let (Some(cache), Some(key), Some(Ok(()))) = (cache.as_deref_mut(), key, &res) {
+                if warnings.count() == warnings_before {
+                    cache.insert(
+                        key,
+                        CachedChapter {
+                            content: chapter.content.clone(),
+                            entries: index.entries[entries_before..].to_vec(),
+                        },
+                    );
+                }
+            }
+        }
+    });
+    res.unwrap_or(Ok(()))
+}
+
+/// Everything, other than the chapters, that preprocessed chapters depend on.
+///
+/// This is the renderer and book configuration, and the contents of any files
+/// named by the configuration, so that editing them invalidates the cache.
+fn cache_inputs(ctx: &PreprocessorContext, config: &Config) -> Result<String> {
+    let mut inputs = format!("{}\n{}", ctx.renderer, serde_json::to_string(&ctx.config)?);
+    let shared_config = ctx
+        .config
+        .get("preprocessor.admonish.config")
+        .and_then(|path| path.as_str())
+        .map(Path::new);
+    for path in shared_config.into_iter().chain(config.rendering_files()) {
+        let path = ctx.root.join(path);
+        let contents = fs::read(&path)
+            .with_context(|| format!("Can't read '{}' for the cache", path.display()))?;
+        inputs.push_str(&format!(
+            "\n{}\n{}",
+            path.display(),
+            String::from_utf8_lossy(&contents)
+        ));
+    }
+    Ok(inputs)
+}
+
+/// Append the stylesheet to each chapter with blocks rendered to HTML, with
+/// only the styles for the directives in the chapter.
+fn inline_stylesheet(
+    root: &Path,
+    config: &Config,
+    book: &mut Book,
+    index: &AdmonitionIndex,
+) -> Result<()> {
+    let mut res = Ok(());
+    book.for_each_mut(|item: &mut BookItem| {
+        if let (Ok(()), BookItem::Chapter(ref mut chapter)) = (&res, item) {
+            let used = index
+                .entries
+                .iter()
+                .filter(|entry| entry.linked && entry.chapter_path == chapter.path)
+                .map(|entry| entry.directive.clone())
+                .collect::<HashSet<_>>();
+            if used.is_empty() {
+                return;
+            }
+            match css::stylesheet(root, config, Some(&used)) {
+                Ok(stylesheet) => chapter
+                    .content
+                    .push_str(&format!("\n\n<style>\n{stylesheet}</style>\n")),
+                Err(error) => res = Err(error),
+            }
+        }
+    });
+    res
+}
+
+/// A chapter generated by the preprocessor, appended to the book.
+fn generated_chapter(title: &str, content: String, path: PathBuf) -> Chapter {
+    let mut chapter = Chapter::new(title, content, path, Vec::new());
+    // Generated, so there is no source to edit
+    chapter.source_path = None;
+    chapter
+}
+
 fn ensure_compatible_assets_version(config: &Config) -> Result<()> {
     use semver::{Version, VersionReq};
 
@@ -113,6 +337,7 @@ fn ensure_compatible_assets_version(config: &Config) -> Result<()> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::types::RenderTextMode;
     use pretty_assertions::assert_eq;
     use serde_json::{json, Value};
 
@@ -190,7 +415,7 @@ x = 20;
 
         let ctx = mock_context(
             &json!({
-                "assets_version": "3.0.0"
+                "assets_version": "3.1.0"
             }),
             "html",
         );
@@ -200,6 +425,108 @@ x = 20;
         assert_eq!(Admonish.run(&ctx, book).unwrap(), expected_book)
     }
 
+    #[test]
+    fn run_html_references() {
+        let content = r#"See {{#admonish-ref backups}}.
+
+```admonish warning title="Back up" id="backups"
+Content.
+```
+"#;
+        let ctx = mock_context(
+            &json!({
+                "assets_version": "3.1.0"
+            }),
+            "html",
+        );
+        let book = Admonish.run(&ctx, mock_book(content)).unwrap();
+        let BookItem::Chapter(chapter) = &book.sections[0] else {
+            panic!("expected a chapter");
+        };
+        assert!(chapter.content.starts_with("See [Back up](#backups)."));
+
+        let dangling = mock_book("See {{#admonish-ref missing}}.");
+        assert!(Admonish.run(&ctx, dangling).is_err());
+    }
+
+    #[test]
+    fn run_html_backlinks() {
+        let target = r#"```admonish warning title="Back up" id="backups"
+Content.
+```
+"#;
+        let book: Book = serde_json::from_value(json!({
+            "sections": [
+                {
+                    "Chapter": {
+                        "name": "Setup",
+                        "content": target,
+                        "number": [1],
+                        "sub_items": [],
+                        "path": "guide/setup.md",
+                        "source_path": "guide/setup.md",
+                        "parent_names": []
+                    }
+                },
+                {
+                    "Chapter": {
+                        "name": "Upgrading",
+                        "content": "See {{#admonish-ref backups}}, and again {{#admonish-ref backups number}}.",
+                        "number": [2],
+                        "sub_items": [],
+                        "path": "upgrading.md",
+                        "source_path": "upgrading.md",
+                        "parent_names": []
+                    }
+                }
+            ],
+            "__non_exhaustive": null
+        }))
+        .unwrap();
+        let ctx = mock_context(
+            &json!({
+                "assets_version": "3.1.0",
+                "backlinks": { "enabled": true }
+            }),
+            "html",
+        );
+        let book = Admonish.run(&ctx, book).unwrap();
+        let BookItem::Chapter(chapter) = &book.sections[0] else {
+            panic!("expected a chapter");
+        };
+        assert!(chapter.content.ends_with(
+            r#"</div>
+
+<div class="admonition-backlinks">
+
+Referenced from: [Upgrading](../upgrading.md)
+
+</div>
+"#
+        ));
+    }
+
+    #[test]
+    fn run_html_inline_css() {
+        // No assets need installing
+        let ctx = mock_context(&json!({ "inline_css": true }), "html");
+        let book = Admonish
+            .run(&ctx, mock_book("```admonish\nContent.\n```\n"))
+            .unwrap();
+        let BookItem::Chapter(chapter) = &book.sections[0] else {
+            panic!("expected a chapter");
+        };
+        let (_, stylesheet) = chapter.content.split_once("\n\n<style>\n").unwrap();
+        assert!(stylesheet.ends_with("</style>\n"));
+        // Only with styles for the directives used
+        assert!(stylesheet.contains(".admonish-note"));
+        assert!(!stylesheet.contains(".admonish-tip"));
+
+        // Chapters without blocks are left unstyled
+        let book = Admonish.run(&ctx, mock_book("Content.\n")).unwrap();
+        assert_eq!(book, mock_book("Content.\n"));
+    }
+
     #[test]
     fn run_test_preserves_by_default() {
         let content = r#"
@@ -212,7 +539,7 @@ x = 20;
 "#;
         let ctx = mock_context(
             &json!({
-                "assets_version": "3.0.0"
+                "assets_version": "3.1.0"
             }),
             "test",
         );
@@ -242,7 +569,7 @@ x = 20;
 "#;
         let ctx = mock_context(
             &json!({
-                "assets_version": "3.0.0",
+                "assets_version": "3.1.0",
                 "renderer": {
                     "test": {
                         "render_mode": "strip",
@@ -256,4 +583,225 @@ x = 20;
 
         assert_eq!(Admonish.run(&ctx, book).unwrap(), expected_book)
     }
+
+    #[test]
+    fn preprocess_chapters_cached() {
+        let ctx = mock_context(&json!({ "assets_version": "3.1.0" }), "html");
+        let config = admonish_config_from_context(&ctx).unwrap();
+        let html_options = HtmlOptions::default();
+        let mut cache = ChapterCache::new(PathBuf::from("cache"), "config");
+        let mut preprocess_with = |render_modes: &RenderModes, content| {
+            let mut book = mock_book(content);
+            let mut index = AdmonitionIndex::default();
+            preprocess_chapters(
+                &config,
+                render_modes,
+                &html_options,
+                &mut book,
+                &mut Warnings::default(),
+                &mut index,
+                Some(&mut cache),
+            )
+            .unwrap();
+            (book, index.entries)
+        };
+        let html = config.render_modes("html").unwrap();
+        let strip = RenderModes {
+            default: RenderTextMode::Strip,
+            directive: Default::default(),
+        };
+
+        // A cached chapter is not rendered again, even if it would be
+        // rendered differently
+        let content = "```admonish\nContent.\n```\n";
+        let rendered = preprocess_with(&html, content);
+        assert_eq!(1, rendered.1.len());
+        assert_eq!(rendered, preprocess_with(&strip, content));
+
+        // Chapters with warnings are always rendered
+        let content = "```admonish\n```\n";
+        let rendered = preprocess_with(&html, content);
+        assert_ne!(rendered, preprocess_with(&strip, content));
+    }
+
+    #[test]
+    fn cache_misses_when_template_changes() {
+        let root =
+            std::env::temp_dir().join(format!("mdbook-admonish-cache-test-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let mut ctx = mock_context(
+            &json!({ "assets_version": "3.1.0", "template": "block.hbs" }),
+            "html",
+        );
+        ctx.root = root.clone();
+        let config = admonish_config_from_context(&ctx).unwrap();
+        let chapter = Chapter::new("Chapter", "Content.".to_owned(), "chapter.md", vec![]);
+        let key = || {
+            ChapterCache::new(root.join("cache"), &cache_inputs(&ctx, &config).unwrap())
+                .key(&chapter, None)
+        };
+
+        fs::write(root.join("block.hbs"), "{{content}}").unwrap();
+        let before = key();
+        assert_eq!(before, key());
+        fs::write(root.join("block.hbs"), "<div>{{content}}</div>").unwrap();
+        let after = key();
+        fs::remove_dir_all(&root).unwrap();
+        assert_ne!(before, after);
+    }
+
+    #[test]
+    fn run_drop_by_directive() {
+        let content = r#"
+```admonish note
+Speaker notes
+```
+```admonish tip
+Keep this
+```
+"#;
+        let expected_content = r#"
+
+
+Keep this
+
+"#;
+        let ctx = mock_context(
+            &json!({
+                "assets_version": "3.1.0",
+                "renderer": {
+                    "slides": {
+                        "render_mode": "strip",
+                        "directive": {
+                            "note": "drop",
+                        },
+                    },
+                },
+            }),
+            "slides",
+        );
+        let book = mock_book(content);
+        let expected_book = mock_book(expected_content);
+
+        assert_eq!(Admonish.run(&ctx, book).unwrap(), expected_book)
+    }
+
+    #[test]
+    fn run_html_absolute_anchor_links() {
+        let content = r#"
+```admonish title="Title"
+Content
+```
+"#;
+        let expected_content = r##"
+
+<div id="admonition-title" class="admonition admonish-note">
+<div class="admonition-title">
+
+Title
+
+<a class="admonition-anchor-link" href="/docs/chapter_1.html#admonition-title"></a>
+</div>
+<div>
+
+Content
+
+</div>
+</div>
+"##;
+
+        let mut ctx = mock_context(
+            &json!({
+                "assets_version": "3.1.0",
+                "anchor_links": "absolute",
+            }),
+            "html",
+        );
+        ctx.config.set("output.html.site-url", "/docs").unwrap();
+        let book = mock_book(content);
+        let expected_book = mock_book(expected_content);
+
+        assert_eq!(Admonish.run(&ctx, book).unwrap(), expected_book)
+    }
+
+    #[test]
+    fn run_html_part_defaults() {
+        let content = r#"
+```admonish
+Content
+```
+"#;
+        let expected_content = |title: &str, id: &str| {
+            format!(
+                r##"
+
+<div id="admonition-{id}" class="admonition admonish-note">
+<div class="admonition-title">
+
+{title}
+
+<a class="admonition-anchor-link" href="#admonition-{id}"></a>
+</div>
+<div>
+
+Content
+
+</div>
+</div>
+"##
+            )
+        };
+        let book = |first: &str, second: &str| -> Book {
+            serde_json::from_value(json!({
+                "sections": [
+                    {
+                        "Chapter": {
+                            "name": "Chapter 1",
+                            "content": first,
+                            "number": [1],
+                            "sub_items": [],
+                            "path": "chapter_1.md",
+                            "source_path": "chapter_1.md",
+                            "parent_names": []
+                        }
+                    },
+                    { "PartTitle": "Appendix" },
+                    {
+                        "Chapter": {
+                            "name": "Glossary",
+                            "content": second,
+                            "number": [2],
+                            "sub_items": [],
+                            "path": "glossary.md",
+                            "source_path": "glossary.md",
+                            "parent_names": []
+                        }
+                    }
+                ],
+                "__non_exhaustive": null
+            }))
+            .unwrap()
+        };
+
+        let ctx = mock_context(
+            &json!({
+                "assets_version": "3.1.0",
+                "part": [
+                    {
+                        "title": "Appendix",
+                        "default": { "title": "Aside" },
+                    },
+                ],
+            }),
+            "html",
+        );
+
+        assert_eq!(
+            Admonish.run(&ctx, book(content, content)).unwrap(),
+            book(
+                &expected_content("Note", "note"),
+                &expected_content("Aside", "aside")
+            )
+        )
+    }
 }
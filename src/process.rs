@@ -0,0 +1,198 @@
+//! Rendering admonitions in markdown outside of an mdbook build.
+
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+use crate::{
+    book_config::{self, admonish_config_from_table},
+    index::AdmonitionIndex,
+    markdown::{preprocess_assigned, Assigned},
+    render::HtmlOptions,
+    types::RenderModes,
+    warnings::Warnings,
+};
+
+/// Configuration for [`process`], built with [`Config::builder`].
+///
+/// ```
+/// let config = mdbook_admonish::Config::builder()
+///     .toml(r#"default = { collapsible = true }"#)
+///     .build()?;
+/// let html = mdbook_admonish::process("```admonish\nContent.\n```\n", &config)?;
+/// assert!(html.contains("<details"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Config {
+    config: book_config::Config,
+    render_modes: RenderModes,
+    html_options: HtmlOptions,
+}
+
+impl Config {
+    /// Start building a configuration, with the same defaults as an empty
+    /// `[preprocessor.admonish]` table in `book.toml`.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+}
+
+/// Builds a [`Config`].
+#[derive(Debug, Clone)]
+pub struct ConfigBuilder {
+    toml: String,
+    book_root: PathBuf,
+    renderer: String,
+    language: Option<String>,
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        Self {
+            toml: String::new(),
+            book_root: PathBuf::from("."),
+            renderer: "html".to_owned(),
+            language: None,
+        }
+    }
+}
+
+impl ConfigBuilder {
+    /// Options as they would be written in the `[preprocessor.admonish]`
+    /// table of `book.toml`, without the table header.
+    pub fn toml(mut self, toml: impl Into<String>) -> Self {
+        self.toml = toml.into();
+        self
+    }
+
+    /// Directory that paths in the options are relative to, such as
+    /// templates. Defaults to the current directory.
+    pub fn book_root(mut self, book_root: impl Into<PathBuf>) -> Self {
+        self.book_root = book_root.into();
+        self
+    }
+
+    /// Name of the renderer to render for, which selects the render mode.
+    /// Defaults to `html`.
+    pub fn renderer(mut self, renderer: impl Into<String>) -> Self {
+        self.renderer = renderer.into();
+        self
+    }
+
+    /// Language of the content, such as `fr`, for the default directive
+    /// titles.
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Check the options, and load any files they name.
+    pub fn build(self) -> Result<Config> {
+        let table = toml::from_str(&self.toml).context("Invalid mdbook-admonish configuration")?;
+        let config = admonish_config_from_table(&self.book_root, table, self.language.as_deref())?;
+        config.check_defaults()?;
+        config.check_parts()?;
+        let render_modes = config.render_modes(&self.renderer)?;
+        let mut html_options = HtmlOptions::from_config(&self.book_root, None, &config)?;
+        // Backlinks need the references of a whole book
+        html_options.backlinks = false;
+        Ok(Config {
+            config,
+            render_modes,
+            html_options,
+        })
+    }
+}
+
+#[cfg(feature = "mdbook")]
+impl ConfigBuilder {
+    /// Start from the configuration of the book at `book_root`, as written
+    /// in the `[preprocessor.admonish]` table of its `book.toml`.
+    pub fn from_book(book_root: impl Into<PathBuf>) -> Result<Self> {
+        let book_root = book_root.into();
+        let book_config = mdbook::Config::from_disk(book_root.join("book.toml"))?;
+        let table = book_config
+            .get_preprocessor("admonish")
+            .context("No configuration for mdbook-admonish in book.toml")?;
+        Ok(Self {
+            toml: toml_mdbook::to_string(table)?,
+            book_root,
+            renderer: "html".to_owned(),
+            language: book_config.book.language.clone(),
+        })
+    }
+}
+
+/// Render the admonitions in `content`, a markdown document, as the
+/// preprocessor would render a chapter.
+///
+/// Any warnings are logged, and are an error if warnings are denied. References
+/// to blocks with `{{#admonish-ref id}}` can only name blocks in `content`.
+pub fn process(content: &str, config: &Config) -> Result<String> {
+    process_assigned(content, config, &mut Assigned::default())
+}
+
+/// As [`process`], following on from the ids and numbers in `assigned`.
+pub(crate) fn process_assigned(
+    content: &str,
+    config: &Config,
+    assigned: &mut Assigned,
+) -> Result<String> {
+    let Config {
+        config,
+        render_modes,
+        html_options,
+    } = config;
+    let mut warnings = Warnings::from_config(&config.warnings, &config.default.directives)?;
+    let mut index = AdmonitionIndex::default();
+    let result = preprocess_assigned(
+        content,
+        config.on_failure,
+        &config.default,
+        render_modes,
+        html_options,
+        None,
+        &mut warnings,
+        &mut index,
+        assigned,
+    );
+    warnings.log();
+    warnings.ensure_none_denied()?;
+    index.expand_references(&result?, None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn process_with_options() {
+        let config = Config::builder()
+            .toml(
+                r#"
+                [renderer.markdown]
+                render_mode = "strip"
+                "#,
+            )
+            .renderer("markdown")
+            .build()
+            .unwrap();
+        assert_eq!(
+            "\nContent.\n\n",
+            process("```admonish\nContent.\n```\n", &config).unwrap()
+        );
+    }
+
+    #[test]
+    fn build_invalid_options() {
+        let error = Config::builder()
+            .toml("on_failure = 3")
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            "Invalid mdbook-admonish configuration in book.toml",
+            error.to_string()
+        );
+    }
+}
@@ -1,13 +1,290 @@
-use mdbook::utils::unique_id_from_content;
+use anyhow::{anyhow, bail, Context, Result};
+use once_cell::sync::Lazy;
+use pulldown_cmark::{
+    escape::{escape_href, escape_html},
+    html::push_html,
+    Event, Parser, Tag,
+};
+use regex::Regex;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
 
-pub use crate::preprocessor::Admonish;
-use crate::{resolve::AdmonitionMeta, types::Directive};
+use crate::{
+    book_config::{
+        AnchorLinks, Config, DirectiveConfig, IconMode, IdScheme, LinkPolicy, Markup,
+        NumberingConfig, Preset, SlugStrategy, StripTitle, Syntax,
+    },
+    external::ExternalRenderer,
+    resolve::AdmonitionMeta,
+    slug::{hash_hex, slug_source, unique_id_from_content},
+    template::{self, TemplateData, Templates},
+    types::{Align, Attach, Directive, Float, Print, Size},
+};
+
+/// Book wide options that affect how admonitions are rendered to HTML.
+#[derive(Debug, Default)]
+pub(crate) struct HtmlOptions {
+    pub(crate) templates: Templates,
+    pub(crate) directive: HashMap<Directive, DirectiveConfig>,
+    /// Custom properties for directives with a custom color or icon.
+    pub(crate) directive_style: HashMap<Directive, String>,
+    pub(crate) id_scheme: IdScheme,
+    pub(crate) slug: SlugStrategy,
+    pub(crate) links: LinkPolicy,
+    pub(crate) anchor_links: AnchorLinks,
+    pub(crate) anchor_link_content: Option<String>,
+    pub(crate) copy_anchor_button: bool,
+    pub(crate) icon_mode: IconMode,
+    pub(crate) preset: Preset,
+    pub(crate) markup: Markup,
+    pub(crate) landmark_min_length: Option<usize>,
+    pub(crate) semantic_html: bool,
+    pub(crate) numbering: Numbering,
+    /// Prefix numbers with the chapter number, such as "Example 3.2".
+    pub(crate) chapter_prefix: bool,
+    pub(crate) external_renderer: Option<ExternalRenderer>,
+    pub(crate) microdata: bool,
+    /// Mark where links to referencing chapters go, beneath each block.
+    pub(crate) backlinks: bool,
+    pub(crate) syntax: HashSet<Syntax>,
+    /// The `output.html.site-url` of the book, ending with a `/`.
+    pub(crate) site_url: String,
+    /// The `book.src` directory, relative to the book root.
+    pub(crate) src_dir: PathBuf,
+    pub(crate) strip_title: StripTitle,
+}
+
+impl HtmlOptions {
+    /// Load options from configuration.
+    ///
+    /// Paths are relative to the book root directory.
+    pub(crate) fn from_config(
+        root: &Path,
+        site_url: Option<&str>,
+        config: &Config,
+    ) -> Result<Self> {
+        let mut site_url = site_url.unwrap_or("/").to_owned();
+        if !site_url.ends_with('/') {
+            site_url.push('/');
+        }
+        let directives = config.directives()?;
+        let mut directive_styles = HashMap::new();
+        for (directive, config) in &directives {
+            let style = directive_style(root, directive, config)?;
+            if !style.is_empty() {
+                directive_styles.insert(directive.clone(), style);
+            }
+        }
+        Ok(Self {
+            templates: Templates::from_config(root, config)?,
+            directive_style: directive_styles,
+            directive: directives
+                .into_iter()
+                .map(|(directive, config)| (directive, config.clone()))
+                .collect(),
+            id_scheme: config.id_scheme,
+            slug: config.slug,
+            links: config.links.clone(),
+            anchor_links: config.anchor_links,
+            anchor_link_content: config.anchor_link_content.clone(),
+            copy_anchor_button: config.copy_anchor_button,
+            icon_mode: config.icon_mode,
+            preset: config.preset,
+            markup: config.markup,
+            landmark_min_length: config.landmark_min_length,
+            semantic_html: config.semantic_html,
+            numbering: Numbering::from_config(config)?,
+            chapter_prefix: config.numbering.chapter_prefix,
+            external_renderer: config
+                .external_renderer
+                .as_deref()
+                .map(|command| ExternalRenderer::new(root, command))
+                .transpose()?,
+            microdata: config.microdata,
+            backlinks: config.backlinks.enabled,
+            syntax: config.syntax.iter().copied().collect(),
+            site_url,
+            src_dir: PathBuf::new(),
+            strip_title: config.strip_title,
+        })
+    }
+
+    /// Where `line` of the chapter at `chapter_path` is in the book sources,
+    /// such as `src/intro.md:12`, for error messages.
+    pub(crate) fn source_location(&self, chapter_path: Option<&Path>, line: usize) -> String {
+        match chapter_path {
+            Some(chapter_path) => {
+                format!("{}:{line}", self.src_dir.join(chapter_path).display())
+            }
+            None => format!("line {line}"),
+        }
+    }
+
+    /// The url to prefix anchor links with, for the chapter at `chapter_path`.
+    ///
+    /// `chapter_path` is the path of the chapter source, relative to the book
+    /// source directory.
+    pub(crate) fn anchor_base(&self, chapter_path: Option<&Path>) -> String {
+        let chapter_path = match (self.anchor_links, chapter_path) {
+            (AnchorLinks::Absolute, Some(chapter_path)) => chapter_path,
+            _ => return String::new(),
+        };
+        // mdbook renders README chapters as the index page of the directory
+        let chapter_path = if chapter_path.file_stem() == Some("README".as_ref()) {
+            chapter_path.with_file_name("index.html")
+        } else {
+            chapter_path.with_extension("html")
+        };
+        let page = chapter_path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        escape_url(&format!("{}{page}", self.site_url))
+    }
+}
+
+/// Custom properties to style blocks with the color and icon of a directive.
+///
+/// Builtin directives keep their own color and icon, where not configured,
+/// including any color set with the `--admonish-<directive>-color` property.
+fn directive_style(root: &Path, directive: &Directive, config: &DirectiveConfig) -> Result<String> {
+    if !config.is_styled() {
+        return Ok(String::new());
+    }
+    let mut style = Vec::new();
+    let builtin = !matches!(directive, Directive::Custom(_));
+    match &config.color {
+        Some(color) => style.push(format!("--admonish-color: {color}")),
+        None if builtin => style.push(format!(
+            "--admonish-color: var(--{}-color, #{})",
+            directive.classname(),
+            directive.color_hex()
+        )),
+        None => {}
+    }
+    match &config.icon {
+        Some(icon) => style.push(format!("--admonish-icon: {}", icon_url(root, icon)?)),
+        None if builtin => style.push(format!(
+            "--admonish-icon: var(--md-admonition-icon--{})",
+            directive.classname()
+        )),
+        None => {}
+    }
+    if let Some(background) = &config.background {
+        style.push(format!("--admonish-background: {background}"));
+    }
+    if let Some(icon_color) = &config.icon_color {
+        style.push(format!("--admonish-icon-color: {icon_color}"));
+    }
+    Ok(style.join("; "))
+}
+
+/// A CSS `url()` of the SVG icon at `icon`, relative to the book root.
+pub(crate) fn icon_url(root: &Path, icon: &Path) -> Result<String> {
+    let path = root.join(icon);
+    let svg = fs::read_to_string(&path)
+        .with_context(|| format!("Can't read directive icon '{}'", path.display()))?;
+    let Some(start) = svg.find("<svg") else {
+        bail!("Directive icon '{}' is not an SVG image", path.display());
+    };
+    // Images loaded from a data URL are parsed as XML, so need the SVG
+    // namespace, which icons copied from HTML often leave out
+    let tag_end = svg[start..].find('>').map_or(svg.len(), |end| start + end);
+    let svg = match svg[start..tag_end].contains("xmlns=") {
+        true => svg,
+        false => format!(
+            "{}<svg xmlns=\"http://www.w3.org/2000/svg\"{}",
+            &svg[..start],
+            &svg[start + "<svg".len()..]
+        ),
+    };
+    Ok(format!(
+        "url('data:image/svg+xml;charset=utf-8,{}')",
+        encode_data_url(&svg)
+    ))
+}
+
+/// Percent encode the characters that are unsafe in a data URL, inside a
+/// quoted CSS string, inside an HTML attribute.
+fn encode_data_url(data: &str) -> String {
+    let mut encoded = String::with_capacity(data.len());
+    for c in data.chars() {
+        match c {
+            '%' => encoded.push_str("%25"),
+            '#' => encoded.push_str("%23"),
+            '"' => encoded.push_str("%22"),
+            '\'' => encoded.push_str("%27"),
+            '<' => encoded.push_str("%3C"),
+            '>' => encoded.push_str("%3E"),
+            '&' => encoded.push_str("%26"),
+            '\\' => encoded.push_str("%5C"),
+            '\n' | '\r' | '\t' => encoded.push(' '),
+            c => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+/// Which admonitions are numbered.
+#[derive(Debug, Default)]
+pub(crate) enum Numbering {
+    #[default]
+    Disabled,
+    All,
+    Directives(HashSet<Directive>),
+}
+
+impl Numbering {
+    fn from_config(config: &Config) -> Result<Self> {
+        let NumberingConfig {
+            enabled,
+            directives,
+            chapter_prefix: _,
+        } = &config.numbering;
+        if !enabled {
+            return Ok(Self::Disabled);
+        }
+        if directives.is_empty() {
+            return Ok(Self::All);
+        }
+        let custom_directives = config.custom_directives()?;
+        directives
+            .iter()
+            .map(|name| {
+                custom_directives
+                    .get(name)
+                    .ok_or_else(|| anyhow!("Unknown directive '{name}' in numbering configuration"))
+            })
+            .collect::<Result<_>>()
+            .map(Self::Directives)
+    }
+
+    /// Whether admonitions of `directive` are numbered.
+    pub(crate) fn applies(&self, directive: &Directive) -> bool {
+        match self {
+            Self::Disabled => false,
+            Self::All => true,
+            Self::Directives(directives) => directives.contains(directive),
+        }
+    }
+}
 
 impl Directive {
-    fn classname(&self) -> &'static str {
+    /// The most specific schema.org type describing blocks of this directive.
+    fn schema_type(&self) -> &'static str {
         match self {
+            Directive::Question => "Question",
+            Directive::Quote => "Quotation",
+            _ => "WebPageElement",
+        }
+    }
+
+    pub(crate) fn classname(&self) -> Cow<'static, str> {
+        Cow::Borrowed(match self {
+            Directive::Custom(name) => return Cow::Owned(format!("admonish-{name}")),
             Directive::Note => "admonish-note",
             Directive::Abstract => "admonish-abstract",
             Directive::Info => "admonish-info",
@@ -20,7 +297,7 @@ impl Directive {
             Directive::Bug => "admonish-bug",
             Directive::Example => "admonish-example",
             Directive::Quote => "admonish-quote",
-        }
+        })
     }
 }
 
@@ -28,9 +305,31 @@ impl Directive {
 pub(crate) struct Admonition<'a> {
     pub(crate) directive: Directive,
     pub(crate) title: String,
+    /// The default title of the directive, used to label and number blocks.
+    pub(crate) directive_title: String,
+    /// Anchor id given on the block, used in place of a generated id.
+    pub(crate) id: Option<String>,
     pub(crate) content: Cow<'a, str>,
     pub(crate) additional_classnames: Vec<String>,
     pub(crate) collapsible: bool,
+    pub(crate) open: bool,
+    /// Whether the title has an icon.
+    pub(crate) icon: bool,
+    pub(crate) body_classnames: Vec<String>,
+    pub(crate) url: Option<String>,
+    pub(crate) attach: Option<Attach>,
+    pub(crate) size: Size,
+    pub(crate) align: Option<Align>,
+    pub(crate) float: Option<Float>,
+    /// Sidenote placement set on the block, if any.
+    pub(crate) sidenote: Option<bool>,
+    /// Whether the block takes part in automatic numbering.
+    pub(crate) numbered: bool,
+    pub(crate) print: Option<Print>,
+    /// Topics of the block, rendered as a `data-tags` attribute.
+    pub(crate) tags: Vec<String>,
+    /// Position of the block among numbered blocks of the same directive.
+    pub(crate) number: Option<String>,
     pub(crate) indent: usize,
 }
 
@@ -39,72 +338,465 @@ impl<'a> Admonition<'a> {
         let AdmonitionMeta {
             directive,
             title,
+            directive_title,
+            id,
             additional_classnames,
             collapsible,
+            open,
+            icon,
+            body_classnames,
+            url,
+            attach,
+            size,
+            align,
+            float,
+            sidenote,
+            numbered,
+            print,
+            tags,
         } = info;
         Self {
             directive,
             title,
+            directive_title,
+            id,
             content: Cow::Borrowed(content),
             additional_classnames,
             collapsible,
+            open,
+            icon,
+            body_classnames,
+            url,
+            attach,
+            size,
+            align,
+            float,
+            sidenote,
+            numbered,
+            print,
+            tags,
+            number: None,
             indent,
         }
     }
 
-    pub(crate) fn html_with_unique_ids(&self, id_counter: &mut HashMap<String, usize>) -> String {
-        let anchor_id = unique_id_from_content(
-            if !self.title.is_empty() {
-                &self.title
-            } else {
-                ANCHOR_ID_DEFAULT
-            },
-            id_counter,
-        );
-        self.html(&anchor_id)
+    /// The anchor id of this block, unique among the ids in `id_counter`.
+    pub(crate) fn anchor_id(
+        &self,
+        id_counter: &mut HashMap<String, usize>,
+        options: &HtmlOptions,
+    ) -> String {
+        match (&self.id, options.id_scheme) {
+            // Explicit ids are kept as given, so links survive title changes
+            (Some(id), _) => id.clone(),
+            (None, IdScheme::Prefixed) => {
+                let anchor_id = unique_id_from_content(
+                    &if !self.title.is_empty() {
+                        slug_source(&self.title, options.slug)
+                    } else {
+                        Cow::Borrowed(ANCHOR_ID_DEFAULT)
+                    },
+                    id_counter,
+                );
+                format!("{ANCHOR_ID_PREFIX}-{anchor_id}")
+            }
+            (None, IdScheme::Content) => {
+                let source = format!(
+                    "{}\n{}\n{}",
+                    self.directive.name(),
+                    self.title,
+                    self.html_content()
+                );
+                // Only identical blocks are deduplicated with a counter
+                let anchor_id = unique_id_from_content(&hash_hex(source.as_bytes()), id_counter);
+                format!("{ANCHOR_ID_PREFIX}-{anchor_id}")
+            }
+            (None, IdScheme::Heading) => {
+                let content = if !self.title.is_empty() {
+                    slug_source(&inline_html(&self.title), options.slug).into_owned()
+                } else {
+                    ANCHOR_ID_PREFIX.to_owned()
+                };
+                unique_id_from_content(&content, id_counter)
+            }
+        }
+    }
+
+    /// The title of this block, numbered and formatted, for references to it.
+    pub(crate) fn reference_title(&self, options: &HtmlOptions) -> String {
+        self.display_title(options.directive.get(&self.directive))
+            .into_owned()
+    }
+
+    pub(crate) fn html_with_id(
+        &self,
+        id: &str,
+        options: &HtmlOptions,
+        anchor_base: &str,
+    ) -> Result<String> {
+        let anchor = format!("{anchor_base}#{id}");
+        let directive_config = options.directive.get(&self.directive);
+        let body_classes = self.body_classes(directive_config);
+        let title = self.display_title(directive_config);
+
+        let markup = options.markup;
+        let classes = self.classes(directive_config, options);
+        let url = self.url.as_deref().map(escape_url);
+        let style = self.style(options);
+        let data = TemplateData {
+            id,
+            anchor: &anchor,
+            directive: self.directive.name(),
+            classes: &markup.attribute(&classes),
+            body_classes: &markup.attribute(&body_classes),
+            style: &style,
+            title: &markup.inline(&title),
+            url: url.as_deref(),
+            content: self.html_content(),
+            collapsible: self.collapsible,
+        };
+        let html = match (
+            &options.external_renderer,
+            options.templates.get(&self.directive),
+        ) {
+            (Some(renderer), _) => renderer.render(&data, self.indent)?,
+            (None, Some(source)) => template::render(source, self.indent, &data)?,
+            (None, None) => self.html(
+                id,
+                &anchor,
+                &title,
+                &body_classes,
+                directive_config,
+                options,
+            ),
+        };
+
+        let (before, after) = options.templates.wrapper(&self.directive);
+        let before = match before {
+            Some(source) => template::render(source, self.indent, &data)?,
+            None => String::new(),
+        };
+        let after = match after {
+            Some(source) => template::render(source, self.indent, &data)?,
+            None => String::new(),
+        };
+        Ok(format!("{before}{html}{after}"))
     }
 
-    fn html(&self, anchor_id: &str) -> String {
-        let mut additional_class = Cow::Borrowed(self.directive.classname());
-        let title = &self.title;
-        let content = &self.content;
+    fn classes(&self, directive_config: Option<&DirectiveConfig>, options: &HtmlOptions) -> String {
+        let mut classes = format!("admonition {}", self.directive.classname());
+        if let Some(classname) = options.preset.classname() {
+            classes.push(' ');
+            classes.push_str(classname);
+        }
+        if let Some(attach) = self.attach {
+            classes.push(' ');
+            classes.push_str(attach.classname());
+        }
+        if let Some(classname) = self.print(directive_config).classname() {
+            classes.push(' ');
+            classes.push_str(classname);
+        }
+        if self.size != Size::default() {
+            classes.push_str(" admonish-sized");
+        }
+        if let Some(align) = self.align {
+            classes.push(' ');
+            classes.push_str(align.classname());
+        }
+        if let Some(float) = self.float {
+            classes.push(' ');
+            classes.push_str(float.classname());
+        }
+        if self.sidenote(directive_config) {
+            classes.push_str(" admonish-sidenote");
+        }
+        if !self.icon {
+            classes.push_str(" admonish-no-icon");
+        }
+        // Styled by custom properties, in place of builtin directive styles
+        if matches!(self.directive, Directive::Custom(_))
+            || directive_config.map_or(false, DirectiveConfig::is_styled)
+        {
+            classes.push_str(" admonish-custom");
+        }
+        for additional_classname in &self.additional_classnames {
+            classes.push(' ');
+            classes.push_str(additional_classname);
+        }
+        classes
+    }
+
+    /// The emoji to render as the icon, in `emoji` icon mode, unless the block
+    /// has no icon.
+    fn emoji<'c>(
+        &self,
+        directive_config: Option<&'c DirectiveConfig>,
+        options: &HtmlOptions,
+    ) -> Option<&'c str> {
+        if !self.icon {
+            return None;
+        }
+        let mode = directive_config
+            .and_then(|config| config.icon_mode)
+            .unwrap_or(options.icon_mode);
+        match mode {
+            IconMode::Css => None,
+            IconMode::Emoji => Some(
+                directive_config
+                    .and_then(|config| config.emoji.as_deref())
+                    .unwrap_or_else(|| self.directive.emoji()),
+            ),
+        }
+    }
+
+    /// Inline style, for the size of the block and the directive style.
+    fn style(&self, options: &HtmlOptions) -> String {
+        let size = self.size.style();
+        match options.directive_style.get(&self.directive) {
+            Some(style) if !size.is_empty() => format!("{style}; {size}"),
+            Some(style) => style.clone(),
+            None => size,
+        }
+    }
+
+    /// Print visibility, falling back to the directive default.
+    fn print(&self, directive_config: Option<&DirectiveConfig>) -> Print {
+        self.print
+            .or_else(|| {
+                // Conflicting options are rejected when loading configuration
+                directive_config.and_then(|config| {
+                    Print::from_options(config.print, config.print_only)
+                        .ok()
+                        .flatten()
+                })
+            })
+            .unwrap_or_default()
+    }
+
+    /// Whether the block is placed in the page margin, falling back to the
+    /// directive default.
+    fn sidenote(&self, directive_config: Option<&DirectiveConfig>) -> bool {
+        self.sidenote
+            .or_else(|| directive_config.and_then(|config| config.sidenote))
+            .unwrap_or_default()
+    }
+
+    /// Classnames for the body, falling back to the directive default.
+    fn body_classes(&self, directive_config: Option<&DirectiveConfig>) -> String {
+        if !self.body_classnames.is_empty() {
+            return self.body_classnames.join(" ");
+        }
+        directive_config
+            .and_then(|config| config.body_class.as_deref())
+            .unwrap_or_default()
+            .split(' ')
+            .filter(|classname| !classname.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The title to display, numbered and formatted as configured for the
+    /// directive.
+    ///
+    /// An empty title is left empty, so that the title bar is still removed.
+    fn display_title(&self, directive_config: Option<&DirectiveConfig>) -> Cow<'_, str> {
+        if self.title.is_empty() {
+            return Cow::Borrowed(&self.title);
+        }
+        let directive_title = &self.directive_title;
+        let title = match &self.number {
+            // Avoid repeating the default title, such as "Example 1: Example"
+            Some(number) if &self.title == directive_title => {
+                Cow::Owned(format!("{directive_title} {number}"))
+            }
+            Some(number) => Cow::Owned(format!("{directive_title} {number}: {}", self.title)),
+            None => Cow::Borrowed(self.title.as_str()),
+        };
+        match directive_config.and_then(|config| config.title_format.as_deref()) {
+            Some(format) => Cow::Owned(
+                format
+                    .replace("{directive_title}", directive_title)
+                    .replace("{title}", &title),
+            ),
+            None => title,
+        }
+    }
+
+    fn html(
+        &self,
+        id: &str,
+        anchor: &str,
+        title: &str,
+        body_classes: &str,
+        directive_config: Option<&DirectiveConfig>,
+        options: &HtmlOptions,
+    ) -> String {
+        let HtmlOptions { links, markup, .. } = options;
+        let mut classes = self.classes(directive_config, options);
+        let emoji = self.emoji(directive_config, options);
+        if emoji.is_some() {
+            classes.push_str(" admonish-emoji");
+        }
+        let classes = markup.attribute(&classes);
+        let body_classes = markup.attribute(body_classes);
+        let title = markup.inline(title);
+        let title = match &self.url {
+            Some(url) => Cow::Owned(format!(
+                r#"<a class="admonition-title-link" href="{url}"{attributes}>{title}</a>"#,
+                url = escape_url(url),
+                attributes = links.attributes(url, &options.site_url),
+            )),
+            None => title,
+        };
+        // Hidden from screen readers, as the title already says what the block is
+        let emoji = match emoji {
+            Some(emoji) => Cow::Owned(format!(
+                r#"<span class="admonition-emoji" aria-hidden="true">{}</span> "#,
+                markup.attribute(emoji)
+            )),
+            None => Cow::Borrowed(""),
+        };
+        let (title_before, title_after) = directive_config
+            .map(|DirectiveConfig { title_bar, .. }| {
+                (
+                    markup.fragment(title_bar.before.as_deref().unwrap_or_default()),
+                    markup.fragment(title_bar.after.as_deref().unwrap_or_default()),
+                )
+            })
+            .unwrap_or_default();
+        let content = self.html_content();
         let indent = " ".repeat(self.indent);
 
         let title_block = if self.collapsible { "summary" } else { "div" };
+        let anchor_link = match options.anchor_links {
+            AnchorLinks::None => Cow::Borrowed(""),
+            AnchorLinks::Fragment | AnchorLinks::Absolute => Cow::Owned(format!(
+                r#"{indent}<a class="admonition-anchor-link" href="{anchor}"{attributes}>{content}</a>
+"#,
+                attributes = links.attributes(anchor, &options.site_url),
+                content =
+                    markup.fragment(options.anchor_link_content.as_deref().unwrap_or_default()),
+            )),
+        };
+
+        // Hidden until the javascript asset enables it, as it does nothing
+        // without it
+        let copy_button = if options.copy_anchor_button {
+            let hidden = match markup {
+                Markup::Html => " hidden",
+                Markup::Xhtml => r#" hidden="hidden""#,
+            };
+            Cow::Owned(format!(
+                r#"{indent}<button type="button" class="admonition-copy-anchor" data-anchor="{anchor}" aria-label="Copy link" title="Copy link"{hidden}></button>
+"#
+            ))
+        } else {
+            Cow::Borrowed("")
+        };
+
+        // Long blocks are landmarks, so screen reader users can navigate
+        // between them. `details` elements do not permit a role.
+        let landmark = !self.collapsible
+            && options.landmark_min_length.map_or(false, |min_length| {
+                self.content.chars().count() >= min_length
+            });
+        // Other blocks are complementary notes, in semantic HTML
+        let role = match (landmark, options.semantic_html && !self.collapsible) {
+            (true, _) => Some("region"),
+            (false, true) => Some("note"),
+            (false, false) => None,
+        };
+        let (role_attributes, title_id_attribute) = match (role, title.is_empty()) {
+            (None, _) => (Cow::Borrowed(""), Cow::Borrowed("")),
+            (Some(role), false) => (
+                Cow::Owned(format!(r#" role="{role}" aria-labelledby="{id}-title""#)),
+                Cow::Owned(format!(r#" id="{id}-title""#)),
+            ),
+            (Some(role), true) => (
+                Cow::Owned(format!(
+                    r#" role="{role}" aria-label="{}""#,
+                    markup.attribute(&self.directive_title)
+                )),
+                Cow::Borrowed(""),
+            ),
+        };
+
+        // Microdata lets search engines and aggregators identify the block,
+        // its title and its text
+        let (microdata_attributes, title, body_microdata_attribute) = if options.microdata {
+            let itemscope = match markup {
+                Markup::Html => "itemscope",
+                Markup::Xhtml => r#"itemscope="itemscope""#,
+            };
+            (
+                Cow::Owned(format!(
+                    r#" {itemscope} itemtype="https://schema.org/{}""#,
+                    self.directive.schema_type()
+                )),
+                // Blocks without a title have no title bar to annotate
+                if !title.is_empty() {
+                    Cow::Owned(format!(r#"<span itemprop="name">{title}</span>"#))
+                } else {
+                    title
+                },
+                r#" itemprop="text""#,
+            )
+        } else {
+            (Cow::Borrowed(""), title, "")
+        };
 
         let title_html = if !title.is_empty() {
             Cow::Owned(format!(
-                r##"{indent}<{title_block} class="admonition-title">
+                r##"{indent}<{title_block}{title_id_attribute} class="admonition-title">
 {indent}
-{indent}{title}
+{indent}{emoji}{title_before}{title}{title_after}
 {indent}
-{indent}<a class="admonition-anchor-link" href="#{ANCHOR_ID_PREFIX}-{anchor_id}"></a>
-{indent}</{title_block}>
+{anchor_link}{copy_button}{indent}</{title_block}>
 "##
             ))
         } else {
             Cow::Borrowed("")
         };
 
-        if !self.additional_classnames.is_empty() {
-            let mut buffer = additional_class.into_owned();
-            for additional_classname in &self.additional_classnames {
-                buffer.push(' ');
-                buffer.push_str(additional_classname);
-            }
-
-            additional_class = Cow::Owned(buffer);
-        }
-
-        let admonition_block = if self.collapsible { "details" } else { "div" };
+        let admonition_block = match (self.collapsible, options.semantic_html, landmark) {
+            (true, _, _) => "details",
+            (false, false, _) => "div",
+            (false, true, true) => "section",
+            (false, true, false) => "aside",
+        };
+        let open_attribute = match (self.collapsible && self.open, markup) {
+            (false, _) => "",
+            (true, Markup::Html) => " open",
+            (true, Markup::Xhtml) => r#" open="open""#,
+        };
+        let style = self.style(options);
+        let style_attribute = if !style.is_empty() {
+            Cow::Owned(format!(r#" style="{style}""#))
+        } else {
+            Cow::Borrowed("")
+        };
+        let tags_attribute = if !self.tags.is_empty() {
+            Cow::Owned(format!(
+                r#" data-tags="{}""#,
+                markup.attribute(&self.tags.join(" "))
+            ))
+        } else {
+            Cow::Borrowed("")
+        };
+        let body_class_attribute = if !body_classes.is_empty() {
+            Cow::Owned(format!(r#" class="{body_classes}""#))
+        } else {
+            Cow::Borrowed("")
+        };
         // Notes on the HTML template:
         // - the additional whitespace around the content are deliberate
         //   In line with the commonmark spec, this allows the inner content to be
         //   rendered as markdown paragraphs.
         format!(
             r#"
-{indent}<{admonition_block} id="{ANCHOR_ID_PREFIX}-{anchor_id}" class="admonition {additional_class}">
-{title_html}{indent}<div>
+{indent}<{admonition_block} id="{id}" class="{classes}"{open_attribute}{style_attribute}{tags_attribute}{role_attributes}{microdata_attributes}>
+{title_html}{indent}<div{body_class_attribute}{body_microdata_attribute}>
 {indent}
 {indent}{content}
 {indent}
@@ -113,12 +805,325 @@ impl<'a> Admonition<'a> {
         )
     }
 
-    /// Strips all admonish syntax, leaving the plain content of the block.
-    pub(crate) fn strip(&self) -> String {
+    /// The content of the block, for placing after an indent in HTML output.
+    ///
+    /// The first line of content keeps its indent from the source, so remove it
+    /// to avoid indenting twice. Otherwise, a nested code block can become an
+    /// indented code block, and lose its language and attributes (such as the
+    /// Rust playground integration provided by mdbook).
+    fn html_content(&self) -> &str {
+        let content: &str = &self.content;
+        let leading_spaces = content.len() - content.trim_start_matches(' ').len();
+        &content[leading_spaces.min(self.indent)..]
+    }
+
+    /// Strips all admonish syntax, leaving the plain content of the block,
+    /// and the title if configured.
+    pub(crate) fn strip(&self, strip_title: StripTitle) -> String {
         // Add in newlines to preserve line numbering for test output
         // These replace the code fences we stripped out
-        format!("\n{}\n", self.content)
+        match (strip_title, self.title.is_empty()) {
+            (StripTitle::None, _) | (_, true) => format!("\n{}\n", self.content),
+            // A heading needs no blank line after it, so line numbering is
+            // still preserved
+            (StripTitle::Heading, false) => format!("#### {}\n{}\n", self.title, self.content),
+            // The blank line after the title takes the line of the closing
+            // fence, rather than one after the content
+            (StripTitle::Bold, false) => {
+                let indent = " ".repeat(self.indent);
+                format!("**{}**\n{indent}\n{}", self.title, self.content)
+            }
+        }
+    }
+
+    /// Rewrite the block as a GitHub alert, such as `> [!WARNING]`.
+    ///
+    /// Alerts have no custom titles, so a title other than the default is
+    /// kept as a bold first line.
+    pub(crate) fn alert(&self) -> String {
+        let mut header = vec![format!("[!{}]", self.directive.alert_kind())];
+        if !self.title.is_empty() && self.title != self.directive_title {
+            header.push(format!("**{}**", self.title));
+            header.push(String::new());
+        }
+        self.quoted(&header)
+    }
+
+    /// Rewrite the block as a plain blockquote, with the title in bold.
+    pub(crate) fn blockquote(&self) -> String {
+        let mut header = Vec::new();
+        if !self.title.is_empty() {
+            header.push(format!("**{}**", self.title));
+            header.push(String::new());
+        }
+        self.quoted(&header)
+    }
+
+    /// Wrap the block in a `tcolorbox` environment, for LaTeX output.
+    ///
+    /// The environment is opened and closed in raw LaTeX blocks, as written
+    /// for Pandoc, so the content is still converted from markdown.
+    pub(crate) fn latex(&self, options: &HtmlOptions) -> String {
+        let indent = " ".repeat(self.indent);
+        let name = format!("admonish-{}", self.directive.name());
+        let color = self.color_hex(options);
+        let title = match self.title.is_empty() {
+            true => String::new(),
+            false => format!(", title={{{}}}", escape_latex(&self.title)),
+        };
+        format!(
+            r#"
+{indent}```{{=latex}}
+{indent}\definecolor{{{name}}}{{HTML}}{{{color}}}
+{indent}\begin{{tcolorbox}}[colframe={name}, colback={name}!5!white{title}]
+{indent}```
+{indent}
+{indent}{content}
+{indent}
+{indent}```{{=latex}}
+{indent}\end{{tcolorbox}}
+{indent}```
+"#,
+            content = self.html_content(),
+        )
+    }
+
+    /// Render the block for EPUB readers, without `details` elements or
+    /// stylesheets.
+    ///
+    /// Many readers ignore or restyle publisher CSS, so the directive color is
+    /// applied inline, and blocks are never collapsible.
+    pub(crate) fn epub(&self, id: &str, options: &HtmlOptions) -> String {
+        let indent = " ".repeat(self.indent);
+        let classes = format!("admonition {}", self.directive.classname());
+        let color = self.color_hex(options);
+        let background = tint(&color, 10);
+        let title = self.display_title(options.directive.get(&self.directive));
+        let title_html = if !title.is_empty() {
+            let title = Markup::Xhtml.inline(&title);
+            let title = match &self.url {
+                Some(url) => Cow::Owned(format!(r#"<a href="{}">{title}</a>"#, escape_url(url))),
+                None => title,
+            };
+            Cow::Owned(format!(
+                r#"{indent}<p class="admonition-title" style="margin: 0.6em 0; font-weight: bold;">{title}</p>
+"#
+            ))
+        } else {
+            Cow::Borrowed("")
+        };
+        format!(
+            r#"
+{indent}<div id="{id}" class="{classes}" style="margin: 1em 0; padding: 0.1em 0.8em; border-left: 0.25em solid #{color}; background-color: #{background};">
+{title_html}{indent}
+{indent}{content}
+{indent}
+{indent}</div>"#,
+            content = self.html_content(),
+        )
+    }
+
+    /// The directive color, as `RRGGBB` hex, preferring a configured color.
+    fn color_hex(&self, options: &HtmlOptions) -> String {
+        options
+            .directive
+            .get(&self.directive)
+            .and_then(|config| config.color.as_deref())
+            .and_then(hex_color)
+            .unwrap_or_else(|| self.directive.color_hex().to_owned())
+    }
+
+    /// The `header` lines, then the content, as a blockquote.
+    fn quoted(&self, header: &[String]) -> String {
+        let indent = " ".repeat(self.indent);
+        let mut quote = String::from("\n");
+        let content = self
+            .content
+            .lines()
+            // Continuation lines are already indented, as in the source
+            .map(|line| line.strip_prefix(indent.as_str()).unwrap_or(line));
+        for line in header.iter().map(String::as_str).chain(content) {
+            if line.trim().is_empty() {
+                quote.push_str(&format!("{indent}>\n"));
+            } else {
+                quote.push_str(&format!("{indent}> {line}\n"));
+            }
+        }
+        quote
+    }
+}
+
+/// A CSS hex color, such as `#e91e63`, as LaTeX `HTML` model hex.
+///
+/// Returns `None` for other kinds of CSS color.
+fn hex_color(color: &str) -> Option<String> {
+    let hex = color.strip_prefix('#')?;
+    let hex = match hex.len() {
+        // Expand shorthand such as `#e16`, ignoring any alpha
+        3 | 4 => hex.chars().take(3).flat_map(|c| [c, c]).collect(),
+        6 | 8 => hex[..6].to_owned(),
+        _ => return None,
+    };
+    Some(hex.to_ascii_uppercase())
+}
+
+/// Mix `percent` of an `RRGGBB` hex color with white, as `RRGGBB` hex.
+fn tint(color: &str, percent: u32) -> String {
+    (0..3)
+        .map(|channel| {
+            let value =
+                u32::from_str_radix(&color[channel * 2..channel * 2 + 2], 16).unwrap_or_default();
+            format!("{:02X}", 255 - (255 - value) * percent / 100)
+        })
+        .collect()
+}
+
+/// Escape text for LaTeX.
+fn escape_latex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str(r"\textbackslash{}"),
+            '~' => escaped.push_str(r"\textasciitilde{}"),
+            '^' => escaped.push_str(r"\textasciicircum{}"),
+            '{' | '}' | '$' | '&' | '#' | '_' | '%' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
     }
+    escaped
+}
+
+/// Escape a user provided url, for use as an HTML attribute.
+fn escape_url(url: &str) -> String {
+    let mut escaped = String::with_capacity(url.len());
+    escape_href(&mut escaped, url).expect("writing to a string is infallible");
+    escaped
+}
+
+/// Render inline markdown (such as a title) to HTML, as mdbook would render
+/// the content of a heading.
+pub(crate) fn inline_html(markdown: &str) -> String {
+    let events = Parser::new(markdown).filter(|event| {
+        !matches!(
+            event,
+            Event::Start(Tag::Paragraph) | Event::End(Tag::Paragraph)
+        )
+    });
+    let mut html = String::with_capacity(markdown.len());
+    push_html(&mut html, events);
+    html
+}
+
+impl Size {
+    /// Inline style setting the size, read by the installed stylesheet.
+    ///
+    /// Lengths are validated when parsed, so need no escaping.
+    fn style(&self) -> String {
+        [
+            ("--admonish-width", &self.width),
+            ("--admonish-max-width", &self.max_width),
+        ]
+        .into_iter()
+        .filter_map(|(property, value)| Some(format!("{property}: {}", value.as_ref()?)))
+        .collect::<Vec<_>>()
+        .join("; ")
+    }
+}
+
+impl Attach {
+    fn classname(&self) -> &'static str {
+        match self {
+            Attach::Above => "admonish-attach-above",
+        }
+    }
+}
+
+impl Align {
+    fn classname(&self) -> &'static str {
+        match self {
+            Align::Left => "admonish-align-left",
+            Align::Center => "admonish-align-center",
+            Align::Right => "admonish-align-right",
+        }
+    }
+}
+
+impl Float {
+    fn classname(&self) -> &'static str {
+        match self {
+            Float::Left => "admonish-float-left",
+            Float::Right => "admonish-float-right",
+        }
+    }
+}
+
+impl Preset {
+    fn classname(&self) -> Option<&'static str> {
+        match self {
+            Preset::Classic => None,
+            Preset::Flat => Some("admonish-preset-flat"),
+            Preset::Compact => Some("admonish-preset-compact"),
+            Preset::QuoteBar => Some("admonish-preset-quote-bar"),
+        }
+    }
+}
+
+impl Print {
+    fn classname(&self) -> Option<&'static str> {
+        match self {
+            Print::Always => None,
+            Print::Never => Some("admonish-no-print"),
+            Print::Only => Some("admonish-print-only"),
+        }
+    }
+}
+
+impl Markup {
+    /// Prepare a value for use inside a double quoted attribute.
+    ///
+    /// Values are escaped for both HTML and XHTML, as they may be user
+    /// provided, such as tags and directive titles.
+    fn attribute<'b>(&self, value: &'b str) -> Cow<'b, str> {
+        if !value.contains(['"', '&', '<', '>']) {
+            return Cow::Borrowed(value);
+        }
+        let mut escaped = String::with_capacity(value.len());
+        escape_html(&mut escaped, value).expect("writing to a string is infallible");
+        Cow::Owned(escaped)
+    }
+
+    /// Prepare user provided inline markdown (such as a title).
+    ///
+    /// For XHTML, this is rendered to HTML up front, so that entities and
+    /// void elements can be made well formed.
+    fn inline<'b>(&self, markdown: &'b str) -> Cow<'b, str> {
+        match self {
+            Markup::Html => Cow::Borrowed(markdown),
+            Markup::Xhtml => Cow::Owned(close_void_elements(&inline_html(markdown)).into_owned()),
+        }
+    }
+
+    /// Prepare a user provided HTML fragment.
+    fn fragment<'b>(&self, html: &'b str) -> Cow<'b, str> {
+        match self {
+            Markup::Html => Cow::Borrowed(html),
+            Markup::Xhtml => close_void_elements(html),
+        }
+    }
+}
+
+/// Self close any void elements (such as `<br>`) in `html`.
+fn close_void_elements(html: &str) -> Cow<'_, str> {
+    static RX_VOID_ELEMENT: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"(?i)<(area|base|br|col|embed|hr|img|input|link|meta|param|source|track|wbr)\b([^>]*?)\s*/?>",
+        )
+        .expect("void element regex")
+    });
+    RX_VOID_ELEMENT.replace_all(html, "<$1$2 />")
 }
 
 const ANCHOR_ID_PREFIX: &str = "admonition";
@@ -1,62 +1,172 @@
 use crate::config::InstanceConfig;
-use crate::types::{AdmonitionDefaults, Directive};
-use std::str::FromStr;
+use crate::types::{
+    AdmonitionDefaults, Align, Attach, CustomDirectives, Directive, Float, Print, Size,
+};
+use std::borrow::Cow;
 
 /// All information required to render an admonition.
 ///
 /// i.e. all configured options have been resolved at this point.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Default)]
 pub(crate) struct AdmonitionMeta {
     pub directive: Directive,
     pub title: String,
+    /// The default title of the directive, used to label and number blocks.
+    pub directive_title: String,
+    pub id: Option<String>,
     pub additional_classnames: Vec<String>,
     pub collapsible: bool,
+    pub open: bool,
+    /// Whether the title has an icon.
+    pub icon: bool,
+    pub body_classnames: Vec<String>,
+    pub url: Option<String>,
+    pub attach: Option<Attach>,
+    pub size: Size,
+    pub align: Option<Align>,
+    pub float: Option<Float>,
+    /// Sidenote placement set on the block, if any.
+    pub sidenote: Option<bool>,
+    /// Whether the block takes part in automatic numbering.
+    pub numbered: bool,
+    /// Print visibility set on the block, if any.
+    pub print: Option<Print>,
+    pub tags: Vec<String>,
 }
 
 impl AdmonitionMeta {
-    pub fn from_info_string(
-        info_string: &str,
-        defaults: &AdmonitionDefaults,
-    ) -> Option<Result<Self, String>> {
-        InstanceConfig::from_info_string(info_string)
-            .map(|raw| raw.map(|raw| Self::resolve(raw, defaults)))
-    }
-
     /// Combine the per-admonition configuration with global defaults (and
     /// other logic) to resolve the values needed for rendering.
-    fn resolve(raw: InstanceConfig, defaults: &AdmonitionDefaults) -> Self {
+    pub(crate) fn resolve(raw: InstanceConfig, defaults: &AdmonitionDefaults) -> Self {
         let InstanceConfig {
             directive: raw_directive,
             title,
+            id,
             additional_classnames,
             collapsible,
+            open,
+            icon,
+            body_classnames,
+            url,
+            attach,
+            size,
+            align,
+            float,
+            sidenote,
+            numbered,
+            print,
+            tags,
+            deprecated_syntax: _,
         } = raw;
 
+        let directives = &defaults.directives;
+        let directive = directives.get(&raw_directive);
+        let defaults = match &directive {
+            Some(directive) => defaults.for_directive(directive),
+            None => Cow::Borrowed(defaults),
+        };
+
         // Use values from block, else load default value
         let title = title.or_else(|| defaults.title.clone());
         let collapsible = collapsible.unwrap_or(defaults.collapsible);
+        let icon = icon.or(defaults.icon).unwrap_or(true);
+        // Like a LaTeX starred environment, an `unnumbered` class opts out
+        let numbered = numbered.unwrap_or(true)
+            && !additional_classnames
+                .iter()
+                .flat_map(|class| class.split_whitespace())
+                .any(|classname| classname == UNNUMBERED_CLASSNAME);
+        let additional_classnames = defaults
+            .class
+            .iter()
+            .cloned()
+            .chain(additional_classnames)
+            .collect();
 
         // Load the directive (and title, if one still not given)
-        let (directive, title) = match (Directive::from_str(&raw_directive), title) {
-            (Ok(directive), None) => (directive, format_directive_title(&raw_directive)),
-            (Err(_), None) => (Directive::Note, "Note".to_owned()),
-            (Ok(directive), Some(title)) => (directive, title),
-            (Err(_), Some(title)) => (Directive::Note, title),
+        let (directive, title) = match (directive, title) {
+            (Some(directive), None) => {
+                let title = directives
+                    .title(&directive)
+                    .map_or_else(|| format_directive_title(&raw_directive), str::to_owned);
+                (directive, title)
+            }
+            (None, None) => (
+                Directive::Note,
+                directive_title(directives, &Directive::Note),
+            ),
+            (Some(directive), Some(title)) => (directive, title),
+            (None, Some(title)) => (Directive::Note, title),
         };
+        let directive_title = directive_title(directives, &directive);
 
         Self {
             directive,
             title,
+            directive_title,
+            id,
             additional_classnames,
             collapsible,
+            open,
+            icon,
+            body_classnames,
+            url,
+            attach,
+            size,
+            align,
+            float,
+            sidenote,
+            numbered,
+            print,
+            tags,
+        }
+    }
+}
+
+impl AdmonitionMeta {
+    /// Normalize all classnames, removing duplicates and invalid names.
+    ///
+    /// Returns any invalid classnames that were removed.
+    pub(crate) fn normalize_classnames(&mut self) -> Vec<String> {
+        let mut invalid = Vec::new();
+        self.additional_classnames =
+            normalize_classnames(&self.additional_classnames, &mut invalid);
+        self.body_classnames = normalize_classnames(&self.body_classnames, &mut invalid);
+        invalid
+    }
+}
+
+/// Split classnames on whitespace, and remove duplicates.
+///
+/// Invalid classnames are moved to `invalid`.
+fn normalize_classnames(classnames: &[String], invalid: &mut Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(classnames.len());
+    for classname in classnames.iter().flat_map(|class| class.split_whitespace()) {
+        if !classname
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            invalid.push(classname.to_owned());
+        } else if !normalized.iter().any(|existing| existing == classname) {
+            normalized.push(classname.to_owned());
         }
     }
+    normalized
+}
+
+const UNNUMBERED_CLASSNAME: &str = "unnumbered";
+
+/// The default title of `directive`, as configured or else from its name.
+fn directive_title(directives: &CustomDirectives, directive: &Directive) -> String {
+    directives
+        .title(directive)
+        .map_or_else(|| format_directive_title(directive.name()), str::to_owned)
 }
 
 /// Format the title of an admonition directive
 ///
 /// We special case a few words to make them look nicer (e.g. "tldr" -> "TL;DR" and "faq" -> "FAQ").
-fn format_directive_title(input: &str) -> String {
+pub(crate) fn format_directive_title(input: &str) -> String {
     match input {
         "tldr" => "TL;DR".to_owned(),
         "faq" => "FAQ".to_owned(),
@@ -92,6 +202,29 @@ mod test {
         assert_eq!(format_directive_title("🦀"), "🦀");
     }
 
+    #[test]
+    fn test_normalize_classnames() {
+        let mut info = AdmonitionMeta::resolve(
+            InstanceConfig {
+                directive: "note".to_owned(),
+                additional_classnames: vec![
+                    "b\tc".to_owned(),
+                    " a ".to_owned(),
+                    "bad\"name".to_owned(),
+                ],
+                body_classnames: vec!["x".to_owned(), "x".to_owned(), "<y>".to_owned()],
+                ..Default::default()
+            },
+            &AdmonitionDefaults {
+                class: Some("a b".to_owned()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(info.normalize_classnames(), vec!["bad\"name", "<y>"]);
+        assert_eq!(info.additional_classnames, vec!["a", "b", "c"]);
+        assert_eq!(info.body_classnames, vec!["x"]);
+    }
+
     #[test]
     fn test_admonition_info_from_raw() {
         assert_eq!(
@@ -101,18 +234,64 @@ mod test {
                     title: None,
                     additional_classnames: Vec::new(),
                     collapsible: None,
+                    ..Default::default()
                 },
                 &Default::default()
             ),
             AdmonitionMeta {
                 directive: Directive::Note,
                 title: "Note".to_owned(),
+                directive_title: "Note".to_owned(),
                 additional_classnames: Vec::new(),
                 collapsible: false,
+                numbered: true,
+                icon: true,
+                ..Default::default()
             }
         );
     }
 
+    #[test]
+    fn test_admonition_info_from_raw_with_directive_defaults() {
+        let defaults: AdmonitionDefaults = toml::from_str(
+            r#"
+collapsible = false
+class = "book"
+
+[warning]
+collapsible = true
+icon = false
+
+[hint]
+title = "Pro tip"
+class = "tip-class"
+"#,
+        )
+        .unwrap();
+        let resolve = |directive: &str| {
+            AdmonitionMeta::resolve(
+                InstanceConfig {
+                    directive: directive.to_owned(),
+                    ..Default::default()
+                },
+                &defaults,
+            )
+        };
+
+        let warning = resolve("warning");
+        assert_eq!(warning.title, "Warning");
+        assert!(warning.collapsible);
+        assert_eq!(warning.additional_classnames, vec!["book"]);
+        // Overrides apply to all names of a directive
+        let tip = resolve("tip");
+        assert_eq!(tip.title, "Pro tip");
+        assert!(!tip.collapsible);
+        assert_eq!(tip.additional_classnames, vec!["tip-class"]);
+        assert!(!resolve("note").collapsible);
+        assert!(!warning.icon);
+        assert!(resolve("note").icon);
+    }
+
     #[test]
     fn test_admonition_info_from_raw_with_defaults() {
         assert_eq!(
@@ -122,17 +301,23 @@ mod test {
                     title: None,
                     additional_classnames: Vec::new(),
                     collapsible: None,
+                    ..Default::default()
                 },
                 &AdmonitionDefaults {
                     title: Some("Important!!!".to_owned()),
                     collapsible: true,
+                    ..Default::default()
                 },
             ),
             AdmonitionMeta {
                 directive: Directive::Note,
                 title: "Important!!!".to_owned(),
+                directive_title: "Note".to_owned(),
                 additional_classnames: Vec::new(),
                 collapsible: true,
+                numbered: true,
+                icon: true,
+                ..Default::default()
             }
         );
     }
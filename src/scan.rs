@@ -0,0 +1,203 @@
+//! Scanning the sources of a book without building it, for the command line.
+
+use anyhow::{anyhow, Result};
+use mdbook::{
+    book::BookItem,
+    preprocess::{LinkPreprocessor, Preprocessor, PreprocessorContext},
+    MDBook,
+};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    path::PathBuf,
+};
+
+use crate::{
+    book_config::{admonish_config, Config, OnFailure},
+    index::AdmonitionIndex,
+    preprocessor::{preprocess_chapters, Admonish},
+    render::HtmlOptions,
+    types::{Directive, RenderModes, RenderTextMode},
+    warnings::{Diagnostic, Warnings},
+};
+
+/// An admonition found in the sources of a book.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ListedAdmonition {
+    /// Path of the chapter source, relative to the book root directory.
+    pub path: PathBuf,
+    /// Line number of the start of the block.
+    pub line: usize,
+    pub directive: String,
+    /// The title as displayed, including any number.
+    pub title: String,
+    pub id: String,
+    pub tags: Vec<String>,
+    pub collapsible: bool,
+    /// Length of the block content, in characters.
+    pub length: usize,
+}
+
+/// List every admonition in the book at `book_root`, in book order.
+///
+/// Blocks are found as the preprocessor would find them for the html
+/// renderer, but nothing is rendered.
+pub fn list_admonitions(book_root: &Path) -> Result<Vec<ListedAdmonition>> {
+    let scan = Scan::load(book_root)?;
+    let mut index = AdmonitionIndex::default();
+    scan.run(&scan.config, &mut Warnings::default(), &mut index)?;
+
+    Ok(index
+        .entries
+        .into_iter()
+        .map(|entry| ListedAdmonition {
+            path: scan.source_path(entry.chapter_path.as_deref()),
+            line: entry.line,
+            directive: entry.directive.name().to_owned(),
+            title: entry.title,
+            id: entry.id,
+            tags: entry.tags,
+            collapsible: entry.collapsible,
+            length: entry.length,
+        })
+        .collect())
+}
+
+/// The directives of every admonition in the book at `book_root`.
+pub(crate) fn used_directives(book_root: &Path) -> Result<HashSet<Directive>> {
+    let scan = Scan::load(book_root)?;
+    let mut index = AdmonitionIndex::default();
+    scan.run(&scan.config, &mut Warnings::default(), &mut index)?;
+    Ok(index
+        .entries
+        .into_iter()
+        .map(|entry| entry.directive)
+        .collect())
+}
+
+/// Find every problem with the admonitions in the book at `book_root`.
+///
+/// All warnings are reported, except those allowed in configuration or by
+/// chapter comments. Blocks that fail to parse are reported rather than
+/// failing the scan, whatever `on_failure` is set to.
+///
+/// Paths are relative to the book root directory.
+pub fn check_book(book_root: &Path) -> Result<Vec<Diagnostic>> {
+    let scan = Scan::load(book_root)?;
+    let config = Config {
+        on_failure: OnFailure::Continue,
+        ..scan.config.clone()
+    };
+    let mut warnings = Warnings::from_config(&config.warnings, &config.default.directives)?;
+    scan.run(&config, &mut warnings, &mut AdmonitionIndex::default())?;
+
+    Ok(warnings
+        .into_diagnostics()
+        .into_iter()
+        .map(|diagnostic| Diagnostic {
+            path: diagnostic
+                .path
+                .as_deref()
+                .map(|path| scan.source_path(Some(path))),
+            ..diagnostic
+        })
+        .collect())
+}
+
+/// Render the chapter with source `chapter_path` in the book at `book_root`,
+/// as the preprocessor would when building the book with `renderer`.
+///
+/// `chapter_path` is either the path of the chapter source file, or its path
+/// relative to the source directory. Includes are expanded first, as by
+/// mdbook's own `links` preprocessor, but no other preprocessors are run.
+pub fn render_chapter(book_root: &Path, chapter_path: &Path, renderer: &str) -> Result<String> {
+    let scan = Scan::load(book_root)?;
+    let source_path = scan.chapter_source_path(chapter_path);
+    // The context can only be built outside of mdbook by deserializing it
+    let ctx: PreprocessorContext = serde_json::from_value(serde_json::json!({
+        "root": scan.book.root,
+        "config": scan.book.config,
+        "renderer": renderer,
+        "mdbook_version": mdbook::MDBOOK_VERSION,
+    }))?;
+
+    let mut book = scan.book.book.clone();
+    if scan.book.config.build.use_default_preprocessors {
+        book = LinkPreprocessor::new().run(&ctx, book)?;
+    }
+    let book = Admonish.run(&ctx, book)?;
+    book.iter()
+        .find_map(|item| match item {
+            BookItem::Chapter(chapter) if chapter.source_path.as_ref() == Some(&source_path) => {
+                Some(chapter.content.clone())
+            }
+            _ => None,
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "No chapter in the book has source '{}'",
+                chapter_path.display()
+            )
+        })
+}
+
+/// A book loaded from disk, with its configuration.
+struct Scan {
+    book: MDBook,
+    config: Config,
+}
+
+impl Scan {
+    fn load(book_root: &Path) -> Result<Self> {
+        let book = MDBook::load(book_root)?;
+        let config = admonish_config(&book.root, &book.config)?;
+        Ok(Self { book, config })
+    }
+
+    /// Find the admonitions in every chapter, as for the html renderer.
+    fn run(
+        &self,
+        config: &Config,
+        warnings: &mut Warnings,
+        index: &mut AdmonitionIndex,
+    ) -> Result<()> {
+        let mut html_options = HtmlOptions::from_config(&self.book.root, None, config)?;
+        html_options.src_dir = self.book.config.book.src.clone();
+        // Scanning should never run user commands
+        html_options.external_renderer = None;
+        let render_modes = RenderModes {
+            default: RenderTextMode::Html,
+            directive: HashMap::new(),
+        };
+        preprocess_chapters(
+            config,
+            &render_modes,
+            &html_options,
+            &mut self.book.book.clone(),
+            warnings,
+            index,
+            None,
+        )
+    }
+
+    /// The path of a chapter source relative to the source directory, from
+    /// either the path of the file, or that relative path itself.
+    fn chapter_source_path(&self, path: &Path) -> PathBuf {
+        let src_dir = self.book.root.join(&self.book.config.book.src);
+        match (path.canonicalize(), src_dir.canonicalize()) {
+            (Ok(file), Ok(src_dir)) if file.starts_with(&src_dir) => {
+                file.strip_prefix(src_dir).unwrap().to_owned()
+            }
+            _ => path.to_owned(),
+        }
+    }
+
+    /// The path of a chapter source relative to the book root, from its path
+    /// relative to the source directory.
+    fn source_path(&self, chapter_path: Option<&Path>) -> PathBuf {
+        chapter_path
+            .map(|path| self.book.config.book.src.join(path))
+            .unwrap_or_default()
+    }
+}
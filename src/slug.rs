@@ -0,0 +1,237 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::book_config::SlugStrategy;
+
+/// The text to generate an anchor id from, for a block titled `title`.
+///
+/// The result is then normalized as mdbook normalizes heading anchors.
+pub(crate) fn slug_source(title: &str, strategy: SlugStrategy) -> Cow<'_, str> {
+    match strategy {
+        SlugStrategy::KeepUnicode => Cow::Borrowed(title),
+        SlugStrategy::Transliterate => {
+            let transliterated = transliterate(title);
+            // Scripts we can't transliterate (such as CJK) would otherwise
+            // leave nothing useful
+            if transliterated.chars().any(|c| c.is_ascii_alphanumeric()) {
+                Cow::Owned(transliterated)
+            } else {
+                Cow::Owned(hash_hex(title.as_bytes()))
+            }
+        }
+        SlugStrategy::Hash => Cow::Owned(hash_hex(title.as_bytes())),
+    }
+}
+
+/// An anchor id for `content`, unique among the ids in `id_counter`.
+///
+/// Ids are generated in the same way as mdbook generates heading anchors, so
+/// that they can be reserved against each other.
+pub(crate) fn unique_id_from_content(
+    content: &str,
+    id_counter: &mut HashMap<String, usize>,
+) -> String {
+    static HTML: Lazy<Regex> = Lazy::new(|| Regex::new(r"(<.*?>)").expect("html tag regex"));
+    let mut content = HTML.replace_all(content, "").into_owned();
+    for entity in ["&lt;", "&gt;", "&amp;", "&#39;", "&quot;"] {
+        content = content.replace(entity, "");
+    }
+    let id = normalize_id(content.trim().trim_start_matches('#').trim());
+
+    // Repeated ids have an incrementing counter appended
+    let id_count = id_counter.entry(id.clone()).or_insert(0);
+    let unique_id = match *id_count {
+        0 => id,
+        id_count => format!("{id}-{id_count}"),
+    };
+    *id_count += 1;
+    unique_id
+}
+
+fn normalize_id(content: &str) -> String {
+    content
+        .chars()
+        .filter_map(|ch| {
+            if ch.is_alphanumeric() || ch == '_' || ch == '-' {
+                Some(ch.to_ascii_lowercase())
+            } else if ch.is_whitespace() {
+                Some('-')
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// A short hex hash of `content`, as used in generated ids and the file
+/// names of installed assets.
+///
+/// FNV-1a, which is stable across releases and platforms, so these don't
+/// change between builds.
+pub fn hash_hex(content: &[u8]) -> String {
+    let hash = content.iter().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{:08x}", hash as u32)
+}
+
+/// Replace accented Latin, Cyrillic and Greek letters with ASCII.
+///
+/// Other non ASCII characters are kept as they are.
+fn transliterate(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    for c in input.chars() {
+        match transliterate_char(c.to_lowercase().next().unwrap_or(c)) {
+            Some(replacement) => output.push_str(replacement),
+            None => output.push(c),
+        }
+    }
+    output
+}
+
+fn transliterate_char(c: char) -> Option<&'static str> {
+    Some(match c {
+        // Latin
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        // Cyrillic
+        'а' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' => "g",
+        'ґ' => "g",
+        'д' => "d",
+        'е' => "e",
+        'ё' => "yo",
+        'є' => "ye",
+        'ж' => "zh",
+        'з' => "z",
+        'и' => "i",
+        'і' => "i",
+        'ї' => "yi",
+        'й' => "y",
+        'к' => "k",
+        'л' => "l",
+        'м' => "m",
+        'н' => "n",
+        'о' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'у' => "u",
+        'ў' => "u",
+        'ф' => "f",
+        'х' => "kh",
+        'ц' => "ts",
+        'ч' => "ch",
+        'ш' => "sh",
+        'щ' => "shch",
+        'ъ' | 'ь' => "",
+        'ы' => "y",
+        'э' => "e",
+        'ю' => "yu",
+        'я' => "ya",
+        // Greek
+        'α' | 'ά' => "a",
+        'β' => "v",
+        'γ' => "g",
+        'δ' => "d",
+        'ε' | 'έ' => "e",
+        'ζ' => "z",
+        'η' | 'ή' => "i",
+        'θ' => "th",
+        'ι' | 'ί' | 'ϊ' | 'ΐ' => "i",
+        'κ' => "k",
+        'λ' => "l",
+        'μ' => "m",
+        'ν' => "n",
+        'ξ' => "x",
+        'ο' | 'ό' => "o",
+        'π' => "p",
+        'ρ' => "r",
+        'σ' | 'ς' => "s",
+        'τ' => "t",
+        'υ' | 'ύ' | 'ϋ' | 'ΰ' => "y",
+        'φ' => "f",
+        'χ' => "ch",
+        'ψ' => "ps",
+        'ω' | 'ώ' => "o",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn slug_source_strategies() {
+        assert_eq!(slug_source("Никита", SlugStrategy::KeepUnicode), "Никита");
+        assert_eq!(slug_source("Никита", SlugStrategy::Transliterate), "nikita");
+        assert_eq!(
+            slug_source("Crème brûlée", SlugStrategy::Transliterate),
+            "Creme brulee"
+        );
+        assert_eq!(slug_source("Αθήνα", SlugStrategy::Transliterate), "athina");
+        // No table for CJK, so fall back to a hash
+        assert_eq!(
+            slug_source("注意事项", SlugStrategy::Transliterate),
+            hash_hex("注意事项".as_bytes())
+        );
+        assert_eq!(
+            slug_source("注意事项", SlugStrategy::Hash),
+            hash_hex("注意事项".as_bytes())
+        );
+    }
+
+    #[test]
+    fn hash_hex_is_stable() {
+        assert_eq!(hash_hex(b""), "84222325");
+        assert_eq!(hash_hex(b"a"), "8601ec8c");
+    }
+
+    #[cfg(feature = "mdbook")]
+    #[test]
+    fn unique_id_from_content_matches_mdbook() {
+        let mut ours = HashMap::new();
+        let mut theirs = HashMap::new();
+        for content in [
+            "Title",
+            "Title",
+            "## <em>Styled</em> &amp; escaped",
+            "  Spaced   out  ",
+            "Ünïcödé_and-dashes!",
+            "",
+        ] {
+            assert_eq!(
+                mdbook::utils::unique_id_from_content(content, &mut theirs),
+                unique_id_from_content(content, &mut ours)
+            );
+        }
+    }
+}
@@ -0,0 +1,419 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::ops::Range;
+
+use crate::config::InstanceConfig;
+use crate::resolve::format_directive_title;
+use crate::types::Directive;
+use std::str::FromStr;
+
+/// An admonition written in a syntax other than an `admonish` code block.
+#[derive(Debug, PartialEq)]
+pub(crate) struct SyntaxBlock {
+    /// The whole block, to be replaced.
+    pub(crate) span: Range<usize>,
+    pub(crate) indent: usize,
+    pub(crate) config: InstanceConfig,
+    pub(crate) body: String,
+}
+
+/// Parse a blockquote as a GitHub alert, such as:
+///
+/// ```markdown
+/// > [!NOTE]
+/// > Content
+/// ```
+///
+/// Each type is read as the directive it is written as in the `alerts` render
+/// mode, titled with the type.
+///
+/// `span` is the span of the blockquote, starting at the first `>`.
+///
+/// Returns `None` if the blockquote is not an alert.
+pub(crate) fn github_alert(
+    content: &str,
+    span: Range<usize>,
+    indent: usize,
+) -> Option<SyntaxBlock> {
+    static RX_GITHUB_ALERT: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r#"^\[!([A-Za-z]+)\][ \t]*$"#).expect("github alert regex"));
+
+    let quote = content[span.clone()].trim_end();
+    let mut lines = quote.split_inclusive('\n');
+    let marker = strip_quote_marker(lines.next()?, 0);
+    let kind = RX_GITHUB_ALERT.captures(marker.trim_end())?[1].to_lowercase();
+    let directive = Directive::from_alert_kind(&kind)?;
+
+    Some(SyntaxBlock {
+        span: span.start..span.start + quote.len(),
+        indent,
+        config: InstanceConfig {
+            title: (kind != directive.name()).then(|| format_directive_title(&kind)),
+            directive: directive.name().to_owned(),
+            ..Default::default()
+        },
+        body: lines.map(|line| strip_quote_marker(line, indent)).collect(),
+    })
+}
+
+/// Parse a blockquote as an Obsidian callout, such as:
+///
+/// ```markdown
+/// > [!tip]- Title
+/// > Content
+/// ```
+///
+/// The type may be any directive, and the title is optional. A `-` after the
+/// type makes the block collapsible and initially closed, and a `+` makes it
+/// collapsible and initially open.
+///
+/// `span` is the span of the blockquote, starting at the first `>`.
+///
+/// Returns `None` if the blockquote is not a callout.
+pub(crate) fn obsidian_callout(
+    content: &str,
+    span: Range<usize>,
+    indent: usize,
+) -> Option<SyntaxBlock> {
+    static RX_OBSIDIAN_CALLOUT: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r#"^\[!([A-Za-z]+)\]([+-])?(?:[ \t]+(.*?))?[ \t]*$"#)
+            .expect("obsidian callout regex")
+    });
+
+    let quote = content[span.clone()].trim_end();
+    let mut lines = quote.split_inclusive('\n');
+    let marker = strip_quote_marker(lines.next()?, 0);
+    let captures = RX_OBSIDIAN_CALLOUT.captures(marker.trim_end())?;
+    let kind = captures[1].to_lowercase();
+    Directive::from_str(&kind).ok()?;
+    let fold = captures.get(2).map(|fold| fold.as_str());
+
+    Some(SyntaxBlock {
+        span: span.start..span.start + quote.len(),
+        indent,
+        config: InstanceConfig {
+            directive: kind,
+            title: captures
+                .get(3)
+                .filter(|title| !title.as_str().is_empty())
+                .map(|title| title.as_str().to_owned()),
+            collapsible: fold.map(|_| true),
+            open: fold == Some("+"),
+            ..Default::default()
+        },
+        body: lines.map(|line| strip_quote_marker(line, indent)).collect(),
+    })
+}
+
+/// Indentation of the content of MkDocs admonitions.
+const MKDOCS_CONTENT_INDENT: usize = 4;
+
+/// Parse a paragraph as a MkDocs admonition, such as:
+///
+/// ```markdown
+/// !!! note "Title"
+///     Content
+/// ```
+///
+/// Collapsible blocks are written with `???` in place of `!!!`, or `???+` to
+/// start open, as supported by the `pymdownx.details` extension. Any words after the type are added as
+/// classnames.
+///
+/// `start` is the start of the paragraph. The block continues for as long as
+/// lines are blank, or indented past the opening line.
+///
+/// Returns `None` if the paragraph is not an admonition.
+pub(crate) fn mkdocs_admonition(content: &str, start: usize, indent: usize) -> Option<SyntaxBlock> {
+    static RX_MKDOCS_ADMONITION: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r#"^(!!!|\?\?\?\+?)[ \t]+([A-Za-z0-9_-]+)((?:[ \t]+[A-Za-z0-9_-]+)*)(?:[ \t]+"(.*)")?[ \t]*$"#)
+            .expect("mkdocs admonition regex")
+    });
+
+    let rest = &content[start..];
+    let mut lines = rest.split_inclusive('\n');
+    let opening = lines.next()?;
+    let captures = RX_MKDOCS_ADMONITION.captures(opening.trim_end())?;
+
+    let content_indent = indent + MKDOCS_CONTENT_INDENT;
+    let mut end = opening.len();
+    let mut offset = opening.len();
+    let mut body = String::new();
+    let mut blank_lines = String::new();
+    for line in lines {
+        offset += line.len();
+        if line.trim().is_empty() {
+            blank_lines.push('\n');
+            continue;
+        }
+        let leading_spaces = line.len() - line.trim_start_matches(' ').len();
+        if leading_spaces < content_indent {
+            break;
+        }
+        body.push_str(&blank_lines);
+        blank_lines.clear();
+        body.push_str(&" ".repeat(indent));
+        body.push_str(&line[content_indent..]);
+        end = offset;
+    }
+
+    Some(SyntaxBlock {
+        span: start..start + rest[..end].trim_end().len(),
+        indent,
+        config: InstanceConfig {
+            directive: captures[2].to_owned(),
+            title: captures.get(4).map(|title| title.as_str().to_owned()),
+            additional_classnames: captures[3].split_whitespace().map(str::to_owned).collect(),
+            collapsible: Some(captures[1].starts_with("???")),
+            open: &captures[1] == "???+",
+            ..Default::default()
+        },
+        body: body.trim_end().to_owned(),
+    })
+}
+
+/// Parse a paragraph as a Pandoc fenced div, such as:
+///
+/// ```markdown
+/// ::: {.warning title="Title"}
+/// Content
+/// :::
+/// ```
+///
+/// The directive is the first class that names a directive, and any other
+/// classes are added as classnames. A single class can also be written
+/// alone, as in `::: note`. Divs may be nested, and are matched to their
+/// closing fence in the same way as Pandoc.
+///
+/// `start` is the start of the paragraph.
+///
+/// Returns `None` if the paragraph does not open a div, or the div does not
+/// name a directive.
+pub(crate) fn pandoc_div(content: &str, start: usize, indent: usize) -> Option<SyntaxBlock> {
+    static RX_PANDOC_OPENING: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r#"^:{3,}[ \t]*(\{.*\}|[A-Za-z0-9_-]+)[ \t]*:*$"#).expect("pandoc div regex")
+    });
+    static RX_PANDOC_CLOSING: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r#"^:{3,}$"#).expect("pandoc div closing regex"));
+
+    let rest = &content[start..];
+    let mut lines = rest.split_inclusive('\n');
+    let opening = lines.next()?;
+    let attributes = RX_PANDOC_OPENING.captures(opening.trim_end())?[1].to_owned();
+
+    let mut config = InstanceConfig::default();
+    match attributes
+        .strip_prefix('{')
+        .and_then(|attributes| attributes.strip_suffix('}'))
+    {
+        Some(attributes) => {
+            for attribute in shlex::split(attributes)? {
+                if let Some(class) = attribute.strip_prefix('.') {
+                    if config.directive.is_empty() && Directive::from_str(class).is_ok() {
+                        config.directive = class.to_owned();
+                    } else if class != "admonition" {
+                        config.additional_classnames.push(class.to_owned());
+                    }
+                } else if let Some(title) = attribute.strip_prefix("title=") {
+                    config.title = Some(title.to_owned());
+                }
+            }
+        }
+        None if Directive::from_str(&attributes).is_ok() => config.directive = attributes,
+        None => return None,
+    }
+    if config.directive.is_empty() {
+        return None;
+    }
+
+    // Find the matching closing fence, skipping over code blocks
+    let mut depth = 0;
+    let mut code_fence: Option<&str> = None;
+    let mut offset = opening.len();
+    for line in lines {
+        let line_start = offset;
+        offset += line.len();
+        let line = line.trim();
+        if let Some(fence) = code_fence {
+            if line.starts_with(fence) && line.trim_start_matches(&fence[..1]).is_empty() {
+                code_fence = None;
+            }
+            continue;
+        }
+        if line.starts_with("```") || line.starts_with("~~~") {
+            let character = &line[..1];
+            code_fence = Some(&line[..line.len() - line.trim_start_matches(character).len()]);
+        } else if RX_PANDOC_CLOSING.is_match(line) {
+            if depth == 0 {
+                return Some(SyntaxBlock {
+                    span: start..start + rest[..offset].trim_end().len(),
+                    indent,
+                    config,
+                    body: rest[opening.len()..line_start].trim_end().to_owned(),
+                });
+            }
+            depth -= 1;
+        } else if RX_PANDOC_OPENING.is_match(line) {
+            depth += 1;
+        }
+    }
+    None
+}
+
+/// Remove the blockquote marker from the start of a line, keeping up to
+/// `indent` spaces of indentation before it.
+///
+/// Lazy continuation lines, without a marker, are returned unchanged.
+fn strip_quote_marker(line: &str, indent: usize) -> String {
+    let leading_spaces = line.len() - line.trim_start_matches(' ').len();
+    let rest = match line[leading_spaces..].strip_prefix('>') {
+        Some(rest) => rest.strip_prefix(' ').unwrap_or(rest),
+        None => return line.to_owned(),
+    };
+    format!("{}{rest}", " ".repeat(leading_spaces.min(indent)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_github_alert() {
+        let content = "> [!Important]\n> Line one\nlazy line two\n>\n> Line three\n\nAfter";
+        assert_eq!(
+            github_alert(content, 0..content.len() - 5, 0),
+            Some(SyntaxBlock {
+                span: 0..content.len() - 7,
+                indent: 0,
+                config: InstanceConfig {
+                    directive: "abstract".to_owned(),
+                    title: Some("Important".to_owned()),
+                    ..Default::default()
+                },
+                body: "Line one\nlazy line two\n\nLine three".to_owned(),
+            })
+        );
+
+        let content = "- item\n\n  > [!TIP]\n  > Indented\n";
+        let start = content.find('>').unwrap();
+        assert_eq!(
+            github_alert(content, start..content.len(), 2).map(|alert| alert.body),
+            Some("  Indented".to_owned())
+        );
+
+        for content in [
+            "> Plain quote",
+            "> [!NOTE] with a title",
+            "> [!abstract]\n> Not a GitHub kind",
+            "> [!HINT]\n> Only an alias",
+            "> Content\n> [!NOTE]",
+        ] {
+            assert_eq!(github_alert(content, 0..content.len(), 0), None);
+        }
+    }
+
+    #[test]
+    fn test_obsidian_callout() {
+        let content = "> [!FAQ]- Why *this*?\n> Because.\n";
+        assert_eq!(
+            obsidian_callout(content, 0..content.len(), 0),
+            Some(SyntaxBlock {
+                span: 0..content.len() - 1,
+                indent: 0,
+                config: InstanceConfig {
+                    directive: "faq".to_owned(),
+                    title: Some("Why *this*?".to_owned()),
+                    collapsible: Some(true),
+                    ..Default::default()
+                },
+                body: "Because.".to_owned(),
+            })
+        );
+
+        let content = "> [!tip]+\n> Open";
+        let block = obsidian_callout(content, 0..content.len(), 0).unwrap();
+        assert_eq!(block.config.title, None);
+        assert_eq!(block.config.collapsible, Some(true));
+        assert!(block.config.open);
+
+        let content = "> [!note]\n> Plain";
+        let block = obsidian_callout(content, 0..content.len(), 0).unwrap();
+        assert_eq!(block.config.collapsible, None);
+
+        for content in ["> Plain quote", "> [!unknown] Title", "> [!tip]* Title"] {
+            assert_eq!(obsidian_callout(content, 0..content.len(), 0), None);
+        }
+    }
+
+    #[test]
+    fn test_mkdocs_admonition() {
+        let content =
+            "!!! danger highlight \"Don't do this\"\n    Line one\n\n        Code\n\nAfter";
+        assert_eq!(
+            mkdocs_admonition(content, 0, 0),
+            Some(SyntaxBlock {
+                span: 0..content.len() - 7,
+                indent: 0,
+                config: InstanceConfig {
+                    directive: "danger".to_owned(),
+                    title: Some("Don't do this".to_owned()),
+                    additional_classnames: vec!["highlight".to_owned()],
+                    collapsible: Some(false),
+                    ..Default::default()
+                },
+                body: "Line one\n\n    Code".to_owned(),
+            })
+        );
+
+        let content = "- item\n\n  ??? tip\n      Indented\n";
+        let start = content.find('?').unwrap();
+        let block = mkdocs_admonition(content, start, 2).unwrap();
+        assert_eq!(block.config.collapsible, Some(true));
+        assert!(!block.config.open);
+        assert_eq!(block.body, "  Indented");
+
+        let block = mkdocs_admonition("???+ note\n    Open\n", 0, 0).unwrap();
+        assert_eq!(block.config.collapsible, Some(true));
+        assert!(block.config.open);
+        assert_eq!(block.body, "Open");
+
+        for content in ["!!!", "!!! note \"Unclosed", "Text !!! note", "!!!+ note"] {
+            assert_eq!(mkdocs_admonition(content, 0, 0), None);
+        }
+    }
+
+    #[test]
+    fn test_pandoc_div() {
+        let content = "::: {.admonition .warning .wide title=\"Watch out\"} :::\nOuter\n\n::: note\nInner\n:::\n\n```\n:::\n```\n:::\nAfter";
+        assert_eq!(
+            pandoc_div(content, 0, 0),
+            Some(SyntaxBlock {
+                span: 0..content.len() - 6,
+                indent: 0,
+                config: InstanceConfig {
+                    directive: "warning".to_owned(),
+                    title: Some("Watch out".to_owned()),
+                    additional_classnames: vec!["wide".to_owned()],
+                    ..Default::default()
+                },
+                body: "Outer\n\n::: note\nInner\n:::\n\n```\n:::\n```".to_owned(),
+            })
+        );
+
+        let block = pandoc_div(":::tip\nContent\n:::", 0, 0).unwrap();
+        assert_eq!(block.config.directive, "tip");
+        assert_eq!(block.body, "Content");
+
+        for content in [
+            // Not a directive
+            "::: sidebar\nContent\n:::",
+            "::: {.sidebar}\nContent\n:::",
+            // Unclosed
+            "::: note\nContent",
+            // A closing fence
+            ":::",
+        ] {
+            assert_eq!(pandoc_div(content, 0, 0), None);
+        }
+    }
+}
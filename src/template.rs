@@ -0,0 +1,244 @@
+#[cfg(not(feature = "templates"))]
+use anyhow::anyhow;
+use anyhow::{Context, Result};
+#[cfg(feature = "templates")]
+use handlebars::{no_escape, Handlebars, Template};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{
+    book_config::{Config, WrapperConfig},
+    types::Directive,
+};
+
+/// User supplied templates, used in place of the builtin HTML structure.
+#[derive(Debug, Default)]
+pub(crate) struct Templates {
+    /// Template for all blocks, where there is no directive template.
+    pub(crate) default: Option<String>,
+    pub(crate) directive: HashMap<Directive, String>,
+    /// Snippets around all blocks.
+    pub(crate) wrapper: WrapperConfig,
+    /// Snippets around blocks of a directive, in place of `wrapper`.
+    pub(crate) directive_wrapper: HashMap<Directive, WrapperConfig>,
+}
+
+/// Values made available to a template when rendering an admonition.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub(crate) struct TemplateData<'a> {
+    pub(crate) id: &'a str,
+    pub(crate) anchor: &'a str,
+    pub(crate) directive: &'a str,
+    pub(crate) classes: &'a str,
+    pub(crate) body_classes: &'a str,
+    /// Inline style for the size of the block. Empty if there is none.
+    pub(crate) style: &'a str,
+    pub(crate) title: &'a str,
+    pub(crate) url: Option<&'a str>,
+    pub(crate) content: &'a str,
+    pub(crate) collapsible: bool,
+}
+
+impl Templates {
+    /// Load all templates referenced in the configuration.
+    ///
+    /// Template paths are relative to the book root directory.
+    pub(crate) fn from_config(root: &Path, config: &Config) -> Result<Self> {
+        let mut templates = Self {
+            default: config
+                .template
+                .as_deref()
+                .map(|path| load_template(root, path))
+                .transpose()?,
+            ..Default::default()
+        };
+        for (directive, directive_config) in config.directives()? {
+            if let Some(path) = &directive_config.template {
+                templates
+                    .directive
+                    .insert(directive, load_template(root, path)?);
+            }
+        }
+
+        check_wrapper(&config.wrapper, "wrapper")?;
+        templates.wrapper = config.wrapper.clone();
+        for (directive, directive_config) in config.directives()? {
+            check_wrapper(
+                &directive_config.wrapper,
+                &format!("directive.{}.wrapper", directive.name()),
+            )?;
+            templates
+                .directive_wrapper
+                .insert(directive, directive_config.wrapper.clone());
+        }
+        Ok(templates)
+    }
+
+    pub(crate) fn get(&self, directive: &Directive) -> Option<&str> {
+        self.directive
+            .get(directive)
+            .or(self.default.as_ref())
+            .map(String::as_str)
+    }
+
+    /// Snippets to render before and after blocks of `directive`.
+    pub(crate) fn wrapper(&self, directive: &Directive) -> (Option<&str>, Option<&str>) {
+        let directive_wrapper = self.directive_wrapper.get(directive);
+        let before = directive_wrapper
+            .and_then(|wrapper| wrapper.before.as_deref())
+            .or(self.wrapper.before.as_deref());
+        let after = directive_wrapper
+            .and_then(|wrapper| wrapper.after.as_deref())
+            .or(self.wrapper.after.as_deref());
+        (before, after)
+    }
+}
+
+/// Read the template at `path`, relative to `root`.
+fn load_template(root: &Path, path: &Path) -> Result<String> {
+    let path = root.join(path);
+    let source = std::fs::read_to_string(&path)
+        .with_context(|| format!("can't read template '{}'", path.display()))?;
+    // Check the template is valid now, rather than once per admonition
+    compile(&source).with_context(|| format!("invalid template '{}'", path.display()))?;
+    Ok(source)
+}
+
+/// Check that wrapper snippets are valid templates.
+fn check_wrapper(wrapper: &WrapperConfig, key: &str) -> Result<()> {
+    for (side, source) in [("before", &wrapper.before), ("after", &wrapper.after)] {
+        if let Some(source) = source {
+            compile(source).with_context(|| format!("invalid template '{key}.{side}'"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Check that `source` is a valid template.
+#[cfg(feature = "templates")]
+fn compile(source: &str) -> Result<()> {
+    Template::compile(source)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "templates"))]
+fn compile(_source: &str) -> Result<()> {
+    Err(without_templates())
+}
+
+#[cfg(not(feature = "templates"))]
+fn without_templates() -> anyhow::Error {
+    anyhow!("mdbook-admonish was built without the `templates` feature")
+}
+
+/// Render the given template source.
+///
+/// In line with the builtin template, output starts on a new line, and all
+/// lines are indented by `indent` spaces. This keeps the output part of any
+/// containing markdown element (such as a list).
+#[cfg(feature = "templates")]
+pub(crate) fn render(source: &str, indent: usize, data: &TemplateData) -> Result<String> {
+    let indented = indent_lines(source, indent);
+    let mut registry = Handlebars::new();
+    // Titles and content are already HTML/markdown, and must not be escaped
+    registry.register_escape_fn(no_escape);
+    registry
+        .render_template(&indented, data)
+        .context("Error rendering admonition template")
+}
+
+#[cfg(not(feature = "templates"))]
+pub(crate) fn render(_source: &str, _indent: usize, _data: &TemplateData) -> Result<String> {
+    Err(without_templates())
+}
+
+/// Start `source` on a new line, and indent all non-empty lines by `indent`
+/// spaces.
+pub(crate) fn indent_lines(source: &str, indent: usize) -> String {
+    let indent = " ".repeat(indent);
+    let mut indented = String::with_capacity(source.len());
+    for line in source.trim_end().lines() {
+        indented.push('\n');
+        if !line.is_empty() {
+            indented.push_str(&indent);
+        }
+        indented.push_str(line);
+    }
+    indented
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[cfg(feature = "templates")]
+    #[test]
+    fn render_indents_template_lines() {
+        let data = TemplateData {
+            id: "admonition-note",
+            anchor: "#admonition-note",
+            directive: "note",
+            classes: "admonition admonish-note",
+            body_classes: "",
+            style: "",
+            title: "Note",
+            url: None,
+            content: "Some <b>content</b>",
+            collapsible: false,
+        };
+        assert_eq!(
+            render(
+                r#"<section id="{{id}}" class="{{classes}}">
+{{#if title}}<header>{{title}}</header>{{/if}}
+
+{{content}}
+
+</section>
+"#,
+                2,
+                &data
+            )
+            .unwrap(),
+            r#"
+  <section id="admonition-note" class="admonition admonish-note">
+  <header>Note</header>
+
+  Some <b>content</b>
+
+  </section>"#
+        );
+    }
+
+    #[test]
+    fn directive_template_takes_precedence() {
+        let templates = Templates {
+            default: Some("default".to_owned()),
+            directive: [(Directive::Danger, "danger".to_owned())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        assert_eq!(templates.get(&Directive::Danger), Some("danger"));
+        assert_eq!(templates.get(&Directive::Note), Some("default"));
+        assert_eq!(Templates::default().get(&Directive::Note), None);
+    }
+
+    #[cfg(not(feature = "templates"))]
+    #[test]
+    fn templates_require_feature() {
+        let config: Config = toml::from_str(
+            r#"
+[wrapper]
+before = "<div>"
+"#,
+        )
+        .unwrap();
+        let error = Templates::from_config(Path::new("."), &config).unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "invalid template 'wrapper.before': mdbook-admonish was built without the `templates` feature"
+        );
+    }
+}
@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::Arc;
 
 /// Book wide defaults that may be provided by the user.
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
@@ -9,10 +12,173 @@ pub(crate) struct AdmonitionDefaults {
 
     #[serde(default)]
     pub(crate) collapsible: bool,
+
+    /// Space separated classnames to add to all blocks.
+    #[serde(default)]
+    pub(crate) class: Option<String>,
+
+    /// For collapsible blocks without a title, use the first sentence of the
+    /// content as the title.
+    #[serde(default)]
+    pub(crate) auto_title: bool,
+
+    /// Whether titles have an icon, `true` if not set.
+    #[serde(default)]
+    pub(crate) icon: Option<bool>,
+
+    /// Overrides for blocks of a directive, keyed by directive name, such as
+    /// `default.warning`.
+    #[serde(flatten, deserialize_with = "deserialize_directive_overrides")]
+    pub(crate) directive_overrides: HashMap<String, DefaultsOverride>,
+
+    /// Directives defined in configuration, loaded from `directive`.
+    #[serde(skip)]
+    pub(crate) directives: CustomDirectives,
+}
+
+/// Deserialize the remaining keys of `default`, which must all be tables of
+/// directive overrides.
+///
+/// Any other key is most likely a misspelled default, so is named in the
+/// error, rather than failing to parse as overrides.
+fn deserialize_directive_overrides<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, DefaultsOverride>, D::Error> {
+    use serde::de::Error;
+
+    HashMap::<String, serde_json::Value>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, value)| match value {
+            serde_json::Value::Object(_) => serde_json::from_value(value)
+                .map(|overrides| (key.clone(), overrides))
+                .map_err(|error| D::Error::custom(format!("{error} in [default.{key}]"))),
+            _ => Err(D::Error::custom(format!(
+                "unknown key `{key}` in [default]"
+            ))),
+        })
+        .collect()
 }
 
-#[derive(Debug, PartialEq)]
+/// Overrides for some of the book wide defaults.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub(crate) struct DefaultsOverride {
+    #[serde(default)]
+    pub(crate) title: Option<String>,
+
+    #[serde(default)]
+    pub(crate) collapsible: Option<bool>,
+
+    #[serde(default)]
+    pub(crate) class: Option<String>,
+
+    #[serde(default)]
+    pub(crate) auto_title: Option<bool>,
+
+    #[serde(default)]
+    pub(crate) icon: Option<bool>,
+}
+
+impl AdmonitionDefaults {
+    /// These defaults, with any values set in `overrides` replaced.
+    pub(crate) fn with_overrides(&self, overrides: &DefaultsOverride) -> Self {
+        let DefaultsOverride {
+            title,
+            collapsible,
+            class,
+            auto_title,
+            icon,
+        } = overrides;
+        Self {
+            title: title.clone().or_else(|| self.title.clone()),
+            collapsible: collapsible.unwrap_or(self.collapsible),
+            class: class.clone().or_else(|| self.class.clone()),
+            auto_title: auto_title.unwrap_or(self.auto_title),
+            icon: icon.or(self.icon),
+            directive_overrides: self.directive_overrides.clone(),
+            directives: self.directives.clone(),
+        }
+    }
+
+    /// These defaults, with the overrides for `directive` applied.
+    pub(crate) fn for_directive(&self, directive: &Directive) -> Cow<'_, Self> {
+        match self
+            .directive_overrides
+            .iter()
+            .find(|(name, _)| self.directives.get(name).as_ref() == Some(directive))
+        {
+            Some((_, overrides)) => Cow::Owned(self.with_overrides(overrides)),
+            None => Cow::Borrowed(self),
+        }
+    }
+}
+
+/// Size of an admonition, as CSS lengths.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct Size {
+    pub(crate) width: Option<String>,
+    pub(crate) max_width: Option<String>,
+}
+
+/// Where an admonition is visually attached to the surrounding content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Attach {
+    /// To the preceding paragraph or code block.
+    Above,
+}
+
+/// Horizontal position of an admonition narrower than the page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Side an admonition floats to, with the surrounding content wrapping it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Float {
+    Left,
+    Right,
+}
+
+/// Whether an admonition is shown when the page is printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Print {
+    /// On screen and in print.
+    #[default]
+    Always,
+    /// On screen only.
+    Never,
+    /// In print only.
+    Only,
+}
+
+impl Print {
+    /// Combine the `print` and `print_only` options.
+    ///
+    /// Returns `None` if neither is set.
+    pub(crate) fn from_options(
+        print: Option<bool>,
+        print_only: Option<bool>,
+    ) -> Result<Option<Self>, String> {
+        match (print, print_only) {
+            (Some(false), Some(true)) => {
+                Err("'print=false' and 'print_only=true' can't be used together".to_owned())
+            }
+            (_, Some(true)) => Ok(Some(Self::Only)),
+            (Some(false), _) => Ok(Some(Self::Never)),
+            (Some(true), _) | (None, Some(false)) => Ok(Some(Self::Always)),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub(crate) enum Directive {
+    #[default]
     Note,
     Abstract,
     Info,
@@ -25,32 +191,343 @@ pub(crate) enum Directive {
     Bug,
     Example,
     Quote,
+    /// A directive defined in configuration.
+    Custom(Arc<str>),
 }
 
 impl FromStr for Directive {
     type Err = ();
 
     fn from_str(string: &str) -> Result<Self, ()> {
-        match string {
-            "note" => Ok(Self::Note),
-            "abstract" | "summary" | "tldr" => Ok(Self::Abstract),
-            "info" | "todo" => Ok(Self::Info),
-            "tip" | "hint" | "important" => Ok(Self::Tip),
-            "success" | "check" | "done" => Ok(Self::Success),
-            "question" | "help" | "faq" => Ok(Self::Question),
-            "warning" | "caution" | "attention" => Ok(Self::Warning),
-            "failure" | "fail" | "missing" => Ok(Self::Failure),
-            "danger" | "error" => Ok(Self::Danger),
-            "bug" => Ok(Self::Bug),
-            "example" => Ok(Self::Example),
-            "quote" | "cite" => Ok(Self::Quote),
-            _ => Err(()),
+        BUILTIN_NAMES
+            .iter()
+            .find(|(name, _)| *name == string)
+            .map(|(_, directive)| directive.clone())
+            .ok_or(())
+    }
+}
+
+/// Directives are serialized by name, for caching between runs.
+impl Serialize for Directive {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Directive {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Self::from_str(&name).unwrap_or_else(|()| Self::custom(&name)))
+    }
+}
+
+/// Names and aliases of the builtin directives.
+const BUILTIN_NAMES: &[(&str, Directive)] = &[
+    ("note", Directive::Note),
+    ("abstract", Directive::Abstract),
+    ("summary", Directive::Abstract),
+    ("tldr", Directive::Abstract),
+    ("info", Directive::Info),
+    ("todo", Directive::Info),
+    ("tip", Directive::Tip),
+    ("hint", Directive::Tip),
+    ("important", Directive::Tip),
+    ("success", Directive::Success),
+    ("check", Directive::Success),
+    ("done", Directive::Success),
+    ("question", Directive::Question),
+    ("help", Directive::Question),
+    ("faq", Directive::Question),
+    ("warning", Directive::Warning),
+    ("caution", Directive::Warning),
+    ("attention", Directive::Warning),
+    ("failure", Directive::Failure),
+    ("fail", Directive::Failure),
+    ("missing", Directive::Failure),
+    ("danger", Directive::Danger),
+    ("error", Directive::Danger),
+    ("bug", Directive::Bug),
+    ("example", Directive::Example),
+    ("quote", Directive::Quote),
+    ("cite", Directive::Quote),
+];
+
+impl Directive {
+    /// All builtin directives.
+    pub(crate) const BUILTIN: &'static [Self] = &[
+        Self::Note,
+        Self::Abstract,
+        Self::Info,
+        Self::Tip,
+        Self::Success,
+        Self::Question,
+        Self::Warning,
+        Self::Failure,
+        Self::Danger,
+        Self::Bug,
+        Self::Example,
+        Self::Quote,
+    ];
+
+    /// The canonical name of this directive, as written by the user.
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            Self::Note => "note",
+            Self::Abstract => "abstract",
+            Self::Info => "info",
+            Self::Tip => "tip",
+            Self::Success => "success",
+            Self::Question => "question",
+            Self::Warning => "warning",
+            Self::Failure => "failure",
+            Self::Danger => "danger",
+            Self::Bug => "bug",
+            Self::Example => "example",
+            Self::Quote => "quote",
+            Self::Custom(name) => name,
+        }
+    }
+
+    /// The directive a GitHub alert of type `kind`, such as `CAUTION`, is
+    /// read as.
+    ///
+    /// Each type is read as the directive written with it by
+    /// [`Directive::alert_kind`], so alerts round-trip.
+    pub(crate) fn from_alert_kind(kind: &str) -> Option<Self> {
+        match kind.to_uppercase().as_str() {
+            "NOTE" => Some(Self::Note),
+            "TIP" => Some(Self::Tip),
+            "IMPORTANT" => Some(Self::Abstract),
+            "WARNING" => Some(Self::Warning),
+            "CAUTION" => Some(Self::Danger),
+            _ => None,
+        }
+    }
+
+    /// The GitHub alert type closest to this directive.
+    ///
+    /// GitHub only supports five types, so several directives share each.
+    pub(crate) fn alert_kind(&self) -> &'static str {
+        match self {
+            Self::Abstract => "IMPORTANT",
+            Self::Tip | Self::Success => "TIP",
+            Self::Warning => "WARNING",
+            Self::Failure | Self::Danger | Self::Bug => "CAUTION",
+            Self::Note
+            | Self::Info
+            | Self::Question
+            | Self::Example
+            | Self::Quote
+            | Self::Custom(_) => "NOTE",
         }
     }
+
+    /// The color of this directive in the builtin stylesheet, as hex RGB.
+    pub(crate) fn color_hex(&self) -> &'static str {
+        match self {
+            Self::Note | Self::Custom(_) => "448AFF",
+            Self::Abstract => "00B0FF",
+            Self::Info => "00B8D4",
+            Self::Tip => "00BFA5",
+            Self::Success => "00C853",
+            Self::Question => "64DD17",
+            Self::Warning => "FF9100",
+            Self::Failure => "FF5252",
+            Self::Danger => "FF1744",
+            Self::Bug => "F50057",
+            Self::Example => "7C4DFF",
+            Self::Quote => "9E9E9E",
+        }
+    }
+
+    /// The emoji rendered as the icon of this directive, in `emoji` icon mode.
+    pub(crate) fn emoji(&self) -> &'static str {
+        match self {
+            Self::Note | Self::Custom(_) => "📝",
+            Self::Abstract => "📋",
+            Self::Info => "ℹ️",
+            Self::Tip => "💡",
+            Self::Success => "✅",
+            Self::Question => "❓",
+            Self::Warning => "⚠️",
+            Self::Failure => "❌",
+            Self::Danger => "⚡",
+            Self::Bug => "🐛",
+            Self::Example => "📑",
+            Self::Quote => "💬",
+        }
+    }
+
+    /// A custom directive, named `name`.
+    pub(crate) fn custom(name: &str) -> Self {
+        Self::Custom(name.into())
+    }
+}
+
+/// Directives and aliases defined in configuration, as well as the builtin
+/// directives.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct CustomDirectives {
+    /// Directives by name or alias.
+    pub(crate) names: HashMap<String, Directive>,
+    /// Default titles, in place of the name written by the user.
+    pub(crate) titles: HashMap<Directive, String>,
+    /// Whether blocks naming an unknown directive are errors, rather than
+    /// rendered as notes.
+    pub(crate) strict: bool,
+}
+
+impl CustomDirectives {
+    /// The directive with this name or alias, if any.
+    pub(crate) fn get(&self, name: &str) -> Option<Directive> {
+        Directive::from_str(name)
+            .ok()
+            .or_else(|| self.names.get(name).cloned())
+    }
+
+    /// The configured default title of `directive`, if any.
+    pub(crate) fn title(&self, directive: &Directive) -> Option<&str> {
+        self.titles.get(directive).map(String::as_str)
+    }
+
+    /// The known directive name closest to `name`, if any is close enough to
+    /// be a likely typo.
+    pub(crate) fn suggest(&self, name: &str) -> Option<&str> {
+        let mut custom_names: Vec<&str> = self.names.keys().map(String::as_str).collect();
+        // Sorted, so ties are broken the same way every time
+        custom_names.sort_unstable();
+        let max_distance = name.chars().count() / 3 + 1;
+        BUILTIN_NAMES
+            .iter()
+            .map(|(name, _)| *name)
+            .chain(custom_names)
+            .map(|candidate| (edit_distance(name, candidate), candidate))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate)
+    }
+}
+
+/// The Damerau-Levenshtein distance between `a` and `b`, counting adjacent
+/// transpositions as a single edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // Distances for the previous two rows
+    let mut previous_previous: Vec<usize> = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(previous_previous[j - 2] + 1);
+            }
+        }
+        previous_previous = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum RenderTextMode {
     Strip,
     Html,
+    /// Remove the block entirely, including its content.
+    Drop,
+    /// Leave the block untouched.
+    Preserve,
+    /// Rewrite the block as a GitHub alert, such as `> [!NOTE]`.
+    Alerts,
+    /// Rewrite the block as a plain blockquote, with a bold title.
+    Blockquote,
+    /// Wrap the block in a LaTeX `tcolorbox` environment.
+    Latex,
+    /// Render to EPUB-safe HTML, with inline styles.
+    Epub,
+}
+
+/// How to render blocks, which may differ by directive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RenderModes {
+    pub(crate) default: RenderTextMode,
+    pub(crate) directive: HashMap<Directive, RenderTextMode>,
+}
+
+impl RenderModes {
+    pub(crate) fn get(&self, directive: &Directive) -> RenderTextMode {
+        self.directive
+            .get(directive)
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Whether any blocks may be rendered to HTML.
+    pub(crate) fn any_html(&self) -> bool {
+        let html =
+            |mode: &RenderTextMode| matches!(mode, RenderTextMode::Html | RenderTextMode::Epub);
+        html(&self.default) || self.directive.values().any(html)
+    }
+
+    /// Whether all blocks are left untouched.
+    #[cfg(feature = "mdbook")]
+    pub(crate) fn all_preserved(&self) -> bool {
+        self.default == RenderTextMode::Preserve
+            && self
+                .directive
+                .values()
+                .all(|mode| *mode == RenderTextMode::Preserve)
+    }
+}
+
+impl From<RenderTextMode> for RenderModes {
+    fn from(default: RenderTextMode) -> Self {
+        Self {
+            default,
+            directive: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("warning", "warning"), 0);
+        assert_eq!(edit_distance("warnign", "warning"), 1);
+        assert_eq!(edit_distance("nte", "note"), 1);
+        assert_eq!(edit_distance("", "tip"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn alert_kinds_round_trip() {
+        for kind in ["NOTE", "TIP", "IMPORTANT", "WARNING", "CAUTION"] {
+            let directive = Directive::from_alert_kind(kind).unwrap();
+            assert_eq!(directive.alert_kind(), kind);
+        }
+        for directive in Directive::BUILTIN {
+            let read = Directive::from_alert_kind(directive.alert_kind()).unwrap();
+            assert_eq!(read.alert_kind(), directive.alert_kind());
+        }
+        assert_eq!(Directive::from_alert_kind("hint"), None);
+    }
+
+    #[test]
+    fn suggestions() {
+        let directives = CustomDirectives {
+            names: HashMap::from([("security".to_owned(), Directive::custom("security"))]),
+            ..Default::default()
+        };
+        assert_eq!(directives.suggest("warnign"), Some("warning"));
+        assert_eq!(directives.suggest("Note"), Some("note"));
+        assert_eq!(directives.suggest("secuirty"), Some("security"));
+        assert_eq!(directives.suggest("completely-different"), None);
+    }
 }
@@ -0,0 +1,512 @@
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::{
+    book_config::WarningsConfig,
+    types::{CustomDirectives, Directive},
+};
+
+/// Categories of warning, which can be suppressed by the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum WarningCode {
+    /// A block uses the old `admonish directive.classname "Title"` syntax.
+    DeprecatedSyntax,
+    /// A block could not be parsed, and was rendered as an error.
+    InvalidBlock,
+    /// A chapter contains more admonitions than configured.
+    BudgetExceeded,
+    /// A classname contains invalid characters, and was ignored.
+    InvalidClassname,
+    /// A block names a directive that is not builtin or configured.
+    UnknownDirective,
+    /// A block has no content.
+    EmptyBlock,
+    /// A block is given an explicit id already used in the chapter.
+    DuplicateId,
+}
+
+impl WarningCode {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::DeprecatedSyntax => "deprecated-syntax",
+            Self::InvalidBlock => "invalid-block",
+            Self::BudgetExceeded => "budget-exceeded",
+            Self::InvalidClassname => "invalid-classname",
+            Self::UnknownDirective => "unknown-directive",
+            Self::EmptyBlock => "empty-block",
+            Self::DuplicateId => "duplicate-id",
+        }
+    }
+}
+
+impl FromStr for WarningCode {
+    type Err = ();
+
+    fn from_str(string: &str) -> Result<Self, ()> {
+        match string {
+            "deprecated-syntax" => Ok(Self::DeprecatedSyntax),
+            "invalid-block" => Ok(Self::InvalidBlock),
+            "budget-exceeded" => Ok(Self::BudgetExceeded),
+            "invalid-classname" => Ok(Self::InvalidClassname),
+            "unknown-directive" => Ok(Self::UnknownDirective),
+            "empty-block" => Ok(Self::EmptyBlock),
+            "duplicate-id" => Ok(Self::DuplicateId),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Collects warnings over the whole book, so that identical warnings are
+/// only reported once.
+#[derive(Debug, Default)]
+pub(crate) struct Warnings {
+    allow: HashSet<WarningCode>,
+    deny: bool,
+    budget: Budget,
+    /// Unique warnings, in the order they were first seen.
+    seen: Vec<(WarningCode, String)>,
+    /// Indices into `diagnostics` of each occurrence of a unique warning.
+    occurrences: HashMap<(WarningCode, String), Vec<usize>>,
+    /// Every warning, with where it was found.
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// A problem found in the book, and where it was found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// Path of the chapter source, if the problem is in a chapter.
+    pub path: Option<PathBuf>,
+    /// Line number of the start of the block, if the problem is in a block.
+    pub line: Option<usize>,
+    /// Column of the start of the block, in characters, if the problem is in
+    /// a block.
+    pub column: Option<usize>,
+    /// The warning code, such as `invalid-block`.
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Where the problem was found, such as `intro.md:12`, if known.
+    fn location(&self) -> Option<String> {
+        match (&self.path, self.line) {
+            (Some(path), Some(line)) => Some(format!("{}:{line}", path.display())),
+            (Some(path), None) => Some(path.display().to_string()),
+            (None, Some(line)) => Some(format!("line {line}")),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Maximum number of admonitions in each chapter.
+#[derive(Debug, Default)]
+struct Budget {
+    total: Option<usize>,
+    directive: HashMap<Directive, usize>,
+}
+
+impl Warnings {
+    pub(crate) fn from_config(
+        config: &WarningsConfig,
+        directives: &CustomDirectives,
+    ) -> Result<Self> {
+        let directive = config
+            .budget
+            .directive
+            .iter()
+            .map(|(name, max)| {
+                let directive = directives
+                    .get(name)
+                    .ok_or_else(|| anyhow!("Unknown directive '{name}' in warnings budget"))?;
+                Ok((directive, *max))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            allow: config.allow.iter().copied().collect(),
+            deny: config.deny,
+            budget: Budget {
+                total: config.budget.total,
+                directive,
+            },
+            ..Default::default()
+        })
+    }
+
+    /// Start collecting warnings for a chapter.
+    ///
+    /// Warnings can be suppressed for the whole chapter with a comment such as
+    /// `<!-- admonish-allow: deprecated-syntax -->`.
+    pub(crate) fn chapter(
+        &mut self,
+        chapter_path: Option<&Path>,
+        content: &str,
+    ) -> ChapterWarnings<'_> {
+        ChapterWarnings {
+            chapter: chapter_path
+                .map(|path| format!(" '{}'", path.display()))
+                .unwrap_or_default(),
+            path: chapter_path.map(Path::to_owned),
+            location: None,
+            allow: inline_allow(content),
+            admonitions: HashMap::new(),
+            warnings: self,
+        }
+    }
+
+    fn warn(
+        &mut self,
+        code: WarningCode,
+        message: String,
+        path: Option<&Path>,
+        location: Option<Location>,
+    ) {
+        if self.allow.contains(&code) {
+            return;
+        }
+        let index = self.diagnostics.len();
+        self.diagnostics.push(Diagnostic {
+            path: path.map(Path::to_owned),
+            line: location.map(|location| location.line),
+            column: location.map(|location| location.column),
+            code: code.name(),
+            message: message.clone(),
+        });
+        let key = (code, message);
+        let occurrences = self.occurrences.entry(key.clone()).or_default();
+        if occurrences.is_empty() {
+            self.seen.push(key);
+        }
+        occurrences.push(index);
+    }
+
+    /// Log all unique warnings collected so far.
+    pub(crate) fn log(&self) {
+        for summary in self.summaries() {
+            log::warn!("{summary}");
+        }
+    }
+
+    /// A line for each unique warning, starting with where it was first
+    /// found, and listing where it was found again.
+    fn summaries(&self) -> Vec<String> {
+        self.seen
+            .iter()
+            .map(|key| {
+                let (code, message) = key;
+                let mut locations = self.occurrences[key]
+                    .iter()
+                    .map(|index| self.diagnostics[*index].location());
+                let mut summary = match locations.next().flatten() {
+                    Some(location) => format!("{location}: {message} [{}]", code.name()),
+                    None => format!("{message} [{}]", code.name()),
+                };
+                let count = self.occurrences[key].len();
+                if count > 1 {
+                    let others = locations.flatten().collect::<Vec<_>>();
+                    match others.is_empty() {
+                        true => summary.push_str(&format!(" ({count} occurrences)")),
+                        false => summary.push_str(&format!(
+                            " ({count} occurrences, also at {})",
+                            others.join(", ")
+                        )),
+                    }
+                }
+                summary
+            })
+            .collect()
+    }
+
+    /// Fail if any warnings were reported, and warnings are denied.
+    pub(crate) fn ensure_none_denied(&self) -> Result<()> {
+        if self.deny && !self.seen.is_empty() {
+            return Err(anyhow!(
+                "Found {} warning(s), and warnings are denied",
+                self.seen.len()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Number of warnings collected so far, including repeats.
+    #[cfg(feature = "mdbook")]
+    pub(crate) fn count(&self) -> usize {
+        self.diagnostics.len()
+    }
+
+    /// Every warning collected so far, in the order they were reported.
+    #[cfg(any(feature = "mdbook", test))]
+    pub(crate) fn into_diagnostics(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+
+    #[cfg(test)]
+    pub(crate) fn unique(&self) -> Vec<(WarningCode, &str, usize)> {
+        self.seen
+            .iter()
+            .map(|key| (key.0, key.1.as_str(), self.occurrences[key].len()))
+            .collect()
+    }
+}
+
+/// A one-based position in a chapter source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Location {
+    pub(crate) line: usize,
+    /// Column in characters.
+    pub(crate) column: usize,
+}
+
+/// Warnings for a single chapter.
+#[derive(Debug)]
+pub(crate) struct ChapterWarnings<'a> {
+    /// Name of the chapter for messages, including a leading space.
+    chapter: String,
+    path: Option<PathBuf>,
+    /// Where the block currently being processed starts, if any.
+    location: Option<Location>,
+    allow: HashSet<WarningCode>,
+    /// Number of admonitions seen, for each directive.
+    admonitions: HashMap<Directive, usize>,
+    warnings: &'a mut Warnings,
+}
+
+impl ChapterWarnings<'_> {
+    pub(crate) fn warn(&mut self, code: WarningCode, message: impl Into<String>) {
+        if self.allow.contains(&code) {
+            return;
+        }
+        self.warnings
+            .warn(code, message.into(), self.path.as_deref(), self.location);
+    }
+
+    /// Report any following warnings against the block starting at
+    /// `location`.
+    pub(crate) fn set_location(&mut self, location: Option<Location>) {
+        self.location = location;
+    }
+
+    /// Record an admonition in this chapter, to check against the budget.
+    pub(crate) fn admonition(&mut self, directive: &Directive) {
+        *self.admonitions.entry(directive.clone()).or_default() += 1;
+    }
+
+    /// Report any warnings that apply to the chapter as a whole.
+    pub(crate) fn finish(mut self) {
+        let Budget { total, directive } = &self.warnings.budget;
+        let mut exceeded = Vec::new();
+        let count: usize = self.admonitions.values().sum();
+        if let Some(max) = total.filter(|max| count > *max) {
+            exceeded.push(format!(
+                "Chapter{} has {count} admonitions, over the budget of {max}",
+                self.chapter
+            ));
+        }
+        let mut directives = self.admonitions.iter().collect::<Vec<_>>();
+        directives.sort_by_key(|(directive, _)| directive.name());
+        for (name, count) in directives {
+            if let Some(max) = directive.get(name).filter(|max| count > max) {
+                exceeded.push(format!(
+                    "Chapter{} has {count} '{}' admonitions, over the budget of {max}",
+                    self.chapter,
+                    name.name()
+                ));
+            }
+        }
+        self.location = None;
+        for message in exceeded {
+            self.warn(WarningCode::BudgetExceeded, message);
+        }
+    }
+}
+
+/// Parse all warning codes allowed by inline comments in `content`.
+fn inline_allow(content: &str) -> HashSet<WarningCode> {
+    static RX_ALLOW: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r#"<!--\s*admonish-allow:([^>]*?)-->"#).expect("allow comment regex")
+    });
+
+    RX_ALLOW
+        .captures_iter(content)
+        .flat_map(|captures| {
+            captures
+                .get(1)
+                .map(|codes| codes.as_str())
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(|code| match WarningCode::from_str(code.trim()) {
+                    Ok(code) => Some(code),
+                    Err(()) => {
+                        log::warn!(
+                            "Unknown warning code in admonish-allow comment: '{}'",
+                            code.trim()
+                        );
+                        None
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::book_config::BudgetConfig;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn deduplicates_identical_warnings() {
+        let mut warnings = Warnings::default();
+        let mut chapter = warnings.chapter(None, "");
+        chapter.warn(WarningCode::InvalidBlock, "Invalid block");
+        chapter.warn(WarningCode::DeprecatedSyntax, "Deprecated");
+        chapter.warn(WarningCode::InvalidBlock, "Invalid block");
+        let mut chapter = warnings.chapter(None, "");
+        chapter.warn(WarningCode::InvalidBlock, "Invalid block");
+
+        assert_eq!(
+            warnings.unique(),
+            vec![
+                (WarningCode::InvalidBlock, "Invalid block", 3),
+                (WarningCode::DeprecatedSyntax, "Deprecated", 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn summaries_include_locations() {
+        let mut warnings = Warnings::default();
+        let mut chapter = warnings.chapter(Some(Path::new("intro.md")), "");
+        chapter.set_location(Some(Location { line: 3, column: 1 }));
+        chapter.warn(WarningCode::UnknownDirective, "Unknown directive 'tpi'");
+        chapter.set_location(Some(Location { line: 9, column: 3 }));
+        chapter.warn(WarningCode::UnknownDirective, "Unknown directive 'tpi'");
+        let mut chapter = warnings.chapter(Some(Path::new("guide.md")), "");
+        chapter.set_location(Some(Location { line: 5, column: 1 }));
+        chapter.warn(WarningCode::UnknownDirective, "Unknown directive 'tpi'");
+        chapter.warn(WarningCode::EmptyBlock, "Empty block");
+        warnings
+            .chapter(None, "")
+            .warn(WarningCode::InvalidBlock, "Invalid block");
+
+        assert_eq!(
+            warnings.summaries(),
+            vec![
+                "intro.md:3: Unknown directive 'tpi' [unknown-directive] (3 occurrences, also at intro.md:9, guide.md:5)",
+                "guide.md:5: Empty block [empty-block]",
+                "Invalid block [invalid-block]",
+            ]
+        );
+    }
+
+    #[test]
+    fn suppresses_allowed_warnings() {
+        let mut warnings = Warnings::from_config(
+            &WarningsConfig {
+                allow: vec![WarningCode::InvalidBlock],
+                ..Default::default()
+            },
+            &CustomDirectives::default(),
+        )
+        .unwrap();
+        let mut chapter = warnings.chapter(None, "");
+        chapter.warn(WarningCode::InvalidBlock, "Invalid block");
+        chapter.warn(WarningCode::DeprecatedSyntax, "Deprecated");
+        let mut chapter = warnings.chapter(
+            None,
+            "# Chapter\n<!-- admonish-allow: deprecated-syntax, invalid-block -->\n",
+        );
+        chapter.warn(
+            WarningCode::DeprecatedSyntax,
+            "Deprecated in allowed chapter",
+        );
+
+        assert_eq!(
+            warnings.unique(),
+            vec![(WarningCode::DeprecatedSyntax, "Deprecated", 1)]
+        );
+    }
+
+    #[test]
+    fn budget_exceeded() {
+        let mut warnings = Warnings::from_config(
+            &WarningsConfig {
+                budget: BudgetConfig {
+                    total: Some(2),
+                    directive: HashMap::from([("warning".to_owned(), 1), ("tip".to_owned(), 1)]),
+                },
+                ..Default::default()
+            },
+            &CustomDirectives::default(),
+        )
+        .unwrap();
+        let mut chapter = warnings.chapter(Some(Path::new("chapter.md")), "");
+        chapter.admonition(&Directive::Warning);
+        chapter.admonition(&Directive::Warning);
+        chapter.admonition(&Directive::Tip);
+        chapter.finish();
+
+        assert_eq!(
+            warnings.unique(),
+            vec![
+                (
+                    WarningCode::BudgetExceeded,
+                    "Chapter 'chapter.md' has 3 admonitions, over the budget of 2",
+                    1
+                ),
+                (
+                    WarningCode::BudgetExceeded,
+                    "Chapter 'chapter.md' has 2 'warning' admonitions, over the budget of 1",
+                    1
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn deny_warnings() {
+        let mut warnings = Warnings::from_config(
+            &WarningsConfig {
+                deny: true,
+                ..Default::default()
+            },
+            &CustomDirectives::default(),
+        )
+        .unwrap();
+        assert!(warnings.ensure_none_denied().is_ok());
+        warnings
+            .chapter(None, "")
+            .warn(WarningCode::InvalidBlock, "Invalid block");
+        assert!(warnings.ensure_none_denied().is_err());
+    }
+
+    #[test]
+    fn diagnostics_have_locations() {
+        let mut warnings = Warnings::default();
+        let mut chapter = warnings.chapter(Some(Path::new("chapter.md")), "");
+        chapter.set_location(Some(Location { line: 3, column: 5 }));
+        chapter.warn(WarningCode::EmptyBlock, "Empty");
+        chapter.warn(WarningCode::EmptyBlock, "Empty");
+        chapter.finish();
+
+        assert_eq!(
+            warnings.into_diagnostics(),
+            vec![
+                Diagnostic {
+                    path: Some(PathBuf::from("chapter.md")),
+                    line: Some(3),
+                    column: Some(5),
+                    code: "empty-block",
+                    message: "Empty".to_owned(),
+                };
+                2
+            ]
+        );
+    }
+}
@@ -0,0 +1,21 @@
+//! Javascript bindings, for building the core to WebAssembly.
+
+use wasm_bindgen::prelude::*;
+
+use crate::process::{process, Config};
+
+/// Render the admonitions in `content`, a markdown document, with `options`
+/// as they would be written in the `[preprocessor.admonish]` table of
+/// `book.toml`.
+///
+/// Exported to javascript as `process(content, options)`. There is no
+/// filesystem, so options can't name files, such as templates.
+#[wasm_bindgen(js_name = process)]
+pub fn process_js(content: &str, options: &str) -> Result<String, JsError> {
+    let config = Config::builder().toml(options).build().map_err(js_error)?;
+    process(content, &config).map_err(js_error)
+}
+
+fn js_error(error: anyhow::Error) -> JsError {
+    JsError::new(&format!("{error:#}"))
+}